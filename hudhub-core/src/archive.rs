@@ -0,0 +1,1551 @@
+//! List and extract the archives the HUDs are published in.
+//! Each format of archive has an extractor. An extractor recognizes the archives of its format, lists their entries,
+//! and extracts them through an [`Extraction`], which applies the same safe-path policy and the same limits to every
+//! format. Supporting a new format means implementing [`ArchiveExtractor`] and registering it in
+//! [`ArchiveExtractors`].
+//! The 7z and rar formats are behind the `sevenz` and `rar` features, enabled by default. The rar extractor uses the
+//! C library unrar, without it hudhub-core is pure Rust.
+
+use crate::vpk::VPK_SIGNATURE;
+use chrono::TimeZone;
+use std::fmt::Debug;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "rar")]
+pub use rar::RarExtractor;
+#[cfg(feature = "sevenz")]
+pub use sevenz::SevenZipExtractor;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveError {
+    #[error("Unsupported archive type.")]
+    UnsupportedArchiveType(PathBuf),
+    #[error("Reading archive failed: '{1}'")]
+    ReadFailed(PathBuf, Box<dyn std::error::Error + Send + Sync>),
+    #[error("Creating directory failed: '{1}'")]
+    CreateDirectoryFailed(PathBuf, std::io::Error),
+    #[error("Failed to write file: '{1}'")]
+    CreateFileFailed(PathBuf, std::io::Error),
+    #[error("Failed to copy file: '{1}'")]
+    CopyFileFailed(PathBuf, std::io::Error),
+    #[error("Extraction aborted, {1}")]
+    LimitExceeded(PathBuf, ExceededLimit),
+    #[error("The archive '{0}' has an entry that could be written outside of its directory: '{1}'")]
+    UnsafeEntry(PathBuf, PathBuf),
+}
+
+/// Limits protecting against archives expanding to much more than they seem to, such as zip bombs.
+/// The sizes are counted while the files are written, the sizes declared by the archive are not trusted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ExtractionLimits {
+    /// The maximum size of all the files extracted.
+    pub max_total_bytes: u64,
+    /// The maximum count of files and directories.
+    pub max_entries: u64,
+    /// The maximum size of one file.
+    pub max_file_bytes: u64,
+    /// The maximum ratio between the size of the files extracted and the size of the archive.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for ExtractionLimits {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: 4 * 1024 * 1024 * 1024,
+            max_entries: 100_000,
+            max_file_bytes: 1024 * 1024 * 1024,
+            max_compression_ratio: 100,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExceededLimit {
+    #[error("the files would take more than {0} bytes")]
+    TotalBytes(u64),
+    #[error("the archive has more than {0} entries")]
+    Entries(u64),
+    #[error("a file would take more than {0} bytes")]
+    FileBytes(u64),
+    #[error("the files would take more than {0} times the size of the archive")]
+    CompressionRatio(u64),
+}
+
+/// A format of archive.
+pub trait ArchiveExtractor: Debug + Send + Sync {
+    /// The extensions of the archives of this format, without the leading dot, such as `tar.gz`.
+    /// The first one is given to the archives downloaded without extension.
+    fn extensions(&self) -> &[&'static str];
+
+    /// True if the first bytes of a file, up to 512, are the signature of this format.
+    fn detect(&self, header: &[u8]) -> bool;
+
+    /// List the entries of an archive from its index, without extracting it.
+    /// The entries an extraction would skip, such as the links, are not listed.
+    fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError>;
+
+    /// Extract the entries of an archive. Each entry is written at the path the extraction gives to its name, and
+    /// skipped if the extraction gives none.
+    fn extract(&self, archive_file_path: &Path, extraction: &mut Extraction) -> Result<(), ArchiveError>;
+}
+
+/// The extractors used to list and extract the archives.
+/// An archive is recognized from its content, and from its extension if no extractor recognizes its content.
+/// The extractors registered last are tried first, so registering an extractor replaces the one of the same format.
+#[derive(Clone, Debug)]
+pub struct ArchiveExtractors {
+    extractors: Vec<Arc<dyn ArchiveExtractor>>,
+}
+
+impl Default for ArchiveExtractors {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut extractors = Self {
+            extractors: vec![
+                Arc::new(ZipExtractor),
+                Arc::new(TarExtractor::new(TarCompression::None)),
+                Arc::new(TarExtractor::new(TarCompression::Gzip)),
+                Arc::new(TarExtractor::new(TarCompression::Xz)),
+                Arc::new(TarExtractor::new(TarCompression::Bzip2)),
+                Arc::new(TarExtractor::new(TarCompression::Zstd)),
+            ],
+        };
+
+        #[cfg(feature = "sevenz")]
+        extractors.register(SevenZipExtractor);
+        #[cfg(feature = "rar")]
+        extractors.register(RarExtractor);
+
+        extractors
+    }
+}
+
+impl ArchiveExtractors {
+    pub fn register(&mut self, extractor: impl ArchiveExtractor + 'static) {
+        self.extractors.push(Arc::new(extractor));
+    }
+
+    /// Find the extractor of an archive, from its content, then from its extension.
+    pub fn find(&self, archive_file_path: &Path) -> Option<&dyn ArchiveExtractor> {
+        let header = read_header(archive_file_path);
+
+        self.iter().find(|extractor| extractor.detect(&header)).or_else(|| {
+            archive_file_path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(|file_name| self.find_by_file_name(file_name))
+        })
+    }
+
+    /// Find the extractor of an archive from the extension of its file name, such as `hud.tar.gz`.
+    pub fn find_by_file_name(&self, file_name: &str) -> Option<&dyn ArchiveExtractor> {
+        let file_name = file_name.to_ascii_lowercase();
+
+        self.iter().find(|extractor| {
+            extractor
+                .extensions()
+                .iter()
+                .any(|extension| file_name.ends_with(&format!(".{}", extension)))
+        })
+    }
+
+    /// The extensions of all the formats supported.
+    pub fn extensions(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.iter().flat_map(|extractor| extractor.extensions().iter().copied())
+    }
+
+    /// List the entries of an archive from its index, without extracting it.
+    pub fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+        self.find_or_fail(archive_file_path)?.list(archive_file_path)
+    }
+
+    /// Extract an archive to a directory.
+    /// If the extraction fails, everything it wrote is removed, the directory is left as it was.
+    pub fn extract(
+        &self,
+        archive_file_path: &Path,
+        destination_directory: impl AsRef<Path>,
+        limits: &ExtractionLimits,
+    ) -> Result<PathBuf, ArchiveError> {
+        self.extract_impl(archive_file_path, destination_directory.as_ref(), None, limits)
+    }
+
+    /// Extract only the entries of an archive under `subtree`, a path as listed by [`ArchiveExtractors::list`],
+    /// such as the directory of one HUD of an archive containing many. The entries keep their path in the archive.
+    pub fn extract_subtree(
+        &self,
+        archive_file_path: &Path,
+        destination_directory: impl AsRef<Path>,
+        subtree: &Path,
+        limits: &ExtractionLimits,
+    ) -> Result<PathBuf, ArchiveError> {
+        self.extract_impl(archive_file_path, destination_directory.as_ref(), Some(subtree), limits)
+    }
+
+    /// Extract the archives found in a directory, down to `max_depth` levels of archives in archives.
+    /// Each archive is replaced by a directory of the same name, so the path of a HUD tells which archive it comes
+    /// from, such as `variants.zip/ahud_dark.7z/ahud_dark`.
+    /// An archive failing to extract is left as is, it may just be a file with an archive extension.
+    pub(crate) fn extract_nested(
+        &self,
+        directory: &Path,
+        excluded_file_path: &Path,
+        max_depth: usize,
+        limits: &ExtractionLimits,
+    ) {
+        if max_depth == 0 {
+            return;
+        }
+
+        let archive_file_paths: Vec<PathBuf> = walkdir::WalkDir::new(directory)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file() && entry.path() != excluded_file_path)
+            .map(|entry| entry.into_path())
+            .filter(|file_path| self.is_nested_archive(file_path))
+            .collect();
+
+        for archive_file_path in archive_file_paths {
+            if self.extract_in_place(&archive_file_path, limits) {
+                self.extract_nested(&archive_file_path, excluded_file_path, max_depth - 1, limits);
+            }
+        }
+    }
+
+    /// True if the file is named like an archive to extract. A .vpk file is a HUD, it is never extracted.
+    pub(crate) fn is_nested_archive(&self, file_path: &Path) -> bool {
+        file_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(|file_name| self.find_by_file_name(file_name))
+            .is_some()
+    }
+
+    fn find_or_fail(&self, archive_file_path: &Path) -> Result<&dyn ArchiveExtractor, ArchiveError> {
+        self.find(archive_file_path)
+            .ok_or_else(|| ArchiveError::UnsupportedArchiveType(archive_file_path.to_path_buf()))
+    }
+
+    fn extract_impl(
+        &self,
+        archive_file_path: &Path,
+        destination_directory: &Path,
+        subtree: Option<&Path>,
+        limits: &ExtractionLimits,
+    ) -> Result<PathBuf, ArchiveError> {
+        let extractor = self.find_or_fail(archive_file_path)?;
+        let existing_paths = list_directory(destination_directory);
+        let mut extraction = Extraction {
+            destination_directory,
+            subtree,
+            budget: ExtractionBudget::new(archive_file_path, limits),
+        };
+        let result = extractor.extract(archive_file_path, &mut extraction);
+
+        if result.is_err() {
+            remove_new_paths(destination_directory, existing_paths);
+        }
+
+        result.map(|_| destination_directory.to_path_buf())
+    }
+
+    /// Replace an archive by a directory of the same name, containing its files.
+    /// Returns false, and leaves the archive as is, if it fails to extract.
+    fn extract_in_place(&self, archive_file_path: &Path, limits: &ExtractionLimits) -> bool {
+        let Some(file_name) = archive_file_path.file_name().and_then(|file_name| file_name.to_str()) else {
+            return false;
+        };
+        let moved_file_path = archive_file_path.with_file_name(format!(".{}.nested", file_name));
+
+        if std::fs::rename(archive_file_path, &moved_file_path).is_err() {
+            return false;
+        }
+
+        let result = std::fs::create_dir(archive_file_path)
+            .map_err(|e| ArchiveError::CreateDirectoryFailed(archive_file_path.to_path_buf(), e))
+            .and_then(|_| self.extract(&moved_file_path, archive_file_path, limits));
+
+        match result {
+            Ok(_) => {
+                let _ = std::fs::remove_file(&moved_file_path);
+
+                true
+            }
+            Err(_) => {
+                let _ = std::fs::remove_dir_all(archive_file_path);
+                let _ = std::fs::rename(&moved_file_path, archive_file_path);
+
+                false
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &dyn ArchiveExtractor> {
+        self.extractors.iter().rev().map(|extractor| extractor.as_ref())
+    }
+}
+
+pub(crate) const VPK_FILE_EXTENSION: &str = "vpk";
+
+/// True if a file is a Valve pak file. It is not extracted, the game loads it as is.
+pub(crate) fn is_vpk_file(file_path: &Path) -> bool {
+    read_header(file_path).starts_with(&VPK_SIGNATURE)
+        || file_path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case(VPK_FILE_EXTENSION))
+}
+
+/// The first bytes of a file, where the formats have their signature.
+fn read_header(file_path: &Path) -> Vec<u8> {
+    let mut header = Vec::with_capacity(512);
+
+    if let Ok(file) = std::fs::File::open(file_path) {
+        let _ = file.take(512).read_to_end(&mut header);
+    }
+
+    header
+}
+
+/// An entry of an archive, as listed without extracting the archive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArchiveEntry {
+    /// Where the entry is extracted, relatively to the destination directory.
+    pub path: PathBuf,
+    pub is_directory: bool,
+    /// The size of the file, as declared by the archive.
+    pub size: u64,
+}
+
+impl ArchiveEntry {
+    /// An entry named as in the archive. Returns none for an entry that would be extracted outside of the destination
+    /// directory, such an entry is never extracted.
+    pub fn new(entry_name: &str, is_directory: bool, size: u64) -> Option<Self> {
+        Some(Self {
+            path: entry_relative_path(entry_name)?,
+            is_directory,
+            size,
+        })
+    }
+}
+
+/// An extraction in progress. It tells the extractor where each entry goes, and counts what is written, stopping the
+/// extraction as soon as a limit is exceeded.
+pub struct Extraction<'a> {
+    destination_directory: &'a Path,
+    subtree: Option<&'a Path>,
+    budget: ExtractionBudget,
+}
+
+impl<'a> Extraction<'a> {
+    pub fn destination_directory(&self) -> &Path {
+        self.destination_directory
+    }
+
+    /// Where an entry is extracted, or none if the entry is skipped.
+    /// This is the policy of every archive type: the entries with an absolute path or a `..` are skipped, and so are
+    /// the entries that would be written through a link already in the destination directory. When only a subtree
+    /// is extracted, the entries outside of it are skipped too.
+    pub fn destination_path(&self, entry_name: &str) -> Option<PathBuf> {
+        entry_destination_path(self.destination_directory, entry_name)
+            .filter(|destination_path| is_in_subtree(destination_path, self.destination_directory, self.subtree))
+    }
+
+    pub fn create_directory(&mut self, directory_path: &Path) -> Result<(), ArchiveError> {
+        self.budget.add_entry()?;
+
+        std::fs::create_dir_all(directory_path)
+            .map_err(|e| ArchiveError::CreateDirectoryFailed(directory_path.to_path_buf(), e))
+    }
+
+    /// Write a file, and its directory if it does not exist. The modification time is set if it is known.
+    pub fn write_file(
+        &mut self,
+        file_path: &Path,
+        reader: &mut dyn Read,
+        modified_time: Option<SystemTime>,
+    ) -> Result<(), ArchiveError> {
+        self.budget.add_entry()?;
+        create_parent_directory(file_path)?;
+        self.budget.write_file(reader, file_path)?;
+        set_modified_time(file_path, modified_time);
+
+        Ok(())
+    }
+}
+
+/// Counts what an extraction wrote, and stops it as soon as a limit is exceeded.
+struct ExtractionBudget {
+    archive_file_path: PathBuf,
+    archive_bytes: u64,
+    limits: ExtractionLimits,
+    entries: u64,
+    total_bytes: u64,
+}
+
+impl ExtractionBudget {
+    fn new(archive_file_path: &Path, limits: &ExtractionLimits) -> Self {
+        Self {
+            archive_file_path: archive_file_path.to_path_buf(),
+            archive_bytes: std::fs::metadata(archive_file_path)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0),
+            limits: *limits,
+            entries: 0,
+            total_bytes: 0,
+        }
+    }
+
+    fn add_entry(&mut self) -> Result<(), ArchiveError> {
+        self.entries += 1;
+
+        match self.entries > self.limits.max_entries {
+            true => Err(self.exceeded(ExceededLimit::Entries(self.limits.max_entries))),
+            false => Ok(()),
+        }
+    }
+
+    /// Check the sizes declared by an archive before extracting it, when the library extracting it writes everything
+    /// at once.
+    #[cfg(feature = "rar")]
+    fn add_declared_file(&mut self, file_bytes: u64) -> Result<(), ArchiveError> {
+        self.add_entry()?;
+        self.total_bytes += file_bytes;
+        self.check_sizes(file_bytes)
+    }
+
+    /// Write a file, chunk by chunk, counting the bytes.
+    fn write_file(&mut self, reader: &mut dyn Read, destination_path: &Path) -> Result<(), ArchiveError> {
+        let mut out_file = std::fs::File::create(destination_path)
+            .map_err(|e| ArchiveError::CreateFileFailed(destination_path.to_path_buf(), e))?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut file_bytes = 0;
+
+        loop {
+            let read_bytes = match reader.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(read_bytes) => read_bytes,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(ArchiveError::CopyFileFailed(destination_path.to_path_buf(), e)),
+            };
+
+            file_bytes += read_bytes as u64;
+            self.total_bytes += read_bytes as u64;
+            self.check_sizes(file_bytes)?;
+            out_file
+                .write_all(&buffer[..read_bytes])
+                .map_err(|e| ArchiveError::CopyFileFailed(destination_path.to_path_buf(), e))?;
+        }
+    }
+
+    fn check_sizes(&self, file_bytes: u64) -> Result<(), ArchiveError> {
+        let limits = &self.limits;
+
+        if file_bytes > limits.max_file_bytes {
+            Err(self.exceeded(ExceededLimit::FileBytes(limits.max_file_bytes)))
+        } else if self.total_bytes > limits.max_total_bytes {
+            Err(self.exceeded(ExceededLimit::TotalBytes(limits.max_total_bytes)))
+        } else if self.total_bytes > self.archive_bytes.saturating_mul(limits.max_compression_ratio) {
+            Err(self.exceeded(ExceededLimit::CompressionRatio(limits.max_compression_ratio)))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn exceeded(&self, limit: ExceededLimit) -> ArchiveError {
+        ArchiveError::LimitExceeded(self.archive_file_path.clone(), limit)
+    }
+}
+
+/// The paths directly in a directory, or none if the directory does not exist.
+fn list_directory(directory: &Path) -> Option<Vec<PathBuf>> {
+    let read_dir = std::fs::read_dir(directory).ok()?;
+
+    Some(read_dir.filter_map(Result::ok).map(|entry| entry.path()).collect())
+}
+
+/// Remove what was written to a directory since it was listed.
+fn remove_new_paths(directory: &Path, existing_paths: Option<Vec<PathBuf>>) {
+    let Some(existing_paths) = existing_paths else {
+        let _ = std::fs::remove_dir_all(directory);
+
+        return;
+    };
+
+    for path in list_directory(directory).unwrap_or_default() {
+        if existing_paths.contains(&path) {
+            continue;
+        }
+
+        let _ = match path.is_dir() && !path.is_symlink() {
+            true => std::fs::remove_dir_all(&path),
+            false => std::fs::remove_file(&path),
+        };
+    }
+}
+
+/// The zip archives.
+#[derive(Debug)]
+pub struct ZipExtractor;
+
+impl ArchiveExtractor for ZipExtractor {
+    fn extensions(&self) -> &[&'static str] {
+        &["zip"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06")
+    }
+
+    fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+        let read_failed = |e: zip::result::ZipError| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e));
+        let archive_file = std::fs::File::open(archive_file_path)
+            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e)))?;
+        let mut archive = zip::ZipArchive::new(archive_file).map_err(read_failed)?;
+        let mut entries = Vec::new();
+
+        for i in 0..archive.len() {
+            // The raw entry is not decompressed.
+            let zip_file = archive.by_index_raw(i).map_err(read_failed)?;
+            let entry_name = zip_entry_name(&zip_file);
+
+            if zip_file.unix_mode().is_some_and(is_unix_link) {
+                continue;
+            }
+
+            entries.extend(ArchiveEntry::new(
+                &entry_name,
+                is_directory_name(&entry_name),
+                zip_file.size(),
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    fn extract(&self, archive_file_path: &Path, extraction: &mut Extraction) -> Result<(), ArchiveError> {
+        let read_failed = |e: zip::result::ZipError| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e));
+        let archive_file = std::fs::File::open(archive_file_path)
+            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e)))?;
+        let mut archive = zip::ZipArchive::new(archive_file).map_err(read_failed)?;
+
+        for i in 0..archive.len() {
+            let mut zip_file = archive.by_index(i).map_err(read_failed)?;
+            let entry_name = zip_entry_name(&zip_file);
+            let Some(destination_path) = extraction.destination_path(&entry_name) else {
+                continue;
+            };
+
+            if zip_file.unix_mode().is_some_and(is_unix_link) {
+                continue;
+            }
+
+            if is_directory_name(&entry_name) {
+                extraction.create_directory(&destination_path)?;
+            } else {
+                let modified_time = zip_modified_time(&zip_file);
+
+                extraction.write_file(&destination_path, &mut zip_file, modified_time)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The identifier of the field of the Info-ZIP extensions storing the name of an entry in UTF-8.
+const ZIP_UNICODE_PATH_FIELD: u16 = 0x7075;
+/// The identifier of the field of the Info-ZIP extensions storing the times of an entry in UTC.
+const ZIP_EXTENDED_TIMESTAMP_FIELD: u16 = 0x5455;
+
+/// The name of a zip entry. The archivers on Windows often write the name in the code page of the system,
+/// without saying so. Some add the name in UTF-8 in an extra field, otherwise the name is decoded as CP437,
+/// the code page of the zip format, unless it is valid UTF-8.
+fn zip_entry_name(zip_file: &zip::read::ZipFile) -> String {
+    let raw_name = zip_file.name_raw();
+    let unicode_name = zip_extra_fields(zip_file.extra_data())
+        .find(|(id, _)| *id == ZIP_UNICODE_PATH_FIELD)
+        .and_then(|(_, data)| {
+            // A version, the CRC-32 of the name it replaces, then the name.
+            let version = *data.first()?;
+            let name_crc = u32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+            let name = std::str::from_utf8(data.get(5..)?).ok()?;
+
+            (version == 1 && name_crc == crc32fast::hash(raw_name)).then(|| name.to_string())
+        });
+
+    unicode_name.unwrap_or_else(|| decode_entry_name(raw_name))
+}
+
+/// The modification time of a zip entry. It is in UTC in the extended timestamp field when there is one,
+/// otherwise it is the date of the entry, in local time.
+fn zip_modified_time(zip_file: &zip::read::ZipFile) -> Option<SystemTime> {
+    let extended_timestamp = zip_extra_fields(zip_file.extra_data())
+        .find(|(id, _)| *id == ZIP_EXTENDED_TIMESTAMP_FIELD)
+        .and_then(|(_, data)| {
+            // Flags telling which times follow, the modification time first.
+            let flags = *data.first()?;
+            let seconds = i32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+
+            (flags & 1 != 0).then_some(seconds)
+        });
+
+    if let Some(seconds) = extended_timestamp {
+        return Some(SystemTime::UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?));
+    }
+
+    let date = zip_file.last_modified();
+
+    dos_modified_time(u32::from(date.datepart()) << 16 | u32::from(date.timepart()))
+}
+
+/// A modification time in the format of MS-DOS, used by the zip and rar entries: the date in the 16 high bits, and
+/// the time, in local time, in the 16 low bits.
+fn dos_modified_time(date_time: u32) -> Option<SystemTime> {
+    let (date, time) = (date_time >> 16, date_time & 0xFFFF);
+    let date_time = chrono::NaiveDate::from_ymd_opt(1980 + (date >> 9) as i32, (date >> 5) & 0xF, date & 0x1F)?
+        .and_hms_opt(time >> 11, (time >> 5) & 0x3F, (time & 0x1F) * 2)?;
+
+    Some(chrono::Local.from_local_datetime(&date_time).earliest()?.into())
+}
+
+/// The fields of the extra data of a zip entry, with their identifier.
+fn zip_extra_fields(mut extra_data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let id = u16::from_le_bytes(extra_data.get(0..2)?.try_into().ok()?);
+        let size = u16::from_le_bytes(extra_data.get(2..4)?.try_into().ok()?) as usize;
+        let data = extra_data.get(4..4 + size)?;
+
+        extra_data = &extra_data[4 + size..];
+
+        Some((id, data))
+    })
+}
+
+/// The compression of a tarball.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TarCompression {
+    None,
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+}
+
+/// The tarballs, one extractor per compression.
+#[derive(Debug)]
+pub struct TarExtractor {
+    compression: TarCompression,
+}
+
+impl TarExtractor {
+    pub fn new(compression: TarCompression) -> Self {
+        Self { compression }
+    }
+
+    fn open(&self, archive_file_path: &Path) -> std::io::Result<tar::Archive<Box<dyn Read>>> {
+        let archive_file = std::fs::File::open(archive_file_path)?;
+        let reader: Box<dyn Read> = match self.compression {
+            TarCompression::None => Box::new(archive_file),
+            TarCompression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(archive_file)),
+            TarCompression::Xz => Box::new(xz2::read::XzDecoder::new(archive_file)),
+            TarCompression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(archive_file)),
+            TarCompression::Zstd => Box::new(zstd::stream::read::Decoder::new(archive_file)?),
+        };
+
+        Ok(tar::Archive::new(reader))
+    }
+}
+
+impl ArchiveExtractor for TarExtractor {
+    /// The short extensions are the ones some tools give to compressed tarballs.
+    fn extensions(&self) -> &[&'static str] {
+        match self.compression {
+            TarCompression::None => &["tar"],
+            TarCompression::Gzip => &["tar.gz", "tgz"],
+            TarCompression::Xz => &["tar.xz", "txz"],
+            TarCompression::Bzip2 => &["tar.bz2", "tbz2"],
+            TarCompression::Zstd => &["tar.zst", "tzst"],
+        }
+    }
+
+    /// A compressed tarball is recognized from the signature of its compression.
+    fn detect(&self, header: &[u8]) -> bool {
+        const TAR_MAGIC_OFFSET: usize = 257;
+
+        match self.compression {
+            TarCompression::None => header.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5) == Some(b"ustar"),
+            TarCompression::Gzip => header.starts_with(&[0x1F, 0x8B]),
+            TarCompression::Xz => header.starts_with(b"\xFD7zXZ\x00"),
+            TarCompression::Bzip2 => header.starts_with(b"BZh"),
+            TarCompression::Zstd => header.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]),
+        }
+    }
+
+    /// A tarball has no index, the entries are read one after the other, but nothing is written.
+    fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+        let read_failed = |e: std::io::Error| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e));
+        let mut archive = self.open(archive_file_path).map_err(read_failed)?;
+        let mut entries = Vec::new();
+
+        for entry in archive.entries().map_err(read_failed)? {
+            let entry = entry.map_err(read_failed)?;
+            let entry_name = decode_entry_name(&entry.path_bytes());
+            let is_directory = match entry.header().entry_type() {
+                tar::EntryType::Directory => true,
+                tar::EntryType::Regular | tar::EntryType::Continuous => false,
+                _ => continue,
+            };
+
+            entries.extend(ArchiveEntry::new(&entry_name, is_directory, entry.size()));
+        }
+
+        Ok(entries)
+    }
+
+    fn extract(&self, archive_file_path: &Path, extraction: &mut Extraction) -> Result<(), ArchiveError> {
+        let read_failed = |e: std::io::Error| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e));
+        let mut archive = self.open(archive_file_path).map_err(read_failed)?;
+
+        for entry in archive.entries().map_err(read_failed)? {
+            let mut entry = entry.map_err(read_failed)?;
+            let entry_name = decode_entry_name(&entry.path_bytes());
+            let Some(destination_path) = extraction.destination_path(&entry_name) else {
+                continue;
+            };
+
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => extraction.create_directory(&destination_path)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let modified_time = entry.header().mtime().ok();
+
+                    extraction.write_file(
+                        &destination_path,
+                        &mut entry,
+                        modified_time.map(|seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)),
+                    )?;
+                }
+                // Links and special files are never extracted, a link could point outside of the destination.
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The characters of the bytes 0x80 to 0xFF in CP437, the code page of the zip format and of DOS.
+const CP437_HIGH_CHARACTERS: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■ ";
+
+/// Decode the name of an entry, as UTF-8 if it is valid, or as CP437.
+fn decode_entry_name(raw_name: &[u8]) -> String {
+    match std::str::from_utf8(raw_name) {
+        Ok(name) => name.to_string(),
+        Err(_) => raw_name
+            .iter()
+            .map(|&byte| match byte {
+                0..=0x7F => byte as char,
+                _ => CP437_HIGH_CHARACTERS.chars().nth((byte - 0x80) as usize).unwrap_or('_'),
+            })
+            .collect(),
+    }
+}
+
+fn is_directory_name(entry_name: &str) -> bool {
+    entry_name.ends_with(['/', '\\'])
+}
+
+/// Create the directory of a file. Many archives have no entry for the directories.
+fn create_parent_directory(file_path: &Path) -> Result<(), ArchiveError> {
+    match file_path.parent() {
+        Some(parent_directory) => std::fs::create_dir_all(parent_directory)
+            .map_err(|e| ArchiveError::CreateDirectoryFailed(parent_directory.to_path_buf(), e)),
+        None => Ok(()),
+    }
+}
+
+/// The modification times are informative, failing to set one does not fail the extraction.
+fn set_modified_time(file_path: &Path, modified_time: Option<SystemTime>) {
+    if let Some(modified_time) = modified_time {
+        let _ = filetime::set_file_mtime(file_path, filetime::FileTime::from_system_time(modified_time));
+    }
+}
+
+/// Whether an entry is extracted, when only a subtree of the archive is extracted.
+fn is_in_subtree(destination_path: &Path, destination_directory: &Path, subtree: Option<&Path>) -> bool {
+    match subtree {
+        Some(subtree) => destination_path.starts_with(destination_directory.join(subtree)),
+        None => true,
+    }
+}
+
+/// Where an entry is extracted, or none if it could be written outside of the destination directory, because its
+/// path is absolute, has a `..`, or goes through a link already in the destination directory.
+fn entry_destination_path(destination_directory: &Path, entry_name: &str) -> Option<PathBuf> {
+    let mut destination_path = destination_directory.to_path_buf();
+
+    for component in entry_relative_path(entry_name)?.components() {
+        destination_path.push(component);
+
+        if destination_path
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink())
+        {
+            return None;
+        }
+    }
+
+    Some(destination_path)
+}
+
+/// The path of an entry relative to the destination directory, or none if it would be written outside of it.
+fn entry_relative_path(entry_name: &str) -> Option<PathBuf> {
+    // The archives made on Windows may separate the directories with backslashes.
+    enclosed_path(Path::new(&entry_name.replace('\\', "/")))
+}
+
+fn enclosed_path(entry_path: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+
+    for component in entry_path.components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    path.components().next().is_some().then_some(path)
+}
+
+/// The mask of the type of file in a Unix mode, as stored by the archives created on Unix.
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+const UNIX_SYMLINK: u32 = 0o120000;
+/// The Windows attribute of the symbolic links and the junctions.
+#[cfg(any(feature = "rar", feature = "sevenz"))]
+const WINDOWS_REPARSE_POINT: u32 = 0x400;
+
+/// Links are never extracted, a link could point outside of the destination directory.
+fn is_unix_link(mode: u32) -> bool {
+    mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK
+}
+
+#[cfg(feature = "sevenz")]
+mod sevenz {
+    use super::{is_unix_link, ArchiveEntry, ArchiveError, ArchiveExtractor, Extraction, WINDOWS_REPARSE_POINT};
+    use std::cell::RefCell;
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    /// Set by the archivers storing a Unix mode in the 16 high bits of the Windows attributes.
+    const WINDOWS_UNIX_EXTENSION: u32 = 0x8000;
+
+    /// The 7z archives.
+    #[derive(Debug)]
+    pub struct SevenZipExtractor;
+
+    impl ArchiveExtractor for SevenZipExtractor {
+        fn extensions(&self) -> &[&'static str] {
+            &["7z"]
+        }
+
+        fn detect(&self, header: &[u8]) -> bool {
+            header.starts_with(b"7z\xBC\xAF\x27\x1C")
+        }
+
+        fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+            let read_failed = |e: sevenz_rust::Error| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e));
+            let archive =
+                sevenz_rust::SevenZReader::open(archive_file_path, sevenz_rust::Password::empty()).map_err(read_failed)?;
+
+            // The entries are read from the archive header, so their data is not decompressed.
+            let entries = archive
+                .archive()
+                .files
+                .iter()
+                .filter(|entry| !(entry.has_windows_attributes && is_windows_link(entry.windows_attributes())))
+                .filter_map(|entry| ArchiveEntry::new(entry.name(), entry.is_directory(), entry.size()))
+                .collect();
+
+            Ok(entries)
+        }
+
+        fn extract(&self, archive_file_path: &Path, extraction: &mut Extraction) -> Result<(), ArchiveError> {
+            let destination_directory = extraction.destination_directory;
+            // The callback can not return an error of ours, so the error stopping the extraction is kept aside.
+            let extraction = RefCell::new(extraction);
+            let aborted = RefCell::new(None);
+            let result = sevenz_rust::decompress_file_with_extract_fn(
+                archive_file_path,
+                destination_directory,
+                |entry, reader, _| {
+                    let mut extraction = extraction.borrow_mut();
+                    let is_link = entry.has_windows_attributes && is_windows_link(entry.windows_attributes());
+                    let Some(destination_path) = extraction.destination_path(entry.name()).filter(|_| !is_link) else {
+                        // The data of the skipped entries is read anyway, the next entries may follow it in the stream.
+                        return std::io::copy(reader, &mut std::io::sink())
+                            .map(|_| true)
+                            .map_err(sevenz_rust::Error::io);
+                    };
+                    let result = match entry.is_directory() {
+                        true => extraction.create_directory(&destination_path),
+                        false => extraction.write_file(
+                            &destination_path,
+                            reader,
+                            entry
+                                .has_last_modified_date
+                                .then(|| SystemTime::from(entry.last_modified_date())),
+                        ),
+                    };
+
+                    result.map(|_| true).map_err(|error| {
+                        *aborted.borrow_mut() = Some(error);
+
+                        sevenz_rust::Error::other("extraction aborted")
+                    })
+                },
+            );
+
+            match (aborted.into_inner(), result) {
+                (Some(error), _) => Err(error),
+                (None, Err(e)) => Err(ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e))),
+                (None, Ok(())) => Ok(()),
+            }
+        }
+    }
+
+    fn is_windows_link(attributes: u32) -> bool {
+        attributes & WINDOWS_REPARSE_POINT != 0
+            || (attributes & WINDOWS_UNIX_EXTENSION != 0 && is_unix_link(attributes >> 16))
+    }
+}
+
+#[cfg(feature = "rar")]
+mod rar {
+    use super::{
+        decode_entry_name, dos_modified_time, entry_relative_path, is_unix_link, ArchiveEntry, ArchiveError,
+        ArchiveExtractor, Extraction, ExtractionBudget, WINDOWS_REPARSE_POINT,
+    };
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("Failed to unrar archive: {0}")]
+    struct RarError(String);
+
+    /// The rar archives, extracted by the C library unrar.
+    /// The library extracts all the entries at once, so the archive is extracted to a temporary directory first, once
+    /// the sizes it declares are checked against the limits. The library writes no more than these sizes. Then the
+    /// files are moved through the extraction, like the entries of the other formats.
+    #[derive(Debug)]
+    pub struct RarExtractor;
+
+    impl ArchiveExtractor for RarExtractor {
+        fn extensions(&self) -> &[&'static str] {
+            &["rar"]
+        }
+
+        fn detect(&self, header: &[u8]) -> bool {
+            header.starts_with(b"Rar!\x1A\x07")
+        }
+
+        fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+            Ok(read_entries(archive_file_path)?
+                .iter()
+                .filter(|entry| !is_rar_link(entry.file_attr))
+                .filter_map(|entry| {
+                    ArchiveEntry::new(
+                        &rar_entry_name(&entry.filename),
+                        entry.is_directory(),
+                        entry.unpacked_size as u64,
+                    )
+                })
+                .collect())
+        }
+
+        fn extract(&self, archive_file_path: &Path, extraction: &mut Extraction) -> Result<(), ArchiveError> {
+            let entries = read_entries(archive_file_path)?;
+            let mut declared_budget = ExtractionBudget::new(archive_file_path, &extraction.budget.limits);
+            let mut extracted_entries = HashMap::new();
+
+            for entry in entries.iter() {
+                // The library writes every entry to the temporary directory, even the ones skipped afterwards.
+                declared_budget.add_declared_file(entry.unpacked_size as u64)?;
+
+                if !is_rar_link(entry.file_attr) {
+                    extracted_entries
+                        .extend(entry_relative_path(&rar_entry_name(&entry.filename)).map(|entry_path| (entry_path, entry)));
+                }
+            }
+
+            let destination_directory = extraction.destination_directory;
+            let temporary_directory = std::fs::create_dir_all(destination_directory)
+                .and_then(|_| tempdir::TempDir::new_in(destination_directory, ".rar"))
+                .map_err(|e| ArchiveError::CreateDirectoryFailed(destination_directory.to_path_buf(), e))?;
+            let rar_error = |e| rar_error(archive_file_path, e);
+
+            unrar::Archive::new(archive_file_path)
+                .map_err(rar_error)?
+                .extract_to(temporary_directory.path())
+                .map_err(rar_error)?
+                .process()
+                .map_err(rar_error)?;
+
+            // Only the files of the entries kept above are moved, the links the library created are never followed.
+            for file in walkdir::WalkDir::new(temporary_directory.path())
+                .min_depth(1)
+                .into_iter()
+                .filter_map(Result::ok)
+            {
+                let file_path = file.path();
+                let entry_name =
+                    extracted_entry_name(file_path.strip_prefix(temporary_directory.path()).unwrap_or(file_path));
+                let Some(entry) = entry_relative_path(&entry_name).and_then(|entry_path| extracted_entries.get(&entry_path))
+                else {
+                    continue;
+                };
+                let Some(destination_path) = extraction.destination_path(&entry_name) else {
+                    continue;
+                };
+
+                if file.file_type().is_dir() {
+                    extraction.create_directory(&destination_path)?;
+                } else if file.file_type().is_file() {
+                    let mut extracted_file = std::fs::File::open(file_path)
+                        .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e)))?;
+
+                    extraction.write_file(&destination_path, &mut extracted_file, dos_modified_time(entry.file_time))?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn read_entries(archive_file_path: &Path) -> Result<Vec<unrar::Entry>, ArchiveError> {
+        let rar_error = |e| rar_error(archive_file_path, e);
+
+        unrar::Archive::new(archive_file_path)
+            .map_err(rar_error)?
+            .list()
+            .map_err(rar_error)?
+            .process()
+            .map_err(rar_error)
+    }
+
+    /// The attributes of a rar entry are the Unix mode or the Windows attributes, depending on where it was made.
+    fn is_rar_link(file_attributes: u32) -> bool {
+        is_unix_link(file_attributes) || file_attributes & WINDOWS_REPARSE_POINT != 0
+    }
+
+    /// The first of the characters the library gives to the bytes of a name it can not decode, such as a name in the
+    /// code page of the Windows system that made the archive. The byte 0x80 is U+E080.
+    const UNDECODED_BYTES_START: u32 = 0xE000;
+
+    /// The name of an entry as listed by the library, decoded like the names of the other formats.
+    fn rar_entry_name(file_name: &Path) -> String {
+        let mut raw_name = Vec::new();
+
+        for character in file_name.to_string_lossy().chars() {
+            match u32::from(character) {
+                code @ 0xE080..=0xE0FF => raw_name.push((code - UNDECODED_BYTES_START) as u8),
+                _ => raw_name.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+
+        decode_entry_name(&raw_name)
+    }
+
+    /// The name of an entry as extracted by the library, relatively to the directory it was extracted to.
+    /// The library writes the bytes of the names it can not decode as they are.
+    #[cfg(unix)]
+    fn extracted_entry_name(extracted_path: &Path) -> String {
+        use std::os::unix::ffi::OsStrExt;
+
+        decode_entry_name(extracted_path.as_os_str().as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    fn extracted_entry_name(extracted_path: &Path) -> String {
+        extracted_path.to_string_lossy().into_owned()
+    }
+
+    fn rar_error(archive_file_path: &Path, error: impl std::fmt::Display) -> ArchiveError {
+        ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(RarError(error.to_string())))
+    }
+}
+
+/// The path of an archive of the test fixtures.
+#[cfg(test)]
+pub(crate) fn archive_fixture(file_name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join("archives")
+        .join(file_name)
+}
+
+/// Create a zip of files and their content. A name ending with `/` is a directory, its content is ignored.
+#[cfg(test)]
+pub(crate) fn create_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+
+    for (file_name, content) in files {
+        match file_name.strip_suffix('/') {
+            Some(directory) => writer.add_directory(directory, zip::write::FileOptions::default()).unwrap(),
+            None => {
+                writer.start_file(*file_name, zip::write::FileOptions::default()).unwrap();
+                writer.write_all(content).unwrap();
+            }
+        }
+    }
+
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        archive_fixture, create_zip, is_vpk_file, ArchiveEntry, ArchiveError, ArchiveExtractor, ArchiveExtractors,
+        ExceededLimit, Extraction, ExtractionLimits,
+    };
+    use crate::{fetch_package_with, FetchSettings, HudName, Source};
+    use chrono::TimeZone;
+    use std::io::BufRead;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+    use tempdir::TempDir;
+    use test_case::test_case;
+
+    /// A text file listing the files of the archive, one per line. The files are empty.
+    #[derive(Debug)]
+    struct FileListExtractor;
+
+    impl FileListExtractor {
+        fn file_names(archive_file_path: &Path) -> Result<Vec<String>, ArchiveError> {
+            let read_failed = |e: std::io::Error| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e));
+            let archive_file = std::fs::File::open(archive_file_path).map_err(read_failed)?;
+
+            std::io::BufReader::new(archive_file)
+                .lines()
+                .skip(1)
+                .collect::<Result<_, _>>()
+                .map_err(read_failed)
+        }
+    }
+
+    impl ArchiveExtractor for FileListExtractor {
+        fn extensions(&self) -> &[&'static str] {
+            &["files"]
+        }
+
+        fn detect(&self, header: &[u8]) -> bool {
+            header.starts_with(b"FILES\n")
+        }
+
+        fn list(&self, archive_file_path: &Path) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+            Ok(Self::file_names(archive_file_path)?
+                .iter()
+                .filter_map(|file_name| ArchiveEntry::new(file_name, false, 0))
+                .collect())
+        }
+
+        fn extract(&self, archive_file_path: &Path, extraction: &mut Extraction) -> Result<(), ArchiveError> {
+            for file_name in Self::file_names(archive_file_path)? {
+                if let Some(destination_path) = extraction.destination_path(&file_name) {
+                    extraction.write_file(&destination_path, &mut std::io::empty(), None)?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn extension_found(extractors: &ArchiveExtractors, file_path: &Path) -> Option<&'static str> {
+        extractors
+            .find(file_path)
+            .and_then(|extractor| extractor.extensions().first().copied())
+    }
+
+    #[test_case(b"PK\x03\x04rest", "bin", Some("zip") ; "zip")]
+    #[cfg_attr(feature = "sevenz", test_case(b"7z\xBC\xAF\x27\x1Crest", "zip", Some("7z") ; "7z named zip"))]
+    #[cfg_attr(feature = "rar", test_case(b"Rar!\x1A\x07\x00rest", "", Some("rar") ; "rar without extension"))]
+    #[test_case(b"\x1F\x8Brest", "", Some("tar.gz") ; "gzip")]
+    #[test_case(b"\xFD7zXZ\x00rest", "zip", Some("tar.xz") ; "xz named zip")]
+    #[test_case(b"BZh91AY", "", Some("tar.bz2") ; "bzip2")]
+    #[test_case(b"\x28\xB5\x2F\xFDrest", "", Some("tar.zst") ; "zstd")]
+    #[test_case(b"", "tar.gz", Some("tar.gz") ; "unknown content named tar gz")]
+    #[test_case(b"", "TGZ", Some("tar.gz") ; "unknown content named tgz")]
+    #[test_case(b"\x34\x12\xAA\x55rest", "", None ; "vpk")]
+    #[test_case(b"unknown", "", None ; "unknown")]
+    fn test_find_extractor(content: &[u8], extension: &str, expected: Option<&str>) {
+        let directory = TempDir::new("test_find_extractor").unwrap();
+        let file_path = directory.path().join("archive").with_extension(extension);
+        std::fs::write(&file_path, content).unwrap();
+
+        assert_eq!(expected, extension_found(&ArchiveExtractors::default(), &file_path));
+    }
+
+    #[test]
+    fn test_find_tar_extractor() {
+        let directory = TempDir::new("test_find_tar_extractor").unwrap();
+        let file_path = directory.path().join("archive");
+        let mut content = vec![0u8; 512];
+        content[257..262].copy_from_slice(b"ustar");
+        std::fs::write(&file_path, content).unwrap();
+
+        assert_eq!(Some("tar"), extension_found(&ArchiveExtractors::default(), &file_path));
+    }
+
+    #[test_case(b"\x34\x12\xAA\x55rest", "", true ; "signature")]
+    #[test_case(b"", "vpk", true ; "unknown content with extension")]
+    #[test_case(b"PK\x03\x04rest", "zip", false ; "zip")]
+    fn test_is_vpk_file(content: &[u8], extension: &str, expected: bool) {
+        let directory = TempDir::new("test_is_vpk_file").unwrap();
+        let file_path = directory.path().join("hud").with_extension(extension);
+        std::fs::write(&file_path, content).unwrap();
+
+        assert_eq!(expected, is_vpk_file(&file_path));
+    }
+
+    #[test]
+    fn test_registered_extractor_is_tried_first() {
+        let directory = TempDir::new("test_registered_extractor_is_tried_first").unwrap();
+        let file_path = directory.path().join("hud.files");
+        std::fs::write(&file_path, b"PK\x03\x04").unwrap();
+        let mut extractors = ArchiveExtractors::default();
+
+        assert_eq!(Some("zip"), extension_found(&extractors, &file_path));
+
+        extractors.register(FileListExtractor);
+        std::fs::write(&file_path, b"FILES\n").unwrap();
+
+        assert_eq!(Some("files"), extension_found(&extractors, &file_path));
+        assert!(extractors.is_nested_archive(Path::new("variants/hud.files")));
+        assert!(extractors.extensions().any(|extension| extension == "files"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_package_with_registered_extractor() {
+        let directory = TempDir::new("test_fetch_package_with_registered_extractor").unwrap();
+        let archive_file_path = directory.path().join("huds.files");
+        std::fs::write(&archive_file_path, "FILES\nahud/info.vdf\n../outside.txt\nbhud\\info.vdf\n").unwrap();
+        let mut settings = FetchSettings::default();
+        settings.extractors.register(FileListExtractor);
+
+        let listed_paths: Vec<PathBuf> = settings
+            .extractors
+            .list(&archive_file_path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        let package = fetch_package_with(Source::LocalFile(archive_file_path), directory.path(), &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(vec![Path::new("ahud/info.vdf"), Path::new("bhud/info.vdf")], listed_paths);
+        assert_eq!(
+            vec![&HudName::new("ahud"), &HudName::new("bhud")],
+            package.hud_names().collect::<Vec<_>>()
+        );
+        assert!(!directory.path().parent().unwrap().join("outside.txt").exists());
+    }
+
+    #[test_case("ahud.tar" ; "tar")]
+    #[test_case("ahud.tar.gz" ; "gzip")]
+    #[test_case("ahud.tar.xz" ; "xz")]
+    #[test_case("ahud.tar.bz2" ; "bzip2")]
+    #[test_case("ahud.tar.zst" ; "zstd")]
+    fn test_extract_tarball(file_name: &str) {
+        let directory = TempDir::new("test_extract_tarball").unwrap();
+
+        ArchiveExtractors::default()
+            .extract(&archive_fixture(file_name), directory.path(), &Default::default())
+            .unwrap();
+
+        let hud_directory_path = directory.path().join("ahud");
+
+        assert!(hud_directory_path.join("info.vdf").is_file());
+        assert!(hud_directory_path.join("resource").join("ui").join("hudlayout.res").is_file());
+    }
+
+    #[test_case("malicious.tar" ; "tar")]
+    #[test_case("malicious.zip" ; "zip")]
+    #[cfg_attr(feature = "sevenz", test_case("malicious.7z" ; "seven zip"))]
+    #[cfg_attr(feature = "rar", test_case("malicious.rar" ; "rar"))]
+    fn test_extract_archive_with_unsafe_entries(file_name: &str) {
+        let directory = TempDir::new("test_extract_archive_with_unsafe_entries").unwrap();
+        let destination_directory = directory.path().join("destination");
+        std::fs::create_dir(&destination_directory).unwrap();
+
+        ArchiveExtractors::default()
+            .extract(&archive_fixture(file_name), &destination_directory, &Default::default())
+            .unwrap();
+
+        assert!(destination_directory.join("ahud").join("info.vdf").is_file());
+        assert!(!directory.path().join("escaped.txt").exists());
+        assert!(!destination_directory.join("absolute.txt").exists());
+        assert!(!destination_directory.join("ahud").join("hardlink").exists());
+
+        let link_path = destination_directory.join("ahud").join("link");
+
+        assert!(!link_path.is_symlink());
+        assert!(!link_path.is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_archive_through_existing_link() {
+        let directory = TempDir::new("test_extract_archive_through_existing_link").unwrap();
+        let outside_directory = directory.path().join("outside");
+        let destination_directory = directory.path().join("destination");
+        std::fs::create_dir(&outside_directory).unwrap();
+        std::fs::create_dir(&destination_directory).unwrap();
+        std::os::unix::fs::symlink(&outside_directory, destination_directory.join("ahud")).unwrap();
+        let archive_file_path = directory.path().join("ahud.zip");
+        std::fs::write(&archive_file_path, create_zip(&[("ahud/", b""), ("ahud/info.vdf", b"")])).unwrap();
+
+        let _ = ArchiveExtractors::default().extract(&archive_file_path, &destination_directory, &Default::default());
+
+        assert!(!outside_directory.join("info.vdf").exists());
+    }
+
+    #[test_case("huds.zip" ; "zip")]
+    #[cfg_attr(feature = "sevenz", test_case("huds.7z" ; "seven zip"))]
+    #[cfg_attr(feature = "rar", test_case("huds.rar" ; "rar"))]
+    #[test_case("huds.tar.gz" ; "tarball")]
+    fn test_list_archive(file_name: &str) {
+        let directory = TempDir::new("test_list_archive").unwrap();
+
+        let mut file_paths: Vec<PathBuf> = ArchiveExtractors::default()
+            .list(&archive_fixture(file_name))
+            .unwrap()
+            .into_iter()
+            .filter(|entry| !entry.is_directory)
+            .map(|entry| entry.path)
+            .collect();
+
+        file_paths.sort();
+
+        assert_eq!(
+            vec![
+                Path::new("pack/ahud/info.vdf"),
+                Path::new("pack/ahud/resource/ui/hudlayout.res"),
+                Path::new("pack/bhud/info.vdf"),
+                Path::new("pack/chud.vpk"),
+            ],
+            file_paths
+        );
+        assert_eq!(0, std::fs::read_dir(directory.path()).unwrap().count());
+    }
+
+    #[test_case("huds.zip" ; "zip")]
+    #[cfg_attr(feature = "sevenz", test_case("huds.7z" ; "seven zip"))]
+    #[cfg_attr(feature = "rar", test_case("huds.rar" ; "rar"))]
+    #[test_case("huds.tar.gz" ; "tarball")]
+    fn test_extract_subtree(file_name: &str) {
+        let directory = TempDir::new("test_extract_subtree").unwrap();
+
+        ArchiveExtractors::default()
+            .extract_subtree(
+                &archive_fixture(file_name),
+                directory.path(),
+                Path::new("pack/ahud"),
+                &Default::default(),
+            )
+            .unwrap();
+
+        let pack_directory = directory.path().join("pack");
+
+        assert_eq!(vec![pack_directory.join("ahud")], directory_paths(&pack_directory));
+        assert!(pack_directory.join("ahud").join("info.vdf").is_file());
+        assert!(pack_directory
+            .join("ahud")
+            .join("resource")
+            .join("ui")
+            .join("hudlayout.res")
+            .is_file());
+    }
+
+    fn modified_time(file_path: &Path) -> SystemTime {
+        std::fs::metadata(file_path).unwrap().modified().unwrap()
+    }
+
+    fn utc_time() -> SystemTime {
+        chrono::Utc.with_ymd_and_hms(2020, 5, 17, 12, 34, 56).unwrap().into()
+    }
+
+    /// The date of a zip or rar entry is in local time.
+    fn local_time() -> SystemTime {
+        chrono::Local.with_ymd_and_hms(2020, 5, 17, 12, 34, 56).unwrap().into()
+    }
+
+    #[test_case("windows.zip", local_time() ; "zip")]
+    #[test_case("windows.tar", utc_time() ; "tar")]
+    #[cfg_attr(feature = "sevenz", test_case("windows.7z", utc_time() ; "seven zip"))]
+    #[cfg_attr(feature = "rar", test_case("windows.rar", local_time() ; "rar"))]
+    fn test_extract_archive_made_on_windows(file_name: &str, expected_modified_time: SystemTime) {
+        let directory = TempDir::new("test_extract_archive_made_on_windows").unwrap();
+
+        ArchiveExtractors::default()
+            .extract(&archive_fixture(file_name), directory.path(), &Default::default())
+            .unwrap();
+
+        let hud_directory_path = directory.path().join("ahud");
+        let layout_file_path = hud_directory_path.join("resource").join("ui").join("hudlayout.res");
+
+        assert_eq!(vec![hud_directory_path.clone()], directory_paths(directory.path()));
+        assert!(hud_directory_path.join("info.vdf").is_file());
+        assert!(layout_file_path.is_file());
+        assert!(hud_directory_path.join("resource").join("münchen.res").is_file());
+        assert_eq!(expected_modified_time, modified_time(&layout_file_path));
+    }
+
+    #[test]
+    fn test_extract_zip_with_unicode_path_field() {
+        let directory = TempDir::new("test_extract_zip_with_unicode_path_field").unwrap();
+
+        ArchiveExtractors::default()
+            .extract(&archive_fixture("unicode-path.zip"), directory.path(), &Default::default())
+            .unwrap();
+
+        let hud_directory_path = directory.path().join("ahud");
+
+        assert!(hud_directory_path.join("resource").join("шрифт.res").is_file());
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            modified_time(&hud_directory_path.join("info.vdf"))
+        );
+    }
+
+    fn limits(max_total_bytes: u64, max_entries: u64, max_file_bytes: u64) -> ExtractionLimits {
+        ExtractionLimits {
+            max_total_bytes,
+            max_entries,
+            max_file_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// The paths directly in a directory.
+    fn directory_paths(directory: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    #[test_case(limits(100, 2, 100), ExceededLimit::Entries(2) ; "entries")]
+    #[test_case(limits(100, 100, 10), ExceededLimit::FileBytes(10) ; "file size")]
+    #[test_case(limits(15, 100, 100), ExceededLimit::TotalBytes(15) ; "total size")]
+    fn test_extract_archive_exceeding_limit(limits: ExtractionLimits, expected_limit: ExceededLimit) {
+        let archive_directory = TempDir::new("test_extract_archive_exceeding_limit_file").unwrap();
+        let archive_file_path = archive_directory.path().join("ahud.zip");
+        std::fs::write(
+            &archive_file_path,
+            create_zip(&[
+                ("ahud/", b""),
+                ("ahud/info.vdf", b"0123456789a"),
+                ("ahud/readme.txt", b"0123456789"),
+            ]),
+        )
+        .unwrap();
+        let directory = TempDir::new("test_extract_archive_exceeding_limit").unwrap();
+        std::fs::write(directory.path().join("existing.txt"), b"").unwrap();
+
+        let result = ArchiveExtractors::default().extract(&archive_file_path, directory.path(), &limits);
+
+        assert!(matches!(result, Err(ArchiveError::LimitExceeded(_, limit)) if limit == expected_limit));
+        assert_eq!(vec![directory.path().join("existing.txt")], directory_paths(directory.path()));
+    }
+
+    #[cfg_attr(
+        feature = "sevenz",
+        test_case("huds.7z", limits(1000, 3, 1000), ExceededLimit::Entries(3) ; "seven zip entries")
+    )]
+    #[cfg_attr(
+        feature = "sevenz",
+        test_case("huds.7z", limits(1000, 1000, 20), ExceededLimit::FileBytes(20) ; "seven zip file size")
+    )]
+    #[cfg_attr(
+        feature = "sevenz",
+        test_case("huds.7z", limits(100, 1000, 1000), ExceededLimit::TotalBytes(100) ; "seven zip total size")
+    )]
+    #[cfg_attr(
+        feature = "rar",
+        test_case("huds.rar", limits(1000, 3, 1000), ExceededLimit::Entries(3) ; "rar entries")
+    )]
+    #[cfg_attr(
+        feature = "rar",
+        test_case("huds.rar", limits(1000, 1000, 20), ExceededLimit::FileBytes(20) ; "rar file size")
+    )]
+    #[cfg_attr(
+        feature = "rar",
+        test_case("huds.rar", limits(100, 1000, 1000), ExceededLimit::TotalBytes(100) ; "rar total size")
+    )]
+    #[test_case("huds.tar.gz", limits(1000, 3, 1000), ExceededLimit::Entries(3) ; "tarball entries")]
+    fn test_extract_fixture_exceeding_limit(file_name: &str, limits: ExtractionLimits, expected_limit: ExceededLimit) {
+        let directory = TempDir::new("test_extract_fixture_exceeding_limit").unwrap();
+        std::fs::write(directory.path().join("existing.txt"), b"").unwrap();
+
+        let result = ArchiveExtractors::default().extract(&archive_fixture(file_name), directory.path(), &limits);
+
+        assert!(matches!(result, Err(ArchiveError::LimitExceeded(_, limit)) if limit == expected_limit));
+        assert_eq!(vec![directory.path().join("existing.txt")], directory_paths(directory.path()));
+    }
+
+    #[test]
+    fn test_extract_zip_bomb() {
+        let directory = TempDir::new("test_extract_zip_bomb").unwrap();
+        let archive_file_path = directory.path().join("ahud.zip");
+        std::fs::write(
+            &archive_file_path,
+            create_zip(&[
+                ("ahud/", b""),
+                ("ahud/info.vdf", b""),
+                ("ahud/zeros.bin", &vec![0u8; 10_000_000]),
+            ]),
+        )
+        .unwrap();
+
+        let result = ArchiveExtractors::default().extract(&archive_file_path, directory.path(), &Default::default());
+
+        assert!(matches!(
+            result,
+            Err(ArchiveError::LimitExceeded(_, ExceededLimit::CompressionRatio(100)))
+        ));
+        assert_eq!(vec![archive_file_path], directory_paths(directory.path()));
+    }
+
+    #[test]
+    fn test_extract_tarball_exceeding_limit() {
+        let directory = TempDir::new("test_extract_tarball_exceeding_limit").unwrap();
+        let destination_directory = directory.path().join("destination");
+
+        let result = ArchiveExtractors::default().extract(
+            &archive_fixture("ahud.tar.xz"),
+            &destination_directory,
+            &limits(1000, 1000, 1),
+        );
+
+        assert!(matches!(
+            result,
+            Err(ArchiveError::LimitExceeded(_, ExceededLimit::FileBytes(1)))
+        ));
+        assert!(!destination_directory.exists());
+    }
+
+    #[test]
+    fn test_extract_unsupported_archive() {
+        let directory = TempDir::new("test_extract_unsupported_archive").unwrap();
+        let file_path = directory.path().join("huds.files");
+        std::fs::write(&file_path, "FILES\nahud/info.vdf\n").unwrap();
+
+        assert!(matches!(
+            ArchiveExtractors::default().extract(&file_path, directory.path().join("huds"), &Default::default()),
+            Err(ArchiveError::UnsupportedArchiveType(_))
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+//! Fetch many sources at once, such as a list of URLs pasted by the user.
+//! The sources are fetched concurrently, and a source failing does not stop the others.
+
+use crate::download::{DownloadProgress, ProgressSender};
+use crate::source::{fetch_package_with, scan_package_with, FetchError, FetchSettings, Source};
+use crate::Package;
+use futures_util::StreamExt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// How many sources are fetched at once by default.
+pub const DEFAULT_MAX_CONCURRENT_FETCHES: usize = 4;
+
+/// The result of the fetch of one source of a list.
+pub struct FetchOutcome {
+    pub source: Source,
+    pub result: Result<Package, FetchError>,
+}
+
+/// Parse a list of sources, one per line, such as the content of a text file.
+/// Blank lines and lines starting with `#` are ignored, and each source is kept once.
+pub fn parse_source_list(text: &str) -> Vec<Source> {
+    let mut sources = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let source = Source::from_url(line);
+
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+
+    sources
+}
+
+/// Fetch the packages of several sources, at most [`FetchSettings::max_concurrent_fetches`] at once.
+/// Each package is fetched to its own subdirectory of `directory`, which must outlive the packages.
+/// The outcomes are in the order of the sources. The progress sent is the sum of the progress of the downloads.
+pub async fn fetch_packages(
+    sources: Vec<Source>,
+    directory: impl AsRef<Path>,
+    settings: &FetchSettings,
+) -> Vec<FetchOutcome> {
+    SourceFetches::new(sources, settings)
+        .run(directory.as_ref(), fetch_package_with)
+        .await
+}
+
+/// Like [`fetch_packages`], but the archives are only listed, see [`scan_package_with`].
+pub async fn scan_packages(
+    sources: Vec<Source>,
+    directory: impl AsRef<Path>,
+    settings: &FetchSettings,
+) -> Vec<FetchOutcome> {
+    SourceFetches::new(sources, settings)
+        .run(directory.as_ref(), scan_package_with)
+        .await
+}
+
+/// The sources of a list, each fetched with its own settings sending the progress of its download to its own channel.
+/// The progress of the sources fetched at once is summed, instead of being interleaved in the progress sender of the
+/// list.
+struct SourceFetches {
+    sources: Vec<(Source, FetchSettings, UnboundedReceiver<DownloadProgress>)>,
+    progress: ProgressSum,
+    max_concurrent_fetches: usize,
+}
+
+impl SourceFetches {
+    fn new(sources: Vec<Source>, settings: &FetchSettings) -> Self {
+        let progress = ProgressSum {
+            sender: settings.progress_sender.clone(),
+            sources: Mutex::new(vec![None; sources.len()]),
+        };
+        let sources = sources
+            .into_iter()
+            .map(|source| {
+                let (progress_sender, progress_receiver) = tokio::sync::mpsc::unbounded_channel();
+                let settings = FetchSettings {
+                    progress_sender: Some(progress_sender),
+                    ..settings.clone()
+                };
+
+                (source, settings, progress_receiver)
+            })
+            .collect();
+
+        Self {
+            sources,
+            progress,
+            max_concurrent_fetches: settings.max_concurrent_fetches.max(1),
+        }
+    }
+
+    /// Fetch the sources with `fetch`, at most `max_concurrent_fetches` at once, forwarding their progress.
+    async fn run<'a, F, R>(&'a mut self, directory: &'a Path, fetch: F) -> Vec<FetchOutcome>
+    where
+        F: Fn(Source, PathBuf, &'a FetchSettings) -> R + Copy + 'a,
+        R: Future<Output = Result<Package, FetchError>> + 'a,
+    {
+        let progress = &self.progress;
+        let mut fetches = Vec::with_capacity(self.sources.len());
+
+        for (index, (source, settings, progress_receiver)) in self.sources.iter_mut().enumerate() {
+            let settings: &'a FetchSettings = settings;
+
+            fetches.push(async move {
+                let package_directory = directory.join(index.to_string());
+                let result = match tokio::fs::create_dir_all(&package_directory).await {
+                    Ok(()) => {
+                        let fetch = fetch(source.clone(), package_directory, settings);
+
+                        tokio::pin!(fetch);
+
+                        loop {
+                            tokio::select! {
+                                result = &mut fetch => break result,
+                                Some(source_progress) = progress_receiver.recv() => {
+                                    progress.report(index, source_progress);
+                                }
+                            }
+                        }
+                    }
+                    Err(error) => Err(error.into()),
+                };
+
+                while let Ok(source_progress) = progress_receiver.try_recv() {
+                    progress.report(index, source_progress);
+                }
+
+                progress.finish(index);
+
+                FetchOutcome {
+                    source: source.clone(),
+                    result,
+                }
+            });
+        }
+
+        futures_util::stream::iter(fetches)
+            .buffered(self.max_concurrent_fetches)
+            .collect()
+            .await
+    }
+}
+
+/// The progress of the downloads of the sources of a list.
+struct ProgressSum {
+    sender: Option<ProgressSender>,
+    sources: Mutex<Vec<Option<DownloadProgress>>>,
+}
+
+impl ProgressSum {
+    /// Store the progress of a source, and send the sum of the progress of the sources.
+    /// The total is known once the size of every download started is known.
+    fn report(&self, index: usize, source_progress: DownloadProgress) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        let mut sources = self.sources.lock().expect("lock progress");
+
+        sources[index] = Some(source_progress);
+
+        let progress = DownloadProgress {
+            downloaded_bytes: sources.iter().flatten().map(|progress| progress.downloaded_bytes).sum(),
+            total_bytes: sources.iter().flatten().map(|progress| progress.total_bytes).sum(),
+            bytes_per_second: sources.iter().flatten().map(|progress| progress.bytes_per_second).sum(),
+        };
+
+        // Nobody listening to the progress is not a reason to stop downloading.
+        let _ = sender.send(progress);
+    }
+
+    /// A source fetched no longer downloads, but its bytes still count.
+    fn finish(&self, index: usize) {
+        if let Some(progress) = self.sources.lock().expect("lock progress")[index].as_mut() {
+            progress.bytes_per_second = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fetch_packages, parse_source_list};
+    use crate::{FetchError, FetchSettings, HudName, Source};
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_parse_source_list() {
+        let text = "
+            # Team HUDs
+            https://github.com/n0kk/ahud
+
+            https://example.com/hud.zip
+            https://github.com/n0kk/ahud
+        ";
+
+        assert_eq!(
+            vec![
+                Source::from_url("https://github.com/n0kk/ahud"),
+                Source::DownloadUrl("https://example.com/hud.zip".into()),
+            ],
+            parse_source_list(text)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_packages_reports_each_source() {
+        let huds_directory = TempDir::new("test_fetch_packages_huds").unwrap();
+        let directory = TempDir::new("test_fetch_packages").unwrap();
+        let settings = FetchSettings {
+            max_concurrent_fetches: 2,
+            ..Default::default()
+        };
+        let mut sources = Vec::new();
+
+        for name in ["ahud", "bhud", "chud"] {
+            let hud_directory = huds_directory.path().join(name);
+
+            std::fs::create_dir_all(hud_directory.join(name)).unwrap();
+            std::fs::write(hud_directory.join(name).join("info.vdf"), b"").unwrap();
+            sources.push(Source::LocalDirectory(hud_directory));
+        }
+
+        sources.insert(1, Source::LocalDirectory(huds_directory.path().join("missing")));
+
+        let outcomes = fetch_packages(sources.clone(), directory.path(), &settings).await;
+        let hud_names: Vec<Option<HudName>> = outcomes
+            .iter()
+            .map(|outcome| outcome.result.as_ref().ok().map(|package| package.entries[0].name.clone()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                Some(HudName::new("ahud")),
+                None,
+                Some(HudName::new("bhud")),
+                Some(HudName::new("chud"))
+            ],
+            hud_names
+        );
+        assert_eq!(sources[1], outcomes[1].source);
+        assert!(matches!(outcomes[1].result, Err(FetchError::InvalidDirectory(..))));
+    }
+
+    /// A server answering each request with the archive of a HUD, slowly, and counting the requests in flight.
+    /// Returns the server, the archive, and the highest count of requests in flight at once.
+    async fn slow_server(request_count: usize) -> (mockito::ServerGuard, Vec<u8>, Arc<AtomicUsize>) {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file("ahud/info.vdf", zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(b"\"ahud\"\n{\n}").unwrap();
+
+        let archive = writer.finish().unwrap().into_inner();
+        let mut server = mockito::Server::new_async().await;
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let body = archive.clone();
+        let server_max_in_flight = max_in_flight.clone();
+
+        server
+            .mock("GET", mockito::Matcher::Regex(r"^/hud\d+\.zip$".into()))
+            .with_chunked_body(move |writer| {
+                let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+
+                server_max_in_flight.fetch_max(count, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(100));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                writer.write_all(&body)
+            })
+            .expect(request_count)
+            .create_async()
+            .await;
+
+        (server, archive, max_in_flight)
+    }
+
+    fn hud_urls(server: &mockito::ServerGuard, count: usize) -> Vec<Source> {
+        (0..count)
+            .map(|index| Source::DownloadUrl(format!("{}/hud{}.zip", server.url(), index)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_packages_limits_concurrent_fetches() {
+        let (server, _, max_in_flight) = slow_server(6).await;
+        let directory = TempDir::new("test_fetch_packages_limits_concurrent_fetches").unwrap();
+        let settings = FetchSettings {
+            max_concurrent_fetches: 2,
+            ..Default::default()
+        };
+
+        let outcomes = fetch_packages(hud_urls(&server, 6), directory.path(), &settings).await;
+
+        assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+        assert_eq!(2, max_in_flight.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_packages_sums_progress() {
+        let (server, archive, _) = slow_server(3).await;
+        let directory = TempDir::new("test_fetch_packages_sums_progress").unwrap();
+        let (progress_sender, mut progress_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let settings = FetchSettings {
+            max_concurrent_fetches: 3,
+            progress_sender: Some(progress_sender),
+            ..Default::default()
+        };
+
+        fetch_packages(hud_urls(&server, 3), directory.path(), &settings).await;
+
+        let mut downloaded_bytes = Vec::new();
+
+        while let Ok(progress) = progress_receiver.try_recv() {
+            downloaded_bytes.push(progress.downloaded_bytes);
+        }
+
+        assert!(downloaded_bytes.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(Some(&(3 * archive.len() as u64)), downloaded_bytes.last());
+    }
+
+    /// The application runs the fetches in its own tasks.
+    #[test]
+    fn test_fetch_packages_is_send() {
+        fn assert_send(_: impl Send) {}
+
+        assert_send(fetch_packages(Vec::new(), "", &FetchSettings::default()));
+    }
+}
@@ -0,0 +1,378 @@
+//! A persistent cache of the downloaded files, shared by all the fetches.
+//! Each entry is identified by its URL and its ETag, or the hash of its content if the server sent no ETag.
+//! The files are stored by the hash of their content, so the same file downloaded from several URLs is stored once.
+
+use crate::download::sha256_file;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const INDEX_FILE_NAME: &str = "index.json";
+const DATA_DIRECTORY_NAME: &str = "data";
+
+/// The default maximum size of the cache: 1 GiB.
+pub const DEFAULT_CACHE_MAX_SIZE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// How long an entry without validator is reused without asking the server.
+/// It is short on purpose, long enough to install a HUD just scanned.
+const ENTRY_WITHOUT_VALIDATOR_LIFETIME_MINUTES: i64 = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CacheError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    #[error("Invalid cache index: {0}")]
+    InvalidIndex(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The SHA-256 of the content, in hexadecimal.
+    pub content_hash: String,
+    /// The name of the file when it was downloaded.
+    pub file_name: String,
+    pub size_bytes: u64,
+    pub stored_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+}
+
+impl CacheEntry {
+    fn has_validator(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+
+    /// True if the entry can be used without asking the server if the content changed.
+    pub(crate) fn is_fresh(&self) -> bool {
+        !self.has_validator() && Utc::now() - self.stored_at < Duration::minutes(ENTRY_WITHOUT_VALIDATOR_LIFETIME_MINUTES)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CacheIndex {
+    entries: Vec<CacheEntry>,
+}
+
+/// The cache of the downloaded files.
+/// Clones share the same lock, so concurrent fetches do not overwrite the changes of each other.
+#[derive(Clone, Debug)]
+pub struct DownloadCache {
+    directory: PathBuf,
+    max_size_bytes: u64,
+    lock: Arc<Mutex<()>>,
+}
+
+impl DownloadCache {
+    pub fn new(directory: impl Into<PathBuf>, max_size_bytes: u64) -> Self {
+        Self {
+            directory: directory.into(),
+            max_size_bytes,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn max_size_bytes(&self) -> u64 {
+        self.max_size_bytes
+    }
+
+    /// The entries of the cache, the most recently used first.
+    pub async fn entries(&self) -> Result<Vec<CacheEntry>, CacheError> {
+        let _guard = self.lock.lock().await;
+        let mut entries = self.load_index().await?.entries;
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used_at));
+
+        Ok(entries)
+    }
+
+    /// The size of the files stored in the cache.
+    pub async fn size_bytes(&self) -> Result<u64, CacheError> {
+        let _guard = self.lock.lock().await;
+
+        Ok(total_size_bytes(&self.load_index().await?))
+    }
+
+    /// Remove the entry of a URL. Returns false if there was no entry for this URL.
+    pub async fn remove(&self, url: &str) -> Result<bool, CacheError> {
+        let _guard = self.lock.lock().await;
+        let mut index = self.load_index().await?;
+        let removed = self.remove_entries(&mut index, |entry| entry.url == url).await?;
+
+        self.save_index(&index).await?;
+
+        Ok(removed > 0)
+    }
+
+    /// Remove all the entries.
+    pub async fn clear(&self) -> Result<(), CacheError> {
+        let _guard = self.lock.lock().await;
+
+        if self.directory.exists() {
+            tokio::fs::remove_dir_all(&self.directory).await?;
+        }
+
+        Ok(())
+    }
+
+    /// The entry of a URL, if there is one and its file still exists.
+    pub(crate) async fn find(&self, url: &str) -> Result<Option<CacheEntry>, CacheError> {
+        let _guard = self.lock.lock().await;
+        let index = self.load_index().await?;
+
+        Ok(index
+            .entries
+            .into_iter()
+            .find(|entry| entry.url == url && self.data_file_path(&entry.content_hash).exists()))
+    }
+
+    /// Copy the file of an entry, and mark the entry as used.
+    pub(crate) async fn copy_to(&self, entry: &CacheEntry, file_path: &Path) -> Result<(), CacheError> {
+        let _guard = self.lock.lock().await;
+        let mut index = self.load_index().await?;
+
+        tokio::fs::copy(self.data_file_path(&entry.content_hash), file_path).await?;
+
+        if let Some(entry) = index.entries.iter_mut().find(|e| e.url == entry.url) {
+            entry.last_used_at = Utc::now();
+        }
+
+        self.save_index(&index).await
+    }
+
+    /// Store a downloaded file, replacing the previous entry of this URL.
+    /// The least recently used entries are evicted until the cache fits its maximum size.
+    pub(crate) async fn insert(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        file_path: &Path,
+    ) -> Result<(), CacheError> {
+        let size_bytes = tokio::fs::metadata(file_path).await?.len();
+
+        if size_bytes > self.max_size_bytes {
+            return Ok(());
+        }
+
+        let content_hash = sha256_file(file_path).await?;
+        let _guard = self.lock.lock().await;
+        let mut index = self.load_index().await?;
+
+        self.remove_entries(&mut index, |entry| entry.url == url).await?;
+        tokio::fs::create_dir_all(self.directory.join(DATA_DIRECTORY_NAME)).await?;
+        tokio::fs::copy(file_path, self.data_file_path(&content_hash)).await?;
+
+        let now = Utc::now();
+
+        index.entries.push(CacheEntry {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            content_hash,
+            file_name: file_path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size_bytes,
+            stored_at: now,
+            last_used_at: now,
+        });
+
+        while total_size_bytes(&index) > self.max_size_bytes {
+            let least_recently_used = index
+                .entries
+                .iter()
+                .min_by_key(|entry| entry.last_used_at)
+                .map(|entry| entry.url.clone())
+                .expect("the cache is not empty");
+
+            self.remove_entries(&mut index, |entry| entry.url == least_recently_used)
+                .await?;
+        }
+
+        self.save_index(&index).await
+    }
+
+    /// Remove the entries matching the predicate, and the files no other entry uses.
+    async fn remove_entries(
+        &self,
+        index: &mut CacheIndex,
+        predicate: impl Fn(&CacheEntry) -> bool,
+    ) -> Result<usize, CacheError> {
+        let (removed, kept): (Vec<CacheEntry>, Vec<CacheEntry>) =
+            index.entries.drain(..).partition(|entry| predicate(entry));
+
+        index.entries = kept;
+
+        for entry in removed.iter() {
+            let is_used = index.entries.iter().any(|e| e.content_hash == entry.content_hash);
+            let data_file_path = self.data_file_path(&entry.content_hash);
+
+            if !is_used && data_file_path.exists() {
+                tokio::fs::remove_file(data_file_path).await?;
+            }
+        }
+
+        Ok(removed.len())
+    }
+
+    fn data_file_path(&self, content_hash: &str) -> PathBuf {
+        self.directory.join(DATA_DIRECTORY_NAME).join(content_hash)
+    }
+
+    async fn load_index(&self) -> Result<CacheIndex, CacheError> {
+        match tokio::fs::read_to_string(self.directory.join(INDEX_FILE_NAME)).await {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(CacheIndex::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn save_index(&self, index: &CacheIndex) -> Result<(), CacheError> {
+        let index_file_path = self.directory.join(INDEX_FILE_NAME);
+        let temporary_file_path = index_file_path.with_extension("tmp");
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(&temporary_file_path, serde_json::to_string(index)?).await?;
+        tokio::fs::rename(temporary_file_path, index_file_path).await?;
+
+        Ok(())
+    }
+}
+
+/// The size of the files of the entries, counting once a file shared by several entries.
+fn total_size_bytes(index: &CacheIndex) -> u64 {
+    let mut content_hashes = HashSet::new();
+
+    index
+        .entries
+        .iter()
+        .filter(|entry| content_hashes.insert(entry.content_hash.as_str()))
+        .map(|entry| entry.size_bytes)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DownloadCache;
+    use tempdir::TempDir;
+
+    async fn insert(cache: &DownloadCache, directory: &TempDir, url: &str, content: &[u8]) {
+        let file_path = directory.path().join("downloaded.zip");
+
+        std::fs::write(&file_path, content).unwrap();
+        cache
+            .insert(url, Some(format!("\"{}\"", url)), None, &file_path)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_find() {
+        let directory = TempDir::new("test_cache_insert_and_find").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 1024);
+
+        insert(&cache, &directory, "https://a", b"aaaa").await;
+
+        let entry = cache.find("https://a").await.unwrap().unwrap();
+        let copy_path = directory.path().join("copy.zip");
+
+        cache.copy_to(&entry, &copy_path).await.unwrap();
+
+        assert_eq!("downloaded.zip", entry.file_name);
+        assert_eq!(4, entry.size_bytes);
+        assert_eq!(b"aaaa".as_slice(), std::fs::read(copy_path).unwrap());
+        assert!(cache.find("https://b").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_insert_replaces_entry_of_same_url() {
+        let directory = TempDir::new("test_cache_insert_replaces").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 1024);
+
+        insert(&cache, &directory, "https://a", b"aaaa").await;
+        insert(&cache, &directory, "https://a", b"bbbbbb").await;
+
+        let entries = cache.entries().await.unwrap();
+
+        assert_eq!(1, entries.len());
+        assert_eq!(6, cache.size_bytes().await.unwrap());
+        assert_eq!(
+            1,
+            std::fs::read_dir(directory.path().join("cache").join("data"))
+                .unwrap()
+                .count()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evict_least_recently_used() {
+        let directory = TempDir::new("test_cache_evict").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 10);
+
+        insert(&cache, &directory, "https://a", b"aaaa").await;
+        insert(&cache, &directory, "https://b", b"bbbb").await;
+
+        let entry = cache.find("https://a").await.unwrap().unwrap();
+
+        cache.copy_to(&entry, &directory.path().join("copy.zip")).await.unwrap();
+        insert(&cache, &directory, "https://c", b"cccc").await;
+
+        let urls: Vec<String> = cache.entries().await.unwrap().into_iter().map(|entry| entry.url).collect();
+
+        assert_eq!(vec!["https://c", "https://a"], urls);
+        assert_eq!(8, cache.size_bytes().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_same_file_of_several_urls_is_counted_once() {
+        let directory = TempDir::new("test_cache_same_file").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 10);
+
+        insert(&cache, &directory, "https://a", b"aaaa").await;
+        insert(&cache, &directory, "https://b", b"aaaa").await;
+        insert(&cache, &directory, "https://c", b"cccc").await;
+
+        let urls: Vec<String> = cache.entries().await.unwrap().into_iter().map(|entry| entry.url).collect();
+
+        assert_eq!(vec!["https://c", "https://b", "https://a"], urls);
+        assert_eq!(8, cache.size_bytes().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_file_bigger_than_cache_is_not_stored() {
+        let directory = TempDir::new("test_cache_too_big").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 2);
+
+        insert(&cache, &directory, "https://a", b"aaaa").await;
+
+        assert!(cache.entries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_and_clear() {
+        let directory = TempDir::new("test_cache_remove_and_clear").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 1024);
+
+        insert(&cache, &directory, "https://a", b"aaaa").await;
+        insert(&cache, &directory, "https://b", b"bbbb").await;
+
+        assert!(cache.remove("https://a").await.unwrap());
+        assert!(!cache.remove("https://a").await.unwrap());
+        assert_eq!(1, cache.entries().await.unwrap().len());
+
+        cache.clear().await.unwrap();
+
+        assert!(cache.entries().await.unwrap().is_empty());
+    }
+}
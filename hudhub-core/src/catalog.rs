@@ -0,0 +1,237 @@
+//! A catalog of HUDs, to find HUDs without searching the web for download links.
+//! The catalog is loaded from a JSON index, modeled on the data published by tf2huds.dev:
+//! ```json
+//! {
+//!     "huds": [
+//!         {
+//!             "name": "ahud",
+//!             "author": "n0kk",
+//!             "source": "https://github.com/n0kk/ahud",
+//!             "tags": ["minimal"],
+//!             "previews": ["https://example.com/ahud.png"]
+//!         }
+//!     ]
+//! }
+//! ```
+//! The source of an entry is a URL, interpreted like a URL pasted by the user.
+
+use crate::http::{HttpClient, HttpError};
+use crate::source::{Source, SourceMetadata};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CATALOG_URL: &str = "https://raw.githubusercontent.com/IohannRabeson/hudhub/main/catalog.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum CatalogError {
+    #[error(transparent)]
+    RequestFailed(#[from] HttpError),
+
+    #[error("Failed to read catalog '{0}': {1}")]
+    ReadFailed(PathBuf, std::io::Error),
+
+    #[error("Invalid catalog: {0}")]
+    InvalidFormat(#[from] serde_json::Error),
+}
+
+/// Where to load a catalog from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CatalogLocation {
+    Url(String),
+    File(PathBuf),
+}
+
+impl Default for CatalogLocation {
+    fn default() -> Self {
+        Self::Url(DEFAULT_CATALOG_URL.into())
+    }
+}
+
+/// A HUD of a catalog.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CatalogEntry {
+    pub name: String,
+    pub author: String,
+    pub source: Source,
+    pub tags: Vec<String>,
+    pub preview_urls: Vec<String>,
+}
+
+impl CatalogEntry {
+    /// Tells if the entry matches a search query.
+    /// The query matches if each of its words is found in the name, the author or the tags, ignoring the case.
+    pub fn matches(&self, query: &str) -> bool {
+        let name = self.name.to_lowercase();
+        let author = self.author.to_lowercase();
+        let tags: Vec<String> = self.tags.iter().map(|tag| tag.to_lowercase()).collect();
+
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| name.contains(word) || author.contains(word) || tags.iter().any(|tag| tag.contains(word)))
+    }
+
+    /// The information to store in the registry when the HUD is added.
+    pub fn metadata(&self) -> SourceMetadata {
+        SourceMetadata {
+            title: self.name.clone(),
+            author: Some(self.author.clone()),
+            version: None,
+            screenshot_urls: self.preview_urls.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    pub entries: Vec<CatalogEntry>,
+}
+
+#[derive(Deserialize)]
+struct IndexFile {
+    huds: Vec<IndexEntry>,
+}
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    name: String,
+    #[serde(default)]
+    author: String,
+    source: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    previews: Vec<String>,
+}
+
+impl Catalog {
+    pub async fn load(location: &CatalogLocation, client: &HttpClient) -> Result<Self, CatalogError> {
+        match location {
+            CatalogLocation::Url(url) => Self::load_url(url, client).await,
+            CatalogLocation::File(file_path) => Self::load_file(file_path).await,
+        }
+    }
+
+    pub async fn load_url(url: &str, client: &HttpClient) -> Result<Self, CatalogError> {
+        let json = client.send_text(client.get(url)).await?;
+
+        Self::from_json(&json)
+    }
+
+    pub async fn load_file(file_path: &Path) -> Result<Self, CatalogError> {
+        let json = tokio::fs::read_to_string(file_path)
+            .await
+            .map_err(|error| CatalogError::ReadFailed(file_path.to_path_buf(), error))?;
+
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CatalogError> {
+        let index: IndexFile = serde_json::from_str(json)?;
+
+        Ok(Self {
+            entries: index
+                .huds
+                .into_iter()
+                .map(|entry| CatalogEntry {
+                    name: entry.name,
+                    author: entry.author,
+                    source: Source::from_url(&entry.source),
+                    tags: entry.tags,
+                    preview_urls: entry.previews,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn search<'a>(&'a self, query: &'a str) -> impl Iterator<Item = &'a CatalogEntry> {
+        self.entries.iter().filter(move |entry| entry.matches(query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Catalog, CatalogLocation};
+    use crate::{GitHubReference, HttpClient, Source};
+    use tempdir::TempDir;
+    use test_case::test_case;
+
+    const CATALOG: &str = r#"{
+        "huds": [
+            {
+                "name": "ahud",
+                "author": "n0kk",
+                "source": "https://github.com/n0kk/ahud",
+                "tags": ["Minimal", "Dark"],
+                "previews": ["https://example.com/ahud.png"]
+            },
+            {
+                "name": "3HUD",
+                "author": "Tripp",
+                "source": "https://www.dropbox.com/s/cwwmppnn3nn68av/3HUD.7z?dl=1"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_from_json() {
+        let catalog = Catalog::from_json(CATALOG).unwrap();
+
+        assert_eq!(2, catalog.entries.len());
+        assert_eq!(
+            Source::GitHub {
+                owner: "n0kk".into(),
+                repo: "ahud".into(),
+                reference: GitHubReference::DefaultBranch
+            },
+            catalog.entries[0].source
+        );
+        assert_eq!(
+            vec!["https://example.com/ahud.png".to_string()],
+            catalog.entries[0].preview_urls
+        );
+        assert!(catalog.entries[1].tags.is_empty());
+    }
+
+    #[test]
+    fn test_from_invalid_json() {
+        assert!(Catalog::from_json(r#"{"huds": [{"name": "no source"}]}"#).is_err());
+    }
+
+    #[test_case("", &["ahud", "3HUD"])]
+    #[test_case("HUD", &["ahud", "3HUD"])]
+    #[test_case("tripp", &["3HUD"])]
+    #[test_case("minimal", &["ahud"])]
+    #[test_case("dark n0kk", &["ahud"])]
+    #[test_case("dark tripp", &[])]
+    fn test_search(query: &str, expected: &[&str]) {
+        let catalog = Catalog::from_json(CATALOG).unwrap();
+        let names: Vec<&str> = catalog.search(query).map(|entry| entry.name.as_str()).collect();
+
+        assert_eq!(expected, names.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_load_file() {
+        let directory = TempDir::new("test_load_catalog_file").unwrap();
+        let file_path = directory.path().join("catalog.json");
+        std::fs::write(&file_path, CATALOG).unwrap();
+
+        let catalog = Catalog::load(&CatalogLocation::File(file_path), &HttpClient::default())
+            .await
+            .unwrap();
+
+        assert_eq!(2, catalog.entries.len());
+    }
+
+    #[tokio::test]
+    async fn test_load_url() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/catalog.json").with_body(CATALOG).create_async().await;
+
+        let location = CatalogLocation::Url(format!("{}/catalog.json", server.url()));
+        let catalog = Catalog::load(&location, &HttpClient::default()).await.unwrap();
+
+        assert_eq!(2, catalog.entries.len());
+    }
+}
@@ -1,8 +1,22 @@
-use crate::source::Source;
-use crate::{fetch_package, FetchError, PackageEntry, HudName, Install, OpenHudDirectoryError};
+use crate::package::PackageEntryKind;
+use crate::source::{Revision, Source};
+use crate::vpk::chunk_file_paths;
+use crate::{fetch_hud_with, FetchError, FetchSettings, HudName, Install, OpenHudDirectoryError, PackageEntry};
 use std::path::{Path, PathBuf};
 use tempdir::TempDir;
-use crate::package::PackageEntryKind;
+
+/// Directories never copied when installing a HUD from a local directory.
+const IGNORED_DIRECTORY_NAMES: &[&str] = &[".git"];
+
+/// How a HUD from a [`Source::LocalDirectory`] is installed.
+/// HUDs from other sources are always moved from the temporary directory they were fetched to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InstallMode {
+    /// Copy the HUD. Installing again copies only the files that changed.
+    Copy,
+    /// Link the HUD, so the changes made to the HUD are visible in game right away.
+    Link,
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum InstallError {
@@ -18,16 +32,33 @@ pub enum InstallError {
     FailedToMoveDirectory(#[from] fs_extra::error::Error),
 }
 
-pub async fn install(source: Source, name: HudName, huds_directory: PathBuf) -> Install {
-    match install_impl(source, name, huds_directory).await {
-        Ok(hud_directory) => Install::installed_now(&hud_directory.path),
+pub async fn install(source: Source, name: HudName, huds_directory: PathBuf, mode: InstallMode) -> Install {
+    install_with(source, name, huds_directory, mode, &FetchSettings::default()).await
+}
+
+pub async fn install_with(
+    source: Source,
+    name: HudName,
+    huds_directory: PathBuf,
+    mode: InstallMode,
+    settings: &FetchSettings,
+) -> Install {
+    match install_impl(source, name, huds_directory, mode, settings).await {
+        Ok((hud_directory, revision)) => Install::installed_now(&hud_directory.path, revision),
         Err(error) => Install::failed(error),
     }
 }
 
-async fn install_impl(source: Source, name: HudName, huds_directory: PathBuf) -> Result<PackageEntry, InstallError> {
+async fn install_impl(
+    source: Source,
+    name: HudName,
+    huds_directory: PathBuf,
+    mode: InstallMode,
+    settings: &FetchSettings,
+) -> Result<(PackageEntry, Option<Revision>), InstallError> {
     let directory = TempDir::new(&format!("install_{}", name))?;
-    let package = fetch_package(source, directory.path()).await?;
+    let is_local_directory = matches!(source, Source::LocalDirectory(_));
+    let package = fetch_hud_with(source, &name, directory.path(), settings).await?;
     let source_hud_entry = package
         .find_hud(&name)
         .ok_or(InstallError::HudNotFound(name.clone()))?;
@@ -35,46 +66,373 @@ async fn install_impl(source: Source, name: HudName, huds_directory: PathBuf) ->
     let destination_path = huds_directory.join(source_name);
 
     match source_hud_entry.kind {
+        // Never move a local directory, it belongs to the user.
+        PackageEntryKind::Directory if is_local_directory => match mode {
+            InstallMode::Copy => {
+                sync_directory(&source_hud_entry.path, &destination_path)?;
+            }
+            InstallMode::Link => {
+                link_directory(&source_hud_entry.path, &destination_path)?;
+            }
+        },
         PackageEntryKind::Directory => {
-            let copy_options = fs_extra::dir::CopyOptions::new().copy_inside(true);
-
-            fs_extra::dir::move_dir(&source_hud_entry.path, &destination_path, &copy_options)?;
+            replace_directory(&source_hud_entry.path, &destination_path, &huds_directory)?;
         }
         PackageEntryKind::VpkFile => {
-            let copy_options = fs_extra::file::CopyOptions::new().overwrite(true);
+            copy_vpk_file(&source_hud_entry.path, &destination_path)?;
+        }
+    };
+
+    Ok((
+        PackageEntry::from_path(&destination_path).expect("scan vpk hud"),
+        package.revision,
+    ))
+}
+
+/// Move a directory to its destination, replacing the directory already there, such as the HUD being updated.
+/// The directory is moved next to the destination first, then renamed to it, so a failed install leaves the
+/// installed HUD as it was.
+fn replace_directory(source: &Path, destination: &Path, huds_directory: &Path) -> Result<(), InstallError> {
+    let staging_directory = TempDir::new_in(huds_directory, ".install")?;
+    let new_path = staging_directory.path().join("new");
+    let old_path = staging_directory.path().join("old");
+    let copy_options = fs_extra::dir::CopyOptions::new().copy_inside(true);
+
+    fs_extra::dir::move_dir(source, &new_path, &copy_options)?;
+
+    if destination.exists() || is_link(destination) {
+        std::fs::rename(destination, &old_path)?;
+    }
+
+    if let Err(error) = std::fs::rename(&new_path, destination) {
+        let _ = std::fs::rename(&old_path, destination);
+
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+/// Copy a .vpk file and its chunk files, replacing the chunk files of the .vpk file installed before, which may
+/// have more chunks.
+fn copy_vpk_file(source: &Path, destination: &Path) -> Result<(), InstallError> {
+    let copy_options = fs_extra::file::CopyOptions::new().overwrite(true);
+
+    for chunk_file_path in chunk_file_paths(destination)? {
+        std::fs::remove_file(chunk_file_path)?;
+    }
+
+    fs_extra::file::copy(source, destination, &copy_options)?;
+
+    for chunk_file_path in chunk_file_paths(source)? {
+        let file_name = chunk_file_path.file_name().expect("chunk file name");
+
+        fs_extra::file::copy(&chunk_file_path, destination.with_file_name(file_name), &copy_options)?;
+    }
+
+    Ok(())
+}
+
+/// Copy the content of a directory, skipping the files that did not change since the last copy.
+/// The files in the destination that do not exist in the source are removed.
+/// Returns the count of files copied.
+fn sync_directory(source: &Path, destination: &Path) -> Result<usize, std::io::Error> {
+    if is_link(destination) {
+        remove_link(destination)?;
+    }
+
+    std::fs::create_dir_all(destination)?;
+
+    let mut copied_files = 0;
+    let source_entries = walkdir::WalkDir::new(source)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| !is_ignored(entry.path()));
 
-            fs_extra::file::copy(&source_hud_entry.path, &destination_path, &copy_options)?;
+    for entry in source_entries {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let relative_path = entry.path().strip_prefix(source).expect("relative path");
+        let destination_path = destination.join(relative_path);
+
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&destination_path)?;
+        } else if is_modified(entry.path(), &destination_path)? {
+            std::fs::copy(entry.path(), &destination_path)?;
+            copied_files += 1;
         }
+    }
+
+    let destination_entries = walkdir::WalkDir::new(destination).min_depth(1).contents_first(true);
+
+    for entry in destination_entries {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let relative_path = entry.path().strip_prefix(destination).expect("relative path");
+        let source_path = source.join(relative_path);
+
+        if source_path.exists() && !is_ignored(&source_path) {
+            continue;
+        }
+
+        match entry.file_type().is_dir() {
+            true => std::fs::remove_dir_all(entry.path())?,
+            false => std::fs::remove_file(entry.path())?,
+        }
+    }
+
+    Ok(copied_files)
+}
+
+fn is_ignored(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| IGNORED_DIRECTORY_NAMES.contains(&name))
+        .unwrap_or(false)
+}
+
+/// A copied file is modified if its size changed, or if the source was written after the copy.
+fn is_modified(source: &Path, destination: &Path) -> Result<bool, std::io::Error> {
+    let destination_metadata = match std::fs::metadata(destination) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+        Err(error) => return Err(error),
     };
+    let source_metadata = std::fs::metadata(source)?;
 
-    Ok(PackageEntry::from_path(&destination_path).expect("scan vpk hud"))
+    Ok(source_metadata.len() != destination_metadata.len()
+        || source_metadata.modified()? > destination_metadata.modified()?)
+}
+
+fn link_directory(source: &Path, destination: &Path) -> Result<(), std::io::Error> {
+    if is_link(destination) {
+        remove_link(destination)?;
+    } else if destination.exists() {
+        std::fs::remove_dir_all(destination)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(source, destination)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(source, destination)?;
+
+    Ok(())
+}
+
+fn is_link(path: &Path) -> bool {
+    path.symlink_metadata()
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+fn remove_link(path: &Path) -> Result<(), std::io::Error> {
+    // On Windows a link to a directory is removed like a directory.
+    #[cfg(windows)]
+    return std::fs::remove_dir(path);
+    #[cfg(not(windows))]
+    return std::fs::remove_file(path);
 }
 
 pub async fn uninstall(hud_path: &Path, huds_directory: PathBuf) -> Result<(), std::io::Error> {
     assert!(hud_path.starts_with(&huds_directory));
 
+    // Remove the link, not the directory it points to.
+    if is_link(hud_path) {
+        return remove_link(hud_path);
+    }
+
     if hud_path.is_dir() {
         return tokio::fs::remove_dir_all(hud_path).await
     }
 
     if hud_path.is_file() {
+        for chunk_file_path in chunk_file_paths(hud_path)? {
+            tokio::fs::remove_file(chunk_file_path).await?;
+        }
+
         return tokio::fs::remove_file(hud_path).await
     }
 
     panic!("Unsupported HUD type");
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{install, sync_directory, uninstall, InstallMode};
+    use crate::vpk::write_vpk;
+    use crate::{HudName, Source};
+    use std::io::Write;
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    fn create_hud(directory: &Path) {
+        std::fs::create_dir_all(directory.join("resource").join("ui")).unwrap();
+        std::fs::create_dir_all(directory.join(".git")).unwrap();
+        std::fs::write(directory.join("info.vdf"), b"\"hud\"\n{\n}").unwrap();
+        std::fs::write(directory.join("resource").join("ui").join("hudplayerhealth.res"), b"health").unwrap();
+        std::fs::write(directory.join(".git").join("HEAD"), b"ref: refs/heads/master").unwrap();
+    }
+
+    #[test]
+    fn test_sync_directory() {
+        let source = TempDir::new("test_sync_directory_source").unwrap();
+        let destination = TempDir::new("test_sync_directory_destination").unwrap();
+        let destination = destination.path().join("hud");
+        create_hud(source.path());
+
+        assert_eq!(2, sync_directory(source.path(), &destination).unwrap());
+        assert!(destination.join("resource").join("ui").join("hudplayerhealth.res").is_file());
+        assert!(!destination.join(".git").exists());
+        assert_eq!(0, sync_directory(source.path(), &destination).unwrap());
+
+        std::fs::write(source.path().join("info.vdf"), b"\"hud\"\n{\n\"ui_version\" \"3\"\n}").unwrap();
+        std::fs::remove_file(source.path().join("resource").join("ui").join("hudplayerhealth.res")).unwrap();
+
+        assert_eq!(1, sync_directory(source.path(), &destination).unwrap());
+        assert!(!destination.join("resource").join("ui").join("hudplayerhealth.res").exists());
+    }
+
+    fn create_hud_zip(archive_file_path: &Path, layout: &[u8]) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(archive_file_path).unwrap());
+
+        writer.add_directory("ahud", Default::default()).unwrap();
+        writer.start_file("ahud/info.vdf", Default::default()).unwrap();
+        writer.write_all(b"\"hud\"\n{\n}").unwrap();
+        writer.start_file("ahud/hudlayout.res", Default::default()).unwrap();
+        writer.write_all(layout).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_install_archive_again_replaces_hud() {
+        let directory = TempDir::new("test_install_archive_again_replaces_hud").unwrap();
+        let archive_file_path = directory.path().join("ahud.zip");
+        let huds_directory = directory.path().join("custom");
+        std::fs::create_dir(&huds_directory).unwrap();
+
+        for version in ["1", "2", "3"] {
+            create_hud_zip(&archive_file_path, version.as_bytes());
+
+            let install = install(
+                Source::LocalFile(archive_file_path.clone()),
+                HudName::new("ahud"),
+                huds_directory.clone(),
+                InstallMode::Copy,
+            )
+            .await;
+            let (path, ..) = install.as_installed().unwrap();
+
+            assert_eq!(version.as_bytes(), std::fs::read(path.join("hudlayout.res")).unwrap());
+        }
+
+        assert!(!huds_directory.join("ahud").join("ahud").exists());
+        assert_eq!(1, std::fs::read_dir(&huds_directory).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_install_vpk_file_split_in_chunks() {
+        let directory = TempDir::new("test_install_vpk_file_split_in_chunks").unwrap();
+        let vpk_directory = directory.path().join("vpk");
+        let huds_directory = directory.path().join("custom");
+        std::fs::create_dir(&vpk_directory).unwrap();
+        std::fs::create_dir(&huds_directory).unwrap();
+        write_vpk(
+            &vpk_directory.join("ahud_dir.vpk"),
+            2,
+            &[("resource/ui/hudlayout.res", b"\"Resource/UI/HudLayout.res\"\n{\n}")],
+            Some(16),
+        );
+        let archive_file_path = directory.path().join("ahud.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_file_path).unwrap());
+
+        for entry in std::fs::read_dir(&vpk_directory).unwrap() {
+            let entry = entry.unwrap();
+            writer
+                .start_file(entry.file_name().to_str().unwrap(), Default::default())
+                .unwrap();
+            writer.write_all(&std::fs::read(entry.path()).unwrap()).unwrap();
+        }
+
+        writer.finish().unwrap();
+
+        let install = install(
+            Source::LocalFile(archive_file_path),
+            HudName::new("ahud"),
+            huds_directory.clone(),
+            InstallMode::Copy,
+        )
+        .await;
+        let (path, ..) = install.as_installed().unwrap();
+
+        assert_eq!(&huds_directory.join("ahud_dir.vpk"), path);
+        assert_eq!(
+            std::fs::read_dir(&vpk_directory).unwrap().count(),
+            std::fs::read_dir(&huds_directory).unwrap().count()
+        );
+        assert!(huds_directory.join("ahud_000.vpk").is_file());
+
+        uninstall(path, huds_directory.clone()).await.unwrap();
+
+        assert_eq!(0, std::fs::read_dir(&huds_directory).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_install_local_directory_copy() {
+        let source = TempDir::new("test_install_local_directory_copy_source").unwrap();
+        let huds_directory = TempDir::new("test_install_local_directory_copy").unwrap();
+        create_hud(&source.path().join("ahud"));
+
+        let install = install(
+            Source::LocalDirectory(source.path().to_path_buf()),
+            HudName::new("ahud"),
+            huds_directory.path().to_path_buf(),
+            InstallMode::Copy,
+        )
+        .await;
+        let (path, ..) = install.as_installed().unwrap();
+
+        assert!(path.join("info.vdf").is_file());
+        assert!(!path.symlink_metadata().unwrap().file_type().is_symlink());
+        assert!(source.path().join("ahud").join("info.vdf").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_install_local_directory_link() {
+        let source = TempDir::new("test_install_local_directory_link_source").unwrap();
+        let huds_directory = TempDir::new("test_install_local_directory_link").unwrap();
+        create_hud(&source.path().join("ahud"));
+
+        let install = install(
+            Source::LocalDirectory(source.path().to_path_buf()),
+            HudName::new("ahud"),
+            huds_directory.path().to_path_buf(),
+            InstallMode::Link,
+        )
+        .await;
+        let (path, ..) = install.as_installed().unwrap();
+
+        assert!(path.symlink_metadata().unwrap().file_type().is_symlink());
+
+        std::fs::write(source.path().join("ahud").join("new.res"), b"").unwrap();
+
+        assert!(path.join("new.res").is_file());
+
+        uninstall(path, huds_directory.path().to_path_buf()).await.unwrap();
+
+        assert!(!path.exists());
+        assert!(source.path().join("ahud").join("new.res").is_file());
+    }
+}
+
 #[cfg(test)]
 mod slow_tests {
-    use super::install;
-    use crate::{PackageEntry, HudName, Source};
+    use super::{install, InstallMode};
+    use crate::{HudName, PackageEntry, Source};
     use tempdir::TempDir;
 
     #[tokio::test]
     async fn test_install_zip() {
         let source = Source::DownloadUrl("https://github.com/n0kk/ahud/archive/refs/heads/master.zip".into());
         let directory = TempDir::new("test_install_zip").unwrap();
-        let install = install(source, HudName::new("ahud-master"), directory.path().to_path_buf()).await;
+        let install = install(source, HudName::new("ahud-master"), directory.path().to_path_buf(), InstallMode::Copy).await;
         let hud = PackageEntry::from_path(install.as_installed().unwrap().0).unwrap();
 
         assert_eq!(HudName::new("ahud-master"), hud.name);
@@ -84,7 +442,7 @@ mod slow_tests {
     async fn test_install_7z() {
         let source = Source::DownloadUrl("https://www.dropbox.com/s/cwwmppnn3nn68av/3HUD.7z?dl=1".into());
         let directory = TempDir::new("test_install_7z").unwrap();
-        let install = install(source, HudName::new("3HUD"), directory.path().to_path_buf()).await;
+        let install = install(source, HudName::new("3HUD"), directory.path().to_path_buf(), InstallMode::Copy).await;
         let hud = PackageEntry::from_path(install.as_installed().unwrap().0).unwrap();
 
         assert_eq!(HudName::new("3HUD"), hud.name);
@@ -94,7 +452,7 @@ mod slow_tests {
     async fn test_install_vpk() {
         let source = Source::DownloadUrl("https://gamebanana.com/dl/945012".into());
         let directory = TempDir::new("test_install_vpk").unwrap();
-        let install = install(source, HudName::new("minhud_plus"), directory.path().to_path_buf()).await;
+        let install = install(source, HudName::new("minhud_plus"), directory.path().to_path_buf(), InstallMode::Copy).await;
         let hud = PackageEntry::from_path(install.as_installed().unwrap().0).unwrap();
 
         assert_eq!(HudName::new("minhud_plus"), hud.name);
@@ -0,0 +1,1106 @@
+//! Download files, streaming them to disk.
+//! The partial downloads are kept in a known directory. If a download fails, the next download of the same URL
+//! resumes where the previous one stopped, if the server supports range requests.
+//! A partial download is locked while it is written, the other downloads of the same URL at the same time are not
+//! resumable.
+//! The completed downloads are stored in the cache, if there is one.
+
+use crate::cache::{CacheEntry, DownloadCache};
+use crate::share_link::normalize_url;
+use crate::source::{FetchError, FetchSettings, Revision};
+use fs2::FileExt;
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED,
+    RANGE,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc::UnboundedSender;
+
+const PARTIAL_FILE_EXTENSION: &str = "part";
+const PARTIAL_INFO_FILE_EXTENSION: &str = "json";
+const PARTIAL_LOCK_FILE_EXTENSION: &str = "lock";
+
+/// The progress of a download.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DownloadProgress {
+    pub downloaded_bytes: u64,
+    /// The size of the file, if the server sent it.
+    pub total_bytes: Option<u64>,
+    pub bytes_per_second: f64,
+}
+
+impl DownloadProgress {
+    /// The part of the file downloaded, between 0 and 1, if the size of the file is known.
+    pub fn ratio(&self) -> Option<f32> {
+        self.total_bytes
+            .filter(|total_bytes| *total_bytes > 0)
+            .map(|total_bytes| (self.downloaded_bytes as f64 / total_bytes as f64).min(1.0) as f32)
+    }
+}
+
+pub type ProgressSender = UnboundedSender<DownloadProgress>;
+
+/// What is known about a partial download, stored next to the partial file.
+/// The validators are used to ensure the rest of the file belongs to the same content.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+struct PartialDownloadInfo {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    total_bytes: Option<u64>,
+}
+
+impl PartialDownloadInfo {
+    fn new(url: &str, headers: &HeaderMap, total_bytes: Option<u64>) -> Self {
+        Self {
+            url: url.to_string(),
+            etag: header_value(headers, ETAG),
+            last_modified: header_value(headers, LAST_MODIFIED),
+            total_bytes,
+        }
+    }
+
+    /// The value of the header If-Range. A strong ETag is preferred, as advised by RFC 9110.
+    fn if_range(&self) -> Option<&str> {
+        match self.etag.as_deref() {
+            Some(etag) if !etag.starts_with("W/") => Some(etag),
+            _ => self.last_modified.as_deref(),
+        }
+    }
+
+    fn is_resumable(&self) -> bool {
+        self.if_range().is_some()
+    }
+}
+
+/// The files of a partial download.
+#[derive(Clone)]
+struct PartialDownload {
+    file_path: PathBuf,
+    info_file_path: PathBuf,
+    /// Locked as long as the partial download is used, by this process or another. The lock is released when the file
+    /// is closed, so it is never left locked by a process that stopped. The file is never removed: removing it while it
+    /// is locked would let another download lock a new file of the same name.
+    _lock_file: Arc<std::fs::File>,
+}
+
+impl PartialDownload {
+    /// Lock the partial download of a URL. Returns none if another download of this URL is using it.
+    fn lock(directory: &Path, url: &str) -> Result<Option<Self>, std::io::Error> {
+        let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+
+        std::fs::create_dir_all(directory)?;
+
+        let lock_file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(directory.join(&key).with_extension(PARTIAL_LOCK_FILE_EXTENSION))?;
+
+        if lock_file.try_lock_exclusive().is_err() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            file_path: directory.join(&key).with_extension(PARTIAL_FILE_EXTENSION),
+            info_file_path: directory.join(&key).with_extension(PARTIAL_INFO_FILE_EXTENSION),
+            _lock_file: Arc::new(lock_file),
+        }))
+    }
+
+    /// The information and the size of the partial download, if there is one for this URL.
+    async fn load(&self, url: &str) -> Option<(PartialDownloadInfo, u64)> {
+        let json = tokio::fs::read_to_string(&self.info_file_path).await.ok()?;
+        let info: PartialDownloadInfo = serde_json::from_str(&json).ok()?;
+        let size = tokio::fs::metadata(&self.file_path).await.ok()?.len();
+
+        (info.url == url && info.is_resumable() && size > 0).then_some((info, size))
+    }
+
+    async fn save(&self, info: &PartialDownloadInfo) -> Result<(), std::io::Error> {
+        if let Some(directory) = self.info_file_path.parent() {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+
+        tokio::fs::write(
+            &self.info_file_path,
+            serde_json::to_string(info).expect("serialize partial download"),
+        )
+        .await
+    }
+
+    async fn remove(&self) {
+        let _ = tokio::fs::remove_file(&self.file_path).await;
+        let _ = tokio::fs::remove_file(&self.info_file_path).await;
+    }
+}
+
+/// A download started, ready to be written to disk.
+pub(crate) enum Download {
+    /// The content did not change since it was stored in the cache.
+    Cached(DownloadCache, CacheEntry),
+    Response(ResponseDownload),
+}
+
+pub(crate) struct ResponseDownload {
+    response: reqwest::Response,
+    /// The count of bytes already downloaded, if the download is resumed.
+    resumed_bytes: u64,
+    info: PartialDownloadInfo,
+    partial: Option<PartialDownload>,
+}
+
+/// Start downloading a URL.
+/// If the cache has this URL, the server is asked if the content changed, so it is not downloaded again.
+/// Otherwise, the previous partial download of this URL is resumed if there is one.
+pub(crate) async fn start_download(url: &str, settings: &FetchSettings) -> Result<Download, FetchError> {
+    let cached = match settings.cache.as_ref() {
+        Some(cache) => cache.find(url).await?.map(|entry| (cache, entry)),
+        None => None,
+    };
+    let partial_download = match settings.partial_downloads_directory.as_ref() {
+        Some(directory) => PartialDownload::lock(directory, url)?,
+        None => None,
+    };
+    let resumable = match partial_download.as_ref() {
+        Some(partial_download) => partial_download.load(url).await,
+        None => None,
+    };
+    let mut request = settings.http.get(url);
+
+    if let Some((cache, entry)) = cached.as_ref() {
+        if entry.is_fresh() {
+            return Ok(Download::Cached((*cache).clone(), entry.clone()));
+        }
+
+        if let Some(etag) = entry.etag.as_ref() {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+
+        if let Some(last_modified) = entry.last_modified.as_ref() {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    if let Some((info, size)) = resumable.as_ref() {
+        request = request
+            .header(RANGE, format!("bytes={}-", size))
+            .header(IF_RANGE, info.if_range().expect("resumable download"));
+    }
+
+    let response = settings.http.send(request).await?;
+
+    if let (StatusCode::NOT_MODIFIED, Some((cache, entry))) = (response.status(), cached) {
+        return Ok(Download::Cached(cache.clone(), entry));
+    }
+
+    if let (Some((info, size)), Some(partial_download)) = (resumable, partial_download.as_ref()) {
+        match check_resumed_response(&response, &info, size) {
+            Ok(true) => {
+                return Ok(Download::Response(ResponseDownload {
+                    response,
+                    resumed_bytes: size,
+                    info,
+                    partial: Some(partial_download.clone()),
+                }));
+            }
+            // The server sent the whole content again, the partial download is replaced.
+            Ok(false) => {}
+            Err(error) => {
+                partial_download.remove().await;
+
+                return Err(error);
+            }
+        }
+    }
+
+    new_download(url, response, partial_download)
+}
+
+/// Start downloading a URL from the response of a request already sent, such as a share link serving the file.
+/// The cache is not asked whether the content changed, and the previous partial download is not resumed: the
+/// content is being sent already.
+pub(crate) fn start_download_from_response(
+    url: &str,
+    response: reqwest::Response,
+    settings: &FetchSettings,
+) -> Result<Download, FetchError> {
+    let partial_download = match settings.partial_downloads_directory.as_ref() {
+        Some(directory) => PartialDownload::lock(directory, url)?,
+        None => None,
+    };
+
+    new_download(url, response, partial_download)
+}
+
+fn new_download(
+    url: &str,
+    response: reqwest::Response,
+    partial_download: Option<PartialDownload>,
+) -> Result<Download, FetchError> {
+    let response = check_response(url, response)?;
+    let info = PartialDownloadInfo::new(url, response.headers(), response.content_length());
+
+    Ok(Download::Response(ResponseDownload {
+        response,
+        resumed_bytes: 0,
+        // Without validator, there is no way to tell if the rest of a file belongs to the same content.
+        partial: partial_download.filter(|_| info.is_resumable()),
+        info,
+    }))
+}
+
+/// Check the status of a response, and that it is not a web page.
+fn check_response(url: &str, response: reqwest::Response) -> Result<reqwest::Response, FetchError> {
+    let status = response.status();
+
+    if !status.is_success() {
+        return Err(FetchError::HttpStatus {
+            url: url.to_string(),
+            status,
+        });
+    }
+
+    let is_html = header_value(response.headers(), CONTENT_TYPE)
+        .map(|content_type| {
+            let mime_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+
+            mime_type == "text/html" || mime_type == "application/xhtml+xml"
+        })
+        .unwrap_or(false);
+
+    if is_html {
+        return Err(html_page_error(url));
+    }
+
+    Ok(response)
+}
+
+pub(crate) fn html_page_error(url: &str) -> FetchError {
+    FetchError::HtmlPage {
+        url: url.to_string(),
+        direct_download_url: normalize_url(url).filter(|direct_download_url| direct_download_url != url),
+    }
+}
+
+/// True if a downloaded file is a web page, for the servers sending web pages without content type.
+pub(crate) fn is_html_file(file_path: &Path) -> bool {
+    let mut header = Vec::with_capacity(1024);
+
+    if let Ok(file) = std::fs::File::open(file_path) {
+        let _ = std::io::Read::read_to_end(&mut std::io::Read::take(file, 1024), &mut header);
+    }
+
+    let header = String::from_utf8_lossy(&header);
+    let header = header.trim_start_matches('\u{feff}').trim_start().to_ascii_lowercase();
+
+    header.starts_with("<!doctype html") || header.starts_with("<html")
+}
+
+/// Check the response to a range request.
+/// Returns true if the response contains the rest of the partial download, false if it contains the whole content.
+fn check_resumed_response(response: &reqwest::Response, info: &PartialDownloadInfo, size: u64) -> Result<bool, FetchError> {
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => {}
+        StatusCode::RANGE_NOT_SATISFIABLE => return Err(FetchError::RangeNotSupported(info.url.clone())),
+        // If-Range did not match, so the server sends the new content.
+        _ => return Ok(false),
+    }
+
+    let headers = response.headers();
+    let (start, total_bytes) = header_value(headers, CONTENT_RANGE)
+        .as_deref()
+        .and_then(parse_content_range)
+        .ok_or_else(|| FetchError::RangeNotSupported(info.url.clone()))?;
+
+    if start != size {
+        return Err(FetchError::RangeNotSupported(info.url.clone()));
+    }
+
+    let etag_changed = matches!((header_value(headers, ETAG), info.etag.as_ref()), (Some(new), Some(old)) if &new != old);
+    let last_modified_changed = matches!(
+        (header_value(headers, LAST_MODIFIED), info.last_modified.as_ref()),
+        (Some(new), Some(old)) if &new != old
+    );
+    let size_changed = matches!((total_bytes, info.total_bytes), (Some(new), Some(old)) if new != old);
+
+    if etag_changed || last_modified_changed || size_changed {
+        return Err(FetchError::ContentChanged(info.url.clone()));
+    }
+
+    Ok(true)
+}
+
+/// Parse the value of the header Content-Range, such as `bytes 100-199/200`.
+/// Returns the position of the first byte, and the total size if it is known.
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+    let (start, _end) = range.split_once('-')?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+
+    Some((start.trim().parse().ok()?, total))
+}
+
+/// Parse the file name of the header Content-Disposition, such as `attachment; filename="hud.zip"`.
+/// The parameter `filename*` (RFC 6266) is preferred, it supports any character using the percent-encoding.
+/// Only the last component of the name is kept, as the name is used to create a file.
+pub(crate) fn parse_content_disposition_file_name(value: &str) -> Option<String> {
+    let parameters = split_parameters(value);
+    let extended_file_name = parameters
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("filename*"))
+        .and_then(|(_, value)| decode_extended_value(value));
+    let file_name = extended_file_name.or_else(|| {
+        parameters
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("filename"))
+            .map(|(_, value)| value.clone())
+    })?;
+    let file_name = file_name.rsplit(['/', '\\']).next()?.trim();
+
+    match file_name {
+        "" | "." | ".." => None,
+        file_name => Some(file_name.to_string()),
+    }
+}
+
+/// Split the parameters of a header value, unquoting the quoted values.
+/// The first item, the disposition type, is not a parameter and is skipped.
+fn split_parameters(value: &str) -> Vec<(String, String)> {
+    let mut parameters = Vec::new();
+    let mut characters = value.chars().peekable();
+
+    // Skip the disposition type.
+    for character in characters.by_ref() {
+        if character == ';' {
+            break;
+        }
+    }
+
+    loop {
+        let name: String = characters.by_ref().take_while(|character| *character != '=').collect();
+        let name = name.trim().to_string();
+
+        if name.is_empty() {
+            break;
+        }
+
+        while characters.next_if(|character| character.is_whitespace()).is_some() {}
+
+        let mut parameter_value = String::new();
+
+        if characters.next_if_eq(&'"').is_some() {
+            while let Some(character) = characters.next() {
+                match character {
+                    '\\' => parameter_value.extend(characters.next()),
+                    '"' => break,
+                    character => parameter_value.push(character),
+                }
+            }
+
+            for character in characters.by_ref() {
+                if character == ';' {
+                    break;
+                }
+            }
+        } else {
+            parameter_value = characters.by_ref().take_while(|character| *character != ';').collect();
+            parameter_value = parameter_value.trim().to_string();
+        }
+
+        parameters.push((name, parameter_value));
+    }
+
+    parameters
+}
+
+/// Decode an extended value of RFC 8187, such as `UTF-8''na%C3%AFve.zip`.
+fn decode_extended_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let bytes = percent_decode(parts.next()?)?;
+
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+fn percent_decode(value: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+
+    while let Some(byte) = input.next() {
+        match byte {
+            b'%' => {
+                let high = (input.next()? as char).to_digit(16)?;
+                let low = (input.next()? as char).to_digit(16)?;
+
+                bytes.push((high * 16 + low) as u8);
+            }
+            byte => bytes.push(byte),
+        }
+    }
+
+    Some(bytes)
+}
+
+fn http_revision(etag: Option<String>, last_modified: Option<String>) -> Option<Revision> {
+    (etag.is_some() || last_modified.is_some()).then_some(Revision::Http { etag, last_modified })
+}
+
+/// Ask the server if the content of a URL changed since it was downloaded, without downloading it.
+/// Returns the new validators if the content changed. Without a validator to compare, there is no way to tell,
+/// so the content is considered unchanged.
+pub(crate) async fn check_modified(
+    url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+    settings: &FetchSettings,
+) -> Result<Option<Revision>, FetchError> {
+    let mut request = settings.http.head(url);
+
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    if let Some(last_modified) = last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = settings.http.send(request).await?;
+    let status = response.status();
+
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    if !status.is_success() {
+        return Err(FetchError::HttpStatus {
+            url: url.to_string(),
+            status,
+        });
+    }
+
+    let latest_etag = header_value(response.headers(), ETAG);
+    let latest_last_modified = header_value(response.headers(), LAST_MODIFIED);
+    let is_modified = match (etag, latest_etag.as_deref()) {
+        (Some(etag), Some(latest_etag)) => etag != latest_etag,
+        _ => matches!(
+            (last_modified, latest_last_modified.as_deref()),
+            (Some(last_modified), Some(latest_last_modified)) if last_modified != latest_last_modified
+        ),
+    };
+
+    Ok(is_modified
+        .then(|| http_revision(latest_etag, latest_last_modified))
+        .flatten())
+}
+
+fn header_value(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value: &HeaderValue| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+impl Download {
+    /// The validators of the content, if the server sent any.
+    pub fn revision(&self) -> Option<Revision> {
+        match self {
+            Download::Cached(_, entry) => http_revision(entry.etag.clone(), entry.last_modified.clone()),
+            Download::Response(download) => http_revision(download.info.etag.clone(), download.info.last_modified.clone()),
+        }
+    }
+
+    /// The response of the server, if the content is downloaded.
+    pub fn response(&self) -> Option<&reqwest::Response> {
+        match self {
+            Download::Cached(..) => None,
+            Download::Response(download) => Some(&download.response),
+        }
+    }
+
+    /// The name of the file stored in the cache, if the content is not downloaded.
+    pub fn cached_file_name(&self) -> Option<&str> {
+        match self {
+            Download::Cached(_, entry) => Some(&entry.file_name),
+            Download::Response(_) => None,
+        }
+    }
+
+    /// Write the content to a file, and store it in the cache.
+    pub async fn write_to(self, file_path: &Path, settings: &FetchSettings) -> Result<(), FetchError> {
+        match self {
+            Download::Cached(cache, entry) => Ok(cache.copy_to(&entry, file_path).await?),
+            Download::Response(download) => {
+                let url = download.info.url.clone();
+                let etag = download.info.etag.clone();
+                let last_modified = download.info.last_modified.clone();
+
+                download.write_to(file_path, settings).await?;
+
+                // A web page served instead of the file, such as an interstitial, is not cached.
+                if let Some(cache) = settings.cache.as_ref().filter(|_| !is_html_file(file_path)) {
+                    cache.insert(&url, etag, last_modified, file_path).await?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ResponseDownload {
+    /// Write the content to a file, chunk by chunk, and report the progress.
+    /// When no data is received for too long, the rest of the content is requested with a range request, as many times
+    /// as the HTTP settings retry a request. A content without validator can not be resumed, so it is not retried.
+    async fn write_to(self, file_path: &Path, settings: &FetchSettings) -> Result<(), FetchError> {
+        let written_file_path = match self.partial.as_ref() {
+            Some(partial_download) => {
+                if self.resumed_bytes == 0 {
+                    partial_download.save(&self.info).await?;
+                }
+
+                partial_download.file_path.clone()
+            }
+            None => file_path.to_path_buf(),
+        };
+        let mut response = self.response;
+        let mut resumed_bytes = self.resumed_bytes;
+        let mut retry = 0;
+
+        loop {
+            match write_response(response, &written_file_path, resumed_bytes, self.info.total_bytes, settings).await {
+                Err(FetchError::ReadTimeout(_))
+                    if retry < settings.http.settings().max_retries && self.info.is_resumable() =>
+                {
+                    let size = tokio::fs::metadata(&written_file_path).await?.len();
+
+                    (response, resumed_bytes) = request_rest(&self.info, size, settings).await?;
+                    retry += 1;
+                }
+                result => break result?,
+            }
+        }
+
+        if let Some(partial_download) = self.partial {
+            move_file(&written_file_path, file_path).await?;
+            partial_download.remove().await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Request the rest of a content after the first `size` bytes.
+/// Returns the response, and the count of bytes it starts after: 0 if the server sends the whole content again.
+async fn request_rest(
+    info: &PartialDownloadInfo,
+    size: u64,
+    settings: &FetchSettings,
+) -> Result<(reqwest::Response, u64), FetchError> {
+    let request = settings
+        .http
+        .get(&info.url)
+        .header(RANGE, format!("bytes={}-", size))
+        .header(IF_RANGE, info.if_range().expect("resumable download"));
+    let response = settings.http.send(request).await?;
+
+    match check_resumed_response(&response, info, size)? {
+        true => Ok((response, size)),
+        false => Ok((check_response(&info.url, response)?, 0)),
+    }
+}
+
+async fn move_file(source: &Path, destination: &Path) -> Result<(), std::io::Error> {
+    // Renaming fails if the directories are not on the same file system.
+    if tokio::fs::rename(source, destination).await.is_err() {
+        tokio::fs::copy(source, destination).await?;
+        tokio::fs::remove_file(source).await?;
+    }
+
+    Ok(())
+}
+
+async fn write_response(
+    mut response: reqwest::Response,
+    file_path: &Path,
+    resumed_bytes: u64,
+    total_bytes: Option<u64>,
+    settings: &FetchSettings,
+) -> Result<(), FetchError> {
+    let mut file = match resumed_bytes {
+        0 => tokio::fs::File::create(file_path).await?,
+        _ => tokio::fs::OpenOptions::new().append(true).open(file_path).await?,
+    };
+    let total_bytes = total_bytes.or_else(|| response.content_length().map(|length| length + resumed_bytes));
+    let start = Instant::now();
+    let mut downloaded_bytes = resumed_bytes;
+
+    let read_timeout = settings.http.read_timeout();
+
+    while let Some(chunk) = tokio::time::timeout(read_timeout, response.chunk())
+        .await
+        .map_err(|_| FetchError::ReadTimeout(response.url().to_string()))??
+    {
+        file.write_all(&chunk).await?;
+        downloaded_bytes += chunk.len() as u64;
+
+        if let Some(progress_sender) = settings.progress_sender.as_ref() {
+            let elapsed_seconds = start.elapsed().as_secs_f64();
+            let progress = DownloadProgress {
+                downloaded_bytes,
+                total_bytes,
+                bytes_per_second: match elapsed_seconds > 0.0 {
+                    true => (downloaded_bytes - resumed_bytes) as f64 / elapsed_seconds,
+                    false => 0.0,
+                },
+            };
+
+            // Nobody listening to the progress is not a reason to stop downloading.
+            let _ = progress_sender.send(progress);
+        }
+    }
+
+    file.flush().await?;
+
+    Ok(())
+}
+
+/// The SHA-256 of a file, in hexadecimal.
+pub(crate) async fn sha256_file(file_path: &Path) -> Result<String, std::io::Error> {
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let count = file.read(&mut buffer).await?;
+
+        if count == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_content_disposition_file_name, parse_content_range, start_download, PartialDownload, PartialDownloadInfo,
+    };
+    use crate::{DownloadCache, FetchError, FetchSettings, HttpClient, HttpSettings};
+    use mockito::Matcher;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempdir::TempDir;
+    use test_case::test_case;
+
+    const CONTENT: &[u8] = b"0123456789";
+
+    #[test_case("attachment; filename=\"minhud_plus.vpk\"", Some("minhud_plus.vpk"))]
+    #[test_case("attachment; filename=7hud-5.11.7z", Some("7hud-5.11.7z"))]
+    #[test_case("attachment; filename=\"a \\\"quoted\\\" name.zip\"", Some("a \"quoted\" name.zip"))]
+    #[test_case("attachment; filename*=UTF-8''na%C3%AFve%20hud.zip", Some("naïve hud.zip"))]
+    #[test_case(
+        "attachment; filename=\"fallback.zip\"; filename*=UTF-8''preferred.zip",
+        Some("preferred.zip")
+    )]
+    #[test_case("attachment; filename*=iso-8859-1'en'caf%E9.zip", Some("café.zip"))]
+    #[test_case("attachment; filename*=UTF-8''bad%zz.zip; filename=good.zip", Some("good.zip"))]
+    #[test_case("attachment; filename=\"../../evil.zip\"", Some("evil.zip"))]
+    #[test_case("attachment; filename=\"..\"", None)]
+    #[test_case("inline", None)]
+    fn test_parse_content_disposition_file_name(input: &str, expected: Option<&str>) {
+        assert_eq!(expected.map(String::from), parse_content_disposition_file_name(input))
+    }
+
+    #[test_case("bytes 5-9/10", Some((5, Some(10))))]
+    #[test_case("bytes 5-9/*", Some((5, None)))]
+    #[test_case("bytes */10", None)]
+    #[test_case("5-9/10", None)]
+    fn test_parse_content_range(input: &str, expected: Option<(u64, Option<u64>)>) {
+        assert_eq!(expected, parse_content_range(input))
+    }
+
+    fn lock_partial_download(settings: &FetchSettings, url: &str) -> Option<PartialDownload> {
+        PartialDownload::lock(settings.partial_downloads_directory.as_ref().unwrap(), url).unwrap()
+    }
+
+    /// Create a partial download containing the first half of [`CONTENT`].
+    /// Returns the path of the partial file, the partial download is not locked.
+    async fn create_partial_download(directory: &TempDir, url: &str, etag: &str) -> (FetchSettings, PathBuf) {
+        let settings = FetchSettings {
+            partial_downloads_directory: Some(directory.path().join("partial")),
+            ..Default::default()
+        };
+        let partial_download = lock_partial_download(&settings, url).unwrap();
+        let info = PartialDownloadInfo {
+            url: url.to_string(),
+            etag: Some(etag.to_string()),
+            last_modified: None,
+            total_bytes: Some(CONTENT.len() as u64),
+        };
+
+        partial_download.save(&info).await.unwrap();
+        tokio::fs::write(&partial_download.file_path, &CONTENT[..5]).await.unwrap();
+
+        (settings, partial_download.file_path.clone())
+    }
+
+    #[tokio::test]
+    async fn test_download_keeps_partial_file_location() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/hud.zip")
+            .with_header("etag", "\"v1\"")
+            .with_body(CONTENT)
+            .create_async()
+            .await;
+        let directory = TempDir::new("test_download").unwrap();
+        let settings = FetchSettings {
+            partial_downloads_directory: Some(directory.path().join("partial")),
+            ..Default::default()
+        };
+        let url = format!("{}/hud.zip", server.url());
+        let file_path = directory.path().join("hud.zip");
+
+        start_download(&url, &settings)
+            .await
+            .unwrap()
+            .write_to(&file_path, &settings)
+            .await
+            .unwrap();
+
+        let partial_download = lock_partial_download(&settings, &url).unwrap();
+
+        assert_eq!(CONTENT, std::fs::read(file_path).unwrap());
+        assert!(!partial_download.file_path.exists());
+        assert!(!partial_download.info_file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_resume_download").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let (settings, _) = create_partial_download(&directory, &url, "\"v1\"").await;
+        let mock = server
+            .mock("GET", "/hud.zip")
+            .match_header("range", "bytes=5-")
+            .match_header("if-range", "\"v1\"")
+            .with_status(206)
+            .with_header("etag", "\"v1\"")
+            .with_header("content-range", "bytes 5-9/10")
+            .with_body(&CONTENT[5..])
+            .create_async()
+            .await;
+        let file_path = directory.path().join("hud.zip");
+
+        start_download(&url, &settings)
+            .await
+            .unwrap()
+            .write_to(&file_path, &settings)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(CONTENT, std::fs::read(file_path).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_resume_download_whole_content_sent_again() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_resume_download_whole_content").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let (settings, _) = create_partial_download(&directory, &url, "\"v1\"").await;
+        server
+            .mock("GET", "/hud.zip")
+            .with_header("etag", "\"v2\"")
+            .with_body(b"abcdefghij")
+            .create_async()
+            .await;
+        let file_path = directory.path().join("hud.zip");
+
+        start_download(&url, &settings)
+            .await
+            .unwrap()
+            .write_to(&file_path, &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(b"abcdefghij".as_slice(), std::fs::read(file_path).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_locked_partial_download_is_not_used() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_locked_partial_download").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let (settings, partial_file_path) = create_partial_download(&directory, &url, "\"v1\"").await;
+        let partial_download = lock_partial_download(&settings, &url).unwrap();
+        let mock = server
+            .mock("GET", "/hud.zip")
+            .match_header("range", Matcher::Missing)
+            .with_header("etag", "\"v1\"")
+            .with_body(CONTENT)
+            .create_async()
+            .await;
+        let file_path = directory.path().join("hud.zip");
+
+        assert!(lock_partial_download(&settings, &url).is_none());
+
+        start_download(&url, &settings)
+            .await
+            .unwrap()
+            .write_to(&file_path, &settings)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(CONTENT, std::fs::read(file_path).unwrap());
+        assert_eq!(&CONTENT[..5], std::fs::read(&partial_file_path).unwrap());
+        drop(partial_download);
+        assert!(lock_partial_download(&settings, &url).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_resume_download_content_changed() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_resume_download_content_changed").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let (settings, partial_file_path) = create_partial_download(&directory, &url, "\"v1\"").await;
+        server
+            .mock("GET", "/hud.zip")
+            .with_status(206)
+            .with_header("etag", "\"v2\"")
+            .with_header("content-range", "bytes 5-9/10")
+            .with_body(&CONTENT[5..])
+            .create_async()
+            .await;
+
+        let result = start_download(&url, &settings).await;
+
+        assert!(matches!(result, Err(FetchError::ContentChanged(_))));
+        assert!(!partial_file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_resume_download_range_not_supported() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_resume_download_range_not_supported").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let (settings, partial_file_path) = create_partial_download(&directory, &url, "\"v1\"").await;
+        server
+            .mock("GET", "/hud.zip")
+            .with_status(206)
+            .with_header("etag", "\"v1\"")
+            .with_header("content-range", "bytes 2-9/10")
+            .with_body(&CONTENT[2..])
+            .create_async()
+            .await;
+
+        let result = start_download(&url, &settings).await;
+
+        assert!(matches!(result, Err(FetchError::RangeNotSupported(_))));
+        assert!(!partial_file_path.exists());
+    }
+
+    /// A server sending the first half of [`CONTENT`], then nothing for longer than the read timeout.
+    async fn stalling_server(etag: Option<&'static str>) -> mockito::ServerGuard {
+        let mut server = mockito::Server::new_async().await;
+        let mut mock = server.mock("GET", "/hud.zip").match_header("range", Matcher::Missing);
+
+        if let Some(etag) = etag {
+            mock = mock.with_header("etag", etag);
+        }
+
+        mock.with_chunked_body(|writer| {
+            writer.write_all(&CONTENT[..5])?;
+            std::thread::sleep(Duration::from_millis(500));
+            writer.write_all(&CONTENT[5..])
+        })
+        .create_async()
+        .await;
+
+        server
+    }
+
+    fn settings_with_read_timeout() -> FetchSettings {
+        FetchSettings::new(
+            HttpClient::new(HttpSettings {
+                read_timeout: Duration::from_millis(200),
+                initial_retry_delay: Duration::from_millis(1),
+                ..Default::default()
+            })
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_download_stalled_is_resumed() {
+        let mut server = stalling_server(Some("\"v1\"")).await;
+        let mock = server
+            .mock("GET", "/hud.zip")
+            .match_header("range", "bytes=5-")
+            .match_header("if-range", "\"v1\"")
+            .with_status(206)
+            .with_header("etag", "\"v1\"")
+            .with_header("content-range", "bytes 5-9/10")
+            .with_body(&CONTENT[5..])
+            .create_async()
+            .await;
+        let directory = TempDir::new("test_download_stalled_is_resumed").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let file_path = directory.path().join("hud.zip");
+        let settings = settings_with_read_timeout();
+
+        start_download(&url, &settings)
+            .await
+            .unwrap()
+            .write_to(&file_path, &settings)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(CONTENT, std::fs::read(file_path).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_download_stalled_without_validator_fails() {
+        let server = stalling_server(None).await;
+        let directory = TempDir::new("test_download_stalled_without_validator_fails").unwrap();
+        let url = format!("{}/hud.zip", server.url());
+        let settings = settings_with_read_timeout();
+
+        let result = start_download(&url, &settings)
+            .await
+            .unwrap()
+            .write_to(&directory.path().join("hud.zip"), &settings)
+            .await;
+
+        assert!(matches!(result, Err(FetchError::ReadTimeout(_))));
+    }
+
+    async fn download_twice(url: &str, settings: &FetchSettings, directory: &TempDir) -> (Vec<u8>, Vec<u8>) {
+        let first_path = directory.path().join("first.zip");
+        let second_path = directory.path().join("second.zip");
+
+        for file_path in [&first_path, &second_path] {
+            start_download(url, settings)
+                .await
+                .unwrap()
+                .write_to(file_path, settings)
+                .await
+                .unwrap();
+        }
+
+        (std::fs::read(first_path).unwrap(), std::fs::read(second_path).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_download_not_modified_is_copied_from_cache() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_download_not_modified").unwrap();
+        let settings = FetchSettings {
+            cache: Some(DownloadCache::new(directory.path().join("cache"), 1024)),
+            ..Default::default()
+        };
+        let download_mock = server
+            .mock("GET", "/hud.zip")
+            .match_header("if-none-match", Matcher::Missing)
+            .with_header("etag", "\"v1\"")
+            .with_body(CONTENT)
+            .expect(1)
+            .create_async()
+            .await;
+        let not_modified_mock = server
+            .mock("GET", "/hud.zip")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .expect(1)
+            .create_async()
+            .await;
+        let url = format!("{}/hud.zip", server.url());
+
+        let (first, second) = download_twice(&url, &settings, &directory).await;
+
+        download_mock.assert_async().await;
+        not_modified_mock.assert_async().await;
+        assert_eq!(CONTENT, first);
+        assert_eq!(CONTENT, second);
+    }
+
+    #[tokio::test]
+    async fn test_download_without_validator_is_reused_from_cache() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_download_without_validator").unwrap();
+        let settings = FetchSettings {
+            cache: Some(DownloadCache::new(directory.path().join("cache"), 1024)),
+            ..Default::default()
+        };
+        let mock = server
+            .mock("GET", "/hud.zip")
+            .with_body(CONTENT)
+            .expect(1)
+            .create_async()
+            .await;
+        let url = format!("{}/hud.zip", server.url());
+
+        let (first, second) = download_twice(&url, &settings, &directory).await;
+
+        mock.assert_async().await;
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_download_modified_replaces_cache_entry() {
+        let mut server = mockito::Server::new_async().await;
+        let directory = TempDir::new("test_download_modified").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 1024);
+        let settings = FetchSettings {
+            cache: Some(cache.clone()),
+            ..Default::default()
+        };
+        server
+            .mock("GET", "/hud.zip")
+            .match_header("if-none-match", Matcher::Missing)
+            .with_header("etag", "\"v1\"")
+            .with_body(CONTENT)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/hud.zip")
+            .match_header("if-none-match", "\"v1\"")
+            .with_header("etag", "\"v2\"")
+            .with_body(b"abcdefghij")
+            .create_async()
+            .await;
+        let url = format!("{}/hud.zip", server.url());
+
+        let (first, second) = download_twice(&url, &settings, &directory).await;
+        let entries = cache.entries().await.unwrap();
+
+        assert_eq!(CONTENT, first);
+        assert_eq!(b"abcdefghij".as_slice(), second);
+        assert_eq!(1, entries.len());
+        assert_eq!(Some("\"v2\""), entries[0].etag.as_deref());
+    }
+}
@@ -0,0 +1,249 @@
+//! Resolve HUDs published on GameBanana.
+//! A mod has one or more files, and the API provides the information about the mod and the download URL of each file.
+
+use crate::http::{HttpClient, HttpError};
+use crate::source::SourceMetadata;
+use reqwest::Url;
+use serde::Deserialize;
+
+const DEFAULT_API_URL: &str = "https://gamebanana.com/apiv11";
+
+#[derive(thiserror::Error, Debug)]
+pub enum GameBananaError {
+    #[error(transparent)]
+    RequestFailed(#[from] HttpError),
+
+    #[error("Invalid GameBanana URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("The mod {0} has no file")]
+    NoFile(u64),
+
+    #[error("The mod {0} has no file {1}")]
+    FileNotFound(u64, u64),
+}
+
+/// A file of a mod, and the information about the mod.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolvedFile {
+    pub file_id: u64,
+    pub file_name: String,
+    pub download_url: Url,
+    pub metadata: SourceMetadata,
+}
+
+/// Client for the GameBanana API.
+/// The base URL is configurable, so tests can run against a local server.
+#[derive(Clone, Debug)]
+pub struct GameBanana {
+    api_url: Url,
+    client: HttpClient,
+}
+
+impl Default for GameBanana {
+    fn default() -> Self {
+        Self::new(Url::parse(DEFAULT_API_URL).expect("valid GameBanana API URL"))
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfilePageResponse {
+    #[serde(rename = "_sName")]
+    name: String,
+    #[serde(rename = "_sVersion", default)]
+    version: String,
+    #[serde(rename = "_aSubmitter")]
+    submitter: Option<SubmitterResponse>,
+    #[serde(rename = "_aPreviewMedia")]
+    preview_media: Option<PreviewMediaResponse>,
+    #[serde(rename = "_aFiles", default)]
+    files: Vec<FileResponse>,
+}
+
+#[derive(Deserialize)]
+struct SubmitterResponse {
+    #[serde(rename = "_sName")]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct PreviewMediaResponse {
+    #[serde(rename = "_aImages", default)]
+    images: Vec<ImageResponse>,
+}
+
+#[derive(Deserialize)]
+struct ImageResponse {
+    #[serde(rename = "_sBaseUrl")]
+    base_url: String,
+    #[serde(rename = "_sFile")]
+    file: String,
+}
+
+#[derive(Deserialize)]
+struct FileResponse {
+    #[serde(rename = "_idRow")]
+    id: u64,
+    #[serde(rename = "_sFile")]
+    file_name: String,
+    #[serde(rename = "_tsDateAdded")]
+    date_added: i64,
+    #[serde(rename = "_sDownloadUrl")]
+    download_url: String,
+}
+
+impl GameBanana {
+    pub fn new(api_url: Url) -> Self {
+        Self {
+            api_url,
+            client: HttpClient::default(),
+        }
+    }
+
+    /// Use a configured HTTP client.
+    pub fn with_client(self, client: HttpClient) -> Self {
+        Self { client, ..self }
+    }
+
+    /// Resolve a file of a mod.
+    /// If no file is specified, the newest file is selected.
+    pub async fn resolve(&self, mod_id: u64, file_id: Option<u64>) -> Result<ResolvedFile, GameBananaError> {
+        let profile_page = self.get_profile_page(mod_id).await?;
+        let file = match file_id {
+            Some(file_id) => profile_page
+                .files
+                .iter()
+                .find(|file| file.id == file_id)
+                .ok_or(GameBananaError::FileNotFound(mod_id, file_id))?,
+            None => profile_page
+                .files
+                .iter()
+                .max_by_key(|file| file.date_added)
+                .ok_or(GameBananaError::NoFile(mod_id))?,
+        };
+        let download_url =
+            Url::parse(&file.download_url).map_err(|_| GameBananaError::InvalidUrl(file.download_url.clone()))?;
+        // The file name is used to create a file on disk, never trust it to be a file name only.
+        let file_name = std::path::Path::new(&file.file_name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| GameBananaError::InvalidUrl(file.download_url.clone()))?
+            .to_string();
+
+        Ok(ResolvedFile {
+            file_id: file.id,
+            file_name,
+            download_url,
+            metadata: SourceMetadata {
+                title: profile_page.name.clone(),
+                author: profile_page.submitter.as_ref().map(|submitter| submitter.name.clone()),
+                version: Some(profile_page.version.clone()).filter(|version| !version.is_empty()),
+                screenshot_urls: profile_page
+                    .preview_media
+                    .iter()
+                    .flat_map(|media| media.images.iter())
+                    .map(|image| format!("{}/{}", image.base_url, image.file))
+                    .collect(),
+            },
+        })
+    }
+
+    async fn get_profile_page(&self, mod_id: u64) -> Result<ProfilePageResponse, GameBananaError> {
+        let mut url = self.api_url.clone();
+
+        url.path_segments_mut()
+            .map_err(|_| GameBananaError::InvalidUrl(self.api_url.to_string()))?
+            .pop_if_empty()
+            .extend(&["Mod", &mod_id.to_string(), "ProfilePage"]);
+
+        Ok(self.client.send_json(self.client.get(url)).await?)
+    }
+}
+
+/// Parse the URL of a mod page, such as `https://gamebanana.com/mods/409271`.
+/// Returns the identifier of the mod.
+pub fn parse_mod_url(url: &str) -> Option<u64> {
+    let url = Url::parse(url).ok()?;
+
+    if url.host_str() != Some("gamebanana.com") && url.host_str() != Some("www.gamebanana.com") {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.filter(|segment| !segment.is_empty()).collect();
+
+    match segments.as_slice() {
+        ["mods", mod_id] => mod_id.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mod_url, GameBanana, GameBananaError};
+    use reqwest::Url;
+    use test_case::test_case;
+
+    const PROFILE_PAGE: &str = include_str!("../fixtures/gamebanana/profile_page.json");
+
+    #[test_case("https://gamebanana.com/mods/409271", Some(409271))]
+    #[test_case("https://gamebanana.com/mods/409271/", Some(409271))]
+    #[test_case("https://gamebanana.com/mods/cats/1649", None)]
+    #[test_case("https://gamebanana.com/dl/815166", None)]
+    #[test_case("https://github.com/mods/409271", None)]
+    fn test_parse_mod_url(input: &str, expected: Option<u64>) {
+        assert_eq!(expected, parse_mod_url(input))
+    }
+
+    async fn gamebanana_server() -> (mockito::ServerGuard, GameBanana) {
+        let mut server = mockito::Server::new_async().await;
+
+        server
+            .mock("GET", "/Mod/409271/ProfilePage")
+            .with_body(PROFILE_PAGE.replace("{server}", &server.url()))
+            .create_async()
+            .await;
+
+        let gamebanana = GameBanana::new(Url::parse(&server.url()).unwrap());
+
+        (server, gamebanana)
+    }
+
+    #[tokio::test]
+    async fn test_resolve_newest_file() {
+        let (server, gamebanana) = gamebanana_server().await;
+        let resolved = gamebanana.resolve(409271, None).await.unwrap();
+
+        assert_eq!(815166, resolved.file_id);
+        assert_eq!("black-mesa-hud.zip", resolved.file_name);
+        assert_eq!(format!("{}/dl/815166", server.url()), resolved.download_url.as_str());
+        assert_eq!("Black Mesa HUD", resolved.metadata.title);
+        assert_eq!(Some("Lucifer".to_string()), resolved.metadata.author);
+        assert_eq!(Some("1.2".to_string()), resolved.metadata.version);
+        assert_eq!(
+            vec![
+                "https://images.gamebanana.com/img/ss/mods/63f5f1b0c2ab2.jpg".to_string(),
+                "https://images.gamebanana.com/img/ss/mods/63f5f1b1a3f4e.jpg".to_string(),
+            ],
+            resolved.metadata.screenshot_urls
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_specific_file() {
+        let (_server, gamebanana) = gamebanana_server().await;
+        let resolved = gamebanana.resolve(409271, Some(815165)).await.unwrap();
+
+        assert_eq!(815165, resolved.file_id);
+        assert_eq!("black-mesa-hud_old.rar", resolved.file_name);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unknown_file() {
+        let (_server, gamebanana) = gamebanana_server().await;
+
+        assert!(matches!(
+            gamebanana.resolve(409271, Some(1)).await,
+            Err(GameBananaError::FileNotFound(409271, 1))
+        ));
+    }
+}
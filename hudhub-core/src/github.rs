@@ -0,0 +1,281 @@
+//! Resolve HUDs hosted in a GitHub repository.
+//! A repository source tracks a reference (a branch, a tag or the latest release). The reference is resolved
+//! to a commit using the GitHub REST API, and the archive is downloaded from codeload.
+
+use crate::http::{HttpClient, HttpError};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+const DEFAULT_CODELOAD_URL: &str = "https://codeload.github.com";
+
+/// The reference tracked by a GitHub source.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum GitHubReference {
+    /// The default branch of the repository, as configured on GitHub.
+    DefaultBranch,
+    Branch(String),
+    Tag(String),
+    /// The tag of the latest published release.
+    LatestRelease,
+}
+
+impl Display for GitHubReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitHubReference::DefaultBranch => write!(f, "default branch"),
+            GitHubReference::Branch(branch) => write!(f, "branch '{}'", branch),
+            GitHubReference::Tag(tag) => write!(f, "tag '{}'", tag),
+            GitHubReference::LatestRelease => write!(f, "latest release"),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GitHubError {
+    #[error(transparent)]
+    RequestFailed(#[from] HttpError),
+
+    #[error("Invalid GitHub URL: {0}")]
+    InvalidUrl(String),
+}
+
+/// A reference resolved to a commit, and the archive to download for it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolvedReference {
+    pub commit_sha: String,
+    pub archive_url: Url,
+    /// The file name to give to the downloaded archive.
+    pub archive_file_name: String,
+    /// The name to give to the root directory of the archive. The archive of a commit names it after the commit,
+    /// it is renamed after the reference so the name of the HUD does not change each time the reference moves.
+    pub root_directory_name: String,
+}
+
+/// Client for the GitHub REST API.
+/// The base URLs are configurable, so tests can run against a local server.
+#[derive(Clone, Debug)]
+pub struct GitHub {
+    api_url: Url,
+    codeload_url: Url,
+    client: HttpClient,
+}
+
+impl Default for GitHub {
+    fn default() -> Self {
+        Self::new(
+            Url::parse(DEFAULT_API_URL).expect("valid GitHub API URL"),
+            Url::parse(DEFAULT_CODELOAD_URL).expect("valid codeload URL"),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct RepositoryResponse {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct ReleaseResponse {
+    tag_name: String,
+}
+
+#[derive(Deserialize)]
+struct CommitResponse {
+    sha: String,
+}
+
+impl GitHub {
+    pub fn new(api_url: Url, codeload_url: Url) -> Self {
+        Self {
+            api_url,
+            codeload_url,
+            // The GitHub API rejects requests without user agent, the default client sets one.
+            client: HttpClient::default(),
+        }
+    }
+
+    /// Use a configured HTTP client.
+    pub fn with_client(self, client: HttpClient) -> Self {
+        Self { client, ..self }
+    }
+
+    /// Resolve a reference to the commit it currently points to, and to the URL of the archive.
+    pub async fn resolve(
+        &self,
+        owner: &str,
+        repository: &str,
+        reference: &GitHubReference,
+    ) -> Result<ResolvedReference, GitHubError> {
+        let name = match reference {
+            GitHubReference::DefaultBranch => self.get_default_branch(owner, repository).await?,
+            GitHubReference::Branch(branch) => branch.clone(),
+            GitHubReference::Tag(tag) => tag.clone(),
+            GitHubReference::LatestRelease => self.get_latest_release_tag(owner, repository).await?,
+        };
+        let commit_sha = self.get_commit_sha(owner, repository, &name).await?;
+        // The archive is downloaded by commit, the reference may have moved since it was resolved.
+        let archive_url = self.url(&self.codeload_url, &[owner, repository, "zip", &commit_sha])?;
+        let root_directory_name = format!("{}-{}", repository, name.replace('/', "-"));
+
+        Ok(ResolvedReference {
+            commit_sha,
+            archive_url,
+            archive_file_name: format!("{}.zip", root_directory_name),
+            root_directory_name,
+        })
+    }
+
+    async fn get_default_branch(&self, owner: &str, repository: &str) -> Result<String, GitHubError> {
+        let response: RepositoryResponse = self.get_json(&["repos", owner, repository]).await?;
+
+        Ok(response.default_branch)
+    }
+
+    async fn get_latest_release_tag(&self, owner: &str, repository: &str) -> Result<String, GitHubError> {
+        let response: ReleaseResponse = self.get_json(&["repos", owner, repository, "releases", "latest"]).await?;
+
+        Ok(response.tag_name)
+    }
+
+    async fn get_commit_sha(&self, owner: &str, repository: &str, reference: &str) -> Result<String, GitHubError> {
+        let response: CommitResponse = self.get_json(&["repos", owner, repository, "commits", reference]).await?;
+
+        Ok(response.sha)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, segments: &[&str]) -> Result<T, GitHubError> {
+        let url = self.url(&self.api_url, segments)?;
+        let request = self
+            .client
+            .get(url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+
+        Ok(self.client.send_json(request).await?)
+    }
+
+    fn url(&self, base_url: &Url, segments: &[&str]) -> Result<Url, GitHubError> {
+        let mut url = base_url.clone();
+
+        url.path_segments_mut()
+            .map_err(|_| GitHubError::InvalidUrl(base_url.to_string()))?
+            .pop_if_empty()
+            .extend(segments);
+
+        Ok(url)
+    }
+}
+
+/// Parse the URL of a repository page, such as `https://github.com/owner/repository/tree/branch`.
+/// Returns the owner, the repository and the reference.
+/// Archive URLs are not recognized, they can be downloaded directly.
+pub fn parse_repository_url(url: &str) -> Option<(String, String, GitHubReference)> {
+    let url = Url::parse(url).ok()?;
+
+    if url.host_str() != Some("github.com") && url.host_str() != Some("www.github.com") {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.filter(|segment| !segment.is_empty()).collect();
+    let (owner, repository) = match segments.as_slice() {
+        [owner, repository, ..] => (owner.to_string(), repository.trim_end_matches(".git").to_string()),
+        _ => return None,
+    };
+    let reference = match &segments[2..] {
+        [] => GitHubReference::DefaultBranch,
+        ["tree", branch @ ..] if !branch.is_empty() => GitHubReference::Branch(branch.join("/")),
+        ["releases", "tag", tag] => GitHubReference::Tag(tag.to_string()),
+        ["releases", "latest"] | ["releases"] => GitHubReference::LatestRelease,
+        _ => return None,
+    };
+
+    Some((owner, repository, reference))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_repository_url, GitHub, GitHubReference};
+    use reqwest::Url;
+    use test_case::test_case;
+
+    #[test_case("https://github.com/n0kk/ahud", Some(("n0kk", "ahud", GitHubReference::DefaultBranch)))]
+    #[test_case("https://github.com/n0kk/ahud.git", Some(("n0kk", "ahud", GitHubReference::DefaultBranch)))]
+    #[test_case(
+        "https://github.com/n0kk/ahud/tree/feature/x",
+        Some(("n0kk", "ahud", GitHubReference::Branch("feature/x".into())))
+    )]
+    #[test_case(
+        "https://github.com/n0kk/ahud/releases/tag/v1.0",
+        Some(("n0kk", "ahud", GitHubReference::Tag("v1.0".into())))
+    )]
+    #[test_case("https://github.com/n0kk/ahud/releases/latest", Some(("n0kk", "ahud", GitHubReference::LatestRelease)))]
+    #[test_case("https://github.com/n0kk/ahud/archive/refs/heads/master.zip", None)]
+    #[test_case("https://gitlab.com/n0kk/ahud", None)]
+    #[test_case("https://github.com/n0kk", None)]
+    fn test_parse_repository_url(input: &str, expected: Option<(&str, &str, GitHubReference)>) {
+        let expected = expected.map(|(owner, repository, reference)| (owner.to_string(), repository.to_string(), reference));
+
+        assert_eq!(expected, parse_repository_url(input))
+    }
+
+    #[tokio::test]
+    async fn test_resolve_branch() {
+        let mut server = mockito::Server::new_async().await;
+        let _commit = server
+            .mock("GET", "/repos/n0kk/ahud/commits/master")
+            .with_body(r#"{"sha": "0123456789abcdef"}"#)
+            .create_async()
+            .await;
+        let url = Url::parse(&server.url()).unwrap();
+        let github = GitHub::new(url.clone(), url);
+        let resolved = github
+            .resolve("n0kk", "ahud", &GitHubReference::Branch("master".into()))
+            .await
+            .unwrap();
+
+        assert_eq!("0123456789abcdef", resolved.commit_sha);
+        assert_eq!("/n0kk/ahud/zip/0123456789abcdef", resolved.archive_url.path());
+        assert_eq!("ahud-master.zip", resolved.archive_file_name);
+        assert_eq!("ahud-master", resolved.root_directory_name);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_latest_release() {
+        let mut server = mockito::Server::new_async().await;
+        let _release = server
+            .mock("GET", "/repos/n0kk/ahud/releases/latest")
+            .with_body(r#"{"tag_name": "v2.0", "name": "Version 2"}"#)
+            .create_async()
+            .await;
+        let _commit = server
+            .mock("GET", "/repos/n0kk/ahud/commits/v2.0")
+            .with_body(r#"{"sha": "fedcba9876543210"}"#)
+            .create_async()
+            .await;
+        let url = Url::parse(&server.url()).unwrap();
+        let github = GitHub::new(url.clone(), url);
+        let resolved = github.resolve("n0kk", "ahud", &GitHubReference::LatestRelease).await.unwrap();
+
+        assert_eq!("fedcba9876543210", resolved.commit_sha);
+        assert_eq!("/n0kk/ahud/zip/fedcba9876543210", resolved.archive_url.path());
+        assert_eq!("ahud-v2.0", resolved.root_directory_name);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unknown_repository() {
+        let mut server = mockito::Server::new_async().await;
+        let _commit = server
+            .mock("GET", "/repos/n0kk/nothing/commits/master")
+            .with_status(404)
+            .create_async()
+            .await;
+        let url = Url::parse(&server.url()).unwrap();
+        let github = GitHub::new(url.clone(), url);
+
+        assert!(github
+            .resolve("n0kk", "nothing", &GitHubReference::Branch("master".into()))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! The HTTP client shared by all the requests.
+//! It sets the timeouts, the proxy, the certificates and the user agent, and retries the requests that failed
+//! because of the server or the network.
+
+use reqwest::{Certificate, Proxy, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_USER_AGENT: &str = concat!("hudhub/", env!("CARGO_PKG_VERSION"));
+
+#[derive(thiserror::Error, Debug)]
+pub enum HttpClientError {
+    #[error("Invalid proxy '{0}': {1}")]
+    InvalidProxy(String, reqwest::Error),
+
+    #[error("Failed to read the certificate '{0}': {1}")]
+    ReadCertificateFailed(PathBuf, std::io::Error),
+
+    #[error("Invalid certificate '{0}': {1}")]
+    InvalidCertificate(PathBuf, reqwest::Error),
+
+    #[error("Failed to create the HTTP client: {0}")]
+    CreateClientFailed(reqwest::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HttpError {
+    #[error(transparent)]
+    RequestFailed(#[from] reqwest::Error),
+
+    #[error("No data received from '{0}' for too long")]
+    Timeout(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpSettings {
+    /// The maximum time to establish a connection.
+    pub connect_timeout: Duration,
+    /// The maximum time waiting for the response, and for data while the response is read.
+    pub read_timeout: Duration,
+    /// How many times a request is sent again after a server error or a connection error.
+    /// A download receiving no data for longer than the read timeout is resumed as many times, if the server sent a
+    /// validator of the content.
+    pub max_retries: u32,
+    /// The time to wait before the first retry. The delay doubles after each retry.
+    pub initial_retry_delay: Duration,
+    /// The URL of the proxy used for all the requests, such as `http://proxy.example.com:8080`.
+    pub proxy_url: Option<String>,
+    /// PEM certificates trusted in addition to the certificates of the system.
+    pub ca_certificate_files: Vec<PathBuf>,
+    pub user_agent: String,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_retry_delay: Duration::from_millis(500),
+            proxy_url: None,
+            ca_certificate_files: Vec::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+}
+
+/// A configured HTTP client.
+/// Clones share the same connection pool.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    settings: HttpSettings,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(HttpSettings::default()).expect("create default HTTP client")
+    }
+}
+
+impl HttpClient {
+    pub fn new(settings: HttpSettings) -> Result<Self, HttpClientError> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(settings.connect_timeout)
+            .user_agent(&settings.user_agent);
+
+        if let Some(proxy_url) = settings.proxy_url.as_ref() {
+            let proxy = Proxy::all(proxy_url).map_err(|error| HttpClientError::InvalidProxy(proxy_url.clone(), error))?;
+
+            builder = builder.proxy(proxy);
+        }
+
+        for certificate_file in settings.ca_certificate_files.iter() {
+            let pem = std::fs::read(certificate_file)
+                .map_err(|error| HttpClientError::ReadCertificateFailed(certificate_file.clone(), error))?;
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|error| HttpClientError::InvalidCertificate(certificate_file.clone(), error))?;
+
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Ok(Self {
+            client: builder.build().map_err(HttpClientError::CreateClientFailed)?,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &HttpSettings {
+        &self.settings
+    }
+
+    pub(crate) fn get(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub(crate) fn head(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.client.head(url)
+    }
+
+    pub(crate) fn read_timeout(&self) -> Duration {
+        self.settings.read_timeout
+    }
+
+    /// Send a request, and send it again after a server error, a connection error or a timeout.
+    /// The last response is returned as is, so the caller decides what to do with the error status.
+    pub(crate) async fn send(&self, request: RequestBuilder) -> Result<Response, HttpError> {
+        let mut retry = 0;
+
+        loop {
+            // A request with a streamed body can not be sent twice.
+            let Some(attempt) = request.try_clone() else {
+                return self.send_once(request).await;
+            };
+            let result = self.send_once(attempt).await;
+            let should_retry = retry < self.settings.max_retries
+                && match result.as_ref() {
+                    Ok(response) => response.status().is_server_error(),
+                    Err(HttpError::RequestFailed(error)) => error.is_connect() || error.is_timeout(),
+                    Err(HttpError::Timeout(_)) => true,
+                };
+
+            if !should_retry {
+                return result;
+            }
+
+            tokio::time::sleep(self.retry_delay(retry)).await;
+            retry += 1;
+        }
+    }
+
+    /// Send a request, and read the body of the successful response as JSON.
+    pub(crate) async fn send_json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, HttpError> {
+        self.send_and_read(
+            request,
+            |response| async move { Ok(response.error_for_status()?.json().await?) },
+        )
+        .await
+    }
+
+    /// Send a request, and read the body of the successful response as text.
+    pub(crate) async fn send_text(&self, request: RequestBuilder) -> Result<String, HttpError> {
+        self.send_and_read(
+            request,
+            |response| async move { Ok(response.error_for_status()?.text().await?) },
+        )
+        .await
+    }
+
+    /// Send a request, and read the response with `read`.
+    /// The request is sent again if the response is not read within the read timeout.
+    pub(crate) async fn send_and_read<T, E, F, R>(&self, request: RequestBuilder, read: F) -> Result<T, E>
+    where
+        E: From<HttpError>,
+        F: Fn(Response) -> R,
+        R: Future<Output = Result<T, E>>,
+    {
+        let mut retry = 0;
+
+        loop {
+            let Some(attempt) = request.try_clone() else {
+                return self.read_once(request, &read).await?;
+            };
+
+            match self.read_once(attempt, &read).await {
+                Err(HttpError::Timeout(_)) if retry < self.settings.max_retries => {
+                    tokio::time::sleep(self.retry_delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result?,
+            }
+        }
+    }
+
+    /// Send a request, and read the response. Returns an error only if reading the response timed out, the errors of
+    /// the request were already retried by [`HttpClient::send`].
+    async fn read_once<T, E, F, R>(&self, request: RequestBuilder, read: &F) -> Result<Result<T, E>, HttpError>
+    where
+        E: From<HttpError>,
+        F: Fn(Response) -> R,
+        R: Future<Output = Result<T, E>>,
+    {
+        let response = match self.send(request).await {
+            Ok(response) => response,
+            Err(error) => return Ok(Err(error.into())),
+        };
+        let url = response.url().to_string();
+
+        tokio::time::timeout(self.settings.read_timeout, read(response))
+            .await
+            .map_err(|_| HttpError::Timeout(url))
+    }
+
+    /// Send a request once, giving up if the server does not answer within the read timeout.
+    async fn send_once(&self, request: RequestBuilder) -> Result<Response, HttpError> {
+        let request = request.build()?;
+        let url = request.url().to_string();
+
+        tokio::time::timeout(self.settings.read_timeout, self.client.execute(request))
+            .await
+            .map_err(|_| HttpError::Timeout(url))?
+            .map_err(HttpError::from)
+    }
+
+    fn retry_delay(&self, retry: u32) -> Duration {
+        self.settings.initial_retry_delay.saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpClient, HttpClientError, HttpError, HttpSettings};
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn client(max_retries: u32) -> HttpClient {
+        HttpClient::new(HttpSettings {
+            max_retries,
+            initial_retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_error() {
+        let mut server = mockito::Server::new_async().await;
+        let error_mock = server.mock("GET", "/hud.zip").with_status(503).expect(2).create_async().await;
+        let mock = server.mock("GET", "/hud.zip").with_body("hud").expect(1).create_async().await;
+        let client = client(3);
+
+        let response = client.send(client.get(format!("{}/hud.zip", server.url()))).await.unwrap();
+
+        error_mock.assert_async().await;
+        mock.assert_async().await;
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/hud.zip").with_status(500).expect(3).create_async().await;
+        let client = client(2);
+
+        let response = client.send(client.get(format!("{}/hud.zip", server.url()))).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(500, response.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/hud.zip").with_status(404).expect(1).create_async().await;
+        let client = client(3);
+
+        client.send(client.get(format!("{}/hud.zip", server.url()))).await.unwrap();
+
+        mock.assert_async().await;
+    }
+
+    /// A server writing the next response to each connection. The connections are kept open, so a connection without
+    /// response, or with an incomplete response, is a server that stopped answering.
+    /// Returns the URL of the server, and the count of the connections.
+    fn start_server(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let server_connections = connections.clone();
+
+        std::thread::spawn(move || {
+            let mut streams = Vec::new();
+
+            for (index, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+
+                server_connections.fetch_add(1, Ordering::SeqCst);
+
+                if let Some(response) = responses.get(index) {
+                    read_request(&mut stream);
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+
+                streams.push(stream);
+            }
+        });
+
+        (url, connections)
+    }
+
+    /// Read the request line and the headers of a request without body.
+    fn read_request(stream: &mut std::net::TcpStream) {
+        let mut request = Vec::new();
+        let mut byte = [0u8];
+
+        while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+            request.push(byte[0]);
+        }
+    }
+
+    fn client_with_read_timeout(max_retries: u32) -> HttpClient {
+        HttpClient::new(HttpSettings {
+            max_retries,
+            initial_retry_delay: Duration::from_millis(1),
+            read_timeout: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_timeout() {
+        let (url, connections) = start_server(Vec::new());
+        let client = client_with_read_timeout(2);
+
+        let result = client.send(client.get(url)).await;
+
+        assert!(matches!(result, Err(HttpError::Timeout(_))));
+        assert_eq!(3, connections.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_retry_body_timeout() {
+        let (url, connections) = start_server(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhel",
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+        ]);
+        let client = client_with_read_timeout(2);
+
+        let text = client.send_text(client.get(url)).await.unwrap();
+
+        assert_eq!("hello", text);
+        assert_eq!(2, connections.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_body_timeout_gives_up() {
+        let (url, connections) = start_server(vec!["HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhel"; 2]);
+        let client = client_with_read_timeout(1);
+
+        let result = client.send_json::<serde_json::Value>(client.get(url)).await;
+
+        assert!(matches!(result, Err(HttpError::Timeout(_))));
+        assert_eq!(2, connections.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_user_agent() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/")
+            .match_header("user-agent", "tester")
+            .create_async()
+            .await;
+        let client = HttpClient::new(HttpSettings {
+            user_agent: "tester".into(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        client.send(client.get(server.url())).await.unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_missing_certificate() {
+        let result = HttpClient::new(HttpSettings {
+            ca_certificate_files: vec!["does_not_exist.pem".into()],
+            ..Default::default()
+        });
+
+        assert!(matches!(result, Err(HttpClientError::ReadCertificateFailed(..))));
+    }
+
+    #[test]
+    fn test_invalid_proxy() {
+        let result = HttpClient::new(HttpSettings {
+            proxy_url: Some("not a proxy".into()),
+            ..Default::default()
+        });
+
+        assert!(matches!(result, Err(HttpClientError::InvalidProxy(..))));
+    }
+}
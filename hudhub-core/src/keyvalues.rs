@@ -0,0 +1,706 @@
+//! Parser of the KeyValues text format of Valve, used by info.vdf and the resource files of the HUDs.
+//! A file is a list of keys, each followed by a string or by a block of keys between braces:
+//! ```text
+//! #base "base.res"
+//! "ahud"
+//! {
+//!     "ui_version"    "3"
+//!     // A comment.
+//!     "font"          "Arial"     [$WIN32]
+//!     "font"          "Helvetica" [$OSX]
+//! }
+//! ```
+//! The tokens may be quoted or not, the quoted ones may have escapes. A key followed by a conditional, such as
+//! `[$WIN32]` or `[!$X360]`, is kept only if the conditional is true for the platform the file is read for.
+//! The `#include` directive appends the keys of another file, and the `#base` directive adds the keys of another file
+//! the file does not have.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// How deep the blocks can be nested, so a malformed file can not exhaust the stack.
+const MAX_NESTING_DEPTH: usize = 256;
+/// How deep the files can include other files.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeyValuesError {
+    #[error("Failed to read '{0}': {1}")]
+    ReadFailed(PathBuf, std::io::Error),
+    #[error("Invalid file '{0}': {1}")]
+    InvalidFile(PathBuf, Box<KeyValuesError>),
+    #[error("Unexpected {0} line {1}")]
+    UnexpectedToken(String, usize),
+    #[error("Unexpected end of file, a block is not closed")]
+    UnexpectedEnd,
+    #[error("The string starting line {0} is not closed")]
+    UnterminatedString(usize),
+    #[error("The conditional line {0} is not closed")]
+    UnterminatedConditional(usize),
+    #[error("The blocks are nested too deep line {0}")]
+    NestingTooDeep(usize),
+}
+
+/// The value of a key: a string, or a block of keys.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KeyValue {
+    String(String),
+    Block(KeyValues),
+}
+
+impl KeyValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            KeyValue::String(value) => Some(value),
+            KeyValue::Block(_) => None,
+        }
+    }
+
+    pub fn as_block(&self) -> Option<&KeyValues> {
+        match self {
+            KeyValue::String(_) => None,
+            KeyValue::Block(block) => Some(block),
+        }
+    }
+}
+
+/// The keys of a file or of a block, in the order of the file. A key may be there more than once.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct KeyValues {
+    pairs: Vec<(String, KeyValue)>,
+}
+
+impl KeyValues {
+    /// Parse a text for the current platform. The `#base` and `#include` directives are not followed, there is no
+    /// file to find the included files from, see [`KeyValues::load`].
+    pub fn parse(text: &str) -> Result<Self, KeyValuesError> {
+        Self::parse_with(text, &Conditions::default())
+    }
+
+    pub fn parse_with(text: &str, conditions: &Conditions) -> Result<Self, KeyValuesError> {
+        parse_document(text, conditions).map(|(key_values, _)| key_values)
+    }
+
+    /// Read a file for the current platform, with the files it includes.
+    /// The included files are found relatively to the directory of the file. The ones that do not exist are ignored,
+    /// the HUDs often include files of the game.
+    pub fn load(file_path: impl AsRef<Path>) -> Result<Self, KeyValuesError> {
+        Self::load_with(file_path, &Conditions::default())
+    }
+
+    pub fn load_with(file_path: impl AsRef<Path>, conditions: &Conditions) -> Result<Self, KeyValuesError> {
+        load_file(file_path.as_ref(), conditions, &mut Vec::new())
+    }
+
+    /// The value of the first key named `key`. The keys are not case sensitive.
+    pub fn get(&self, key: &str) -> Option<&KeyValue> {
+        self.iter()
+            .find(|(other_key, _)| other_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(KeyValue::as_str)
+    }
+
+    pub fn get_block(&self, key: &str) -> Option<&KeyValues> {
+        self.get(key).and_then(KeyValue::as_block)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &KeyValue)> {
+        self.pairs.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Add the keys of a base file this one does not have. The blocks both have are merged the same way.
+    fn merge_base(&mut self, base: KeyValues) {
+        for (key, base_value) in base.pairs {
+            match self
+                .pairs
+                .iter_mut()
+                .find(|(other_key, _)| other_key.eq_ignore_ascii_case(&key))
+            {
+                Some((_, KeyValue::Block(block))) => {
+                    if let KeyValue::Block(base_block) = base_value {
+                        block.merge_base(base_block);
+                    }
+                }
+                Some(_) => {}
+                None => self.pairs.push((key, base_value)),
+            }
+        }
+    }
+}
+
+/// The conditions a file is read for, such as `WIN32`. A conditional is true if the conditions it requires are
+/// defined, so a key followed by `[$WIN32]` is only kept on Windows.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Conditions {
+    defined: Vec<String>,
+}
+
+impl Default for Conditions {
+    fn default() -> Self {
+        Self::current_platform()
+    }
+}
+
+impl Conditions {
+    /// The names of the defined conditions, without the `$`, such as `WIN32`.
+    pub fn new(defined: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            defined: defined.into_iter().map(|name| name.into().to_ascii_uppercase()).collect(),
+        }
+    }
+
+    /// The conditions the game defines on the platform the application runs on.
+    pub fn current_platform() -> Self {
+        if cfg!(windows) {
+            Self::new(["WIN32", "WINDOWS"])
+        } else if cfg!(target_os = "macos") {
+            Self::new(["OSX", "POSIX"])
+        } else {
+            Self::new(["LINUX", "POSIX"])
+        }
+    }
+
+    /// Evaluate a conditional, without its brackets, such as `$WIN32||$OSX` or `!$X360`.
+    /// The `&&` are evaluated before the `||`.
+    fn evaluate(&self, conditional: &str) -> bool {
+        conditional.split("||").any(|terms| {
+            terms.split("&&").all(|term| {
+                let term = term.trim();
+                let (negated, term) = match term.strip_prefix('!') {
+                    Some(term) => (true, term.trim_start()),
+                    None => (false, term),
+                };
+                let name = term.strip_prefix('$').unwrap_or(term);
+
+                self.defined.iter().any(|defined| defined.eq_ignore_ascii_case(name)) != negated
+            })
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DirectiveKind {
+    Base,
+    Include,
+}
+
+/// A `#base` or an `#include` directive, with the path of the file it includes.
+struct Directive {
+    kind: DirectiveKind,
+    path: String,
+}
+
+fn load_file(
+    file_path: &Path,
+    conditions: &Conditions,
+    loading_files: &mut Vec<PathBuf>,
+) -> Result<KeyValues, KeyValuesError> {
+    let text = read_text(file_path)?;
+    let (mut key_values, directives) = parse_document(&text, conditions)
+        .map_err(|error| KeyValuesError::InvalidFile(file_path.to_path_buf(), Box::new(error)))?;
+    let directory = file_path.parent().unwrap_or(Path::new(""));
+
+    loading_files.push(std::fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf()));
+
+    for directive in directives {
+        // The paths are often written with backslashes, as the game runs on Windows.
+        let included_file_path = directory.join(directive.path.replace('\\', "/"));
+        let canonical_path = std::fs::canonicalize(&included_file_path).unwrap_or_else(|_| included_file_path.clone());
+
+        if !included_file_path.is_file()
+            || loading_files.contains(&canonical_path)
+            || loading_files.len() >= MAX_INCLUDE_DEPTH
+        {
+            continue;
+        }
+
+        let included = load_file(&included_file_path, conditions, loading_files)?;
+
+        match directive.kind {
+            DirectiveKind::Include => key_values.pairs.extend(included.pairs),
+            DirectiveKind::Base => key_values.merge_base(included),
+        }
+    }
+
+    loading_files.pop();
+
+    Ok(key_values)
+}
+
+fn read_text(file_path: &Path) -> Result<String, KeyValuesError> {
+    let bytes = std::fs::read(file_path).map_err(|e| KeyValuesError::ReadFailed(file_path.to_path_buf(), e))?;
+
+    Ok(decode_text(&bytes))
+}
+
+/// Decode a text file, in UTF-8 or, if it starts with its byte order mark, in UTF-16 as some resource files are.
+pub(crate) fn decode_text(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(utf16_bytes) => String::from_utf16_lossy(
+            &utf16_bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Parse the keys of a file, and the directives found between them.
+fn parse_document(text: &str, conditions: &Conditions) -> Result<(KeyValues, Vec<Directive>), KeyValuesError> {
+    let mut tokenizer = Tokenizer::new(text);
+    let mut key_values = KeyValues::default();
+    let mut directives = Vec::new();
+
+    while let Some(token) = tokenizer.next_token()? {
+        if let Some(kind) = directive_kind(&token) {
+            match tokenizer.next_token()? {
+                Some(Token::String { text: path, .. }) => directives.push(Directive { kind, path }),
+                token => return Err(tokenizer.unexpected(token)),
+            }
+
+            continue;
+        }
+
+        match token {
+            Token::String { text: key, .. } => parse_pair(key, &mut tokenizer, conditions, &mut key_values, 0)?,
+            token => return Err(tokenizer.unexpected(Some(token))),
+        }
+    }
+
+    Ok((key_values, directives))
+}
+
+/// The directives are unquoted tokens, a quoted `"#base"` is a key.
+fn directive_kind(token: &Token) -> Option<DirectiveKind> {
+    match token {
+        Token::String { text, quoted: false } if text.eq_ignore_ascii_case("#base") => Some(DirectiveKind::Base),
+        Token::String { text, quoted: false } if text.eq_ignore_ascii_case("#include") => Some(DirectiveKind::Include),
+        _ => None,
+    }
+}
+
+/// Parse the value of a key, and add the key if its conditionals are true.
+fn parse_pair(
+    key: String,
+    tokenizer: &mut Tokenizer,
+    conditions: &Conditions,
+    key_values: &mut KeyValues,
+    depth: usize,
+) -> Result<(), KeyValuesError> {
+    let mut is_included = true;
+    let mut token = tokenizer.next_token()?;
+
+    // A block may have its conditional before its brace.
+    if let Some(Token::Conditional(conditional)) = &token {
+        is_included = conditions.evaluate(conditional);
+        token = tokenizer.next_token()?;
+    }
+
+    let value = match token {
+        Some(Token::OpenBrace) => KeyValue::Block(parse_block(tokenizer, conditions, depth + 1)?),
+        Some(Token::String { text, .. }) => {
+            if let Some(Token::Conditional(conditional)) = tokenizer.peek_token()? {
+                is_included &= conditions.evaluate(&conditional);
+                tokenizer.next_token()?;
+            }
+
+            KeyValue::String(text)
+        }
+        token => return Err(tokenizer.unexpected(token)),
+    };
+
+    if is_included {
+        key_values.pairs.push((key, value));
+    }
+
+    Ok(())
+}
+
+/// Parse the keys of a block, after its opening brace.
+fn parse_block(tokenizer: &mut Tokenizer, conditions: &Conditions, depth: usize) -> Result<KeyValues, KeyValuesError> {
+    if depth > MAX_NESTING_DEPTH {
+        return Err(KeyValuesError::NestingTooDeep(tokenizer.line));
+    }
+
+    let mut key_values = KeyValues::default();
+
+    loop {
+        match tokenizer.next_token()? {
+            Some(Token::CloseBrace) => return Ok(key_values),
+            Some(Token::String { text: key, .. }) => parse_pair(key, tokenizer, conditions, &mut key_values, depth)?,
+            None => return Err(KeyValuesError::UnexpectedEnd),
+            token => return Err(tokenizer.unexpected(token)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    String {
+        text: String,
+        quoted: bool,
+    },
+    OpenBrace,
+    CloseBrace,
+    /// A conditional without its brackets, such as `$WIN32`.
+    Conditional(String),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::String { text, .. } => write!(f, "'{}'", text),
+            Token::OpenBrace => write!(f, "'{{'"),
+            Token::CloseBrace => write!(f, "'}}'"),
+            Token::Conditional(conditional) => write!(f, "'[{}]'", conditional),
+        }
+    }
+}
+
+struct Tokenizer<'a> {
+    text: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text: text.strip_prefix('\u{FEFF}').unwrap_or(text),
+            position: 0,
+            line: 1,
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<Token>, KeyValuesError> {
+        let (position, line) = (self.position, self.line);
+        let token = self.next_token();
+
+        self.position = position;
+        self.line = line;
+
+        token
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, KeyValuesError> {
+        self.skip_whitespaces_and_comments();
+
+        let Some(character) = self.rest().chars().next() else {
+            return Ok(None);
+        };
+
+        match character {
+            '{' => {
+                self.position += 1;
+
+                Ok(Some(Token::OpenBrace))
+            }
+            '}' => {
+                self.position += 1;
+
+                Ok(Some(Token::CloseBrace))
+            }
+            '"' => self.read_quoted_string().map(Some),
+            '[' => self.read_conditional().map(Some),
+            _ => {
+                let length = self
+                    .rest()
+                    .find(|character: char| character.is_whitespace() || matches!(character, '"' | '{' | '}'))
+                    .unwrap_or(self.rest().len());
+                let text = self.rest()[..length].to_string();
+
+                self.position += length;
+
+                Ok(Some(Token::String { text, quoted: false }))
+            }
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespaces_and_comments(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+
+            self.line += rest[..rest.len() - trimmed.len()].matches('\n').count();
+            self.position += rest.len() - trimmed.len();
+
+            if !trimmed.starts_with("//") {
+                return;
+            }
+
+            self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    /// Read a string between quotes. The escapes are `\n`, `\t`, `\\` and `\"`, any other backslash is kept as is,
+    /// as the paths of the files of the game are often written with backslashes.
+    fn read_quoted_string(&mut self) -> Result<Token, KeyValuesError> {
+        let first_line = self.line;
+        let mut text = String::new();
+        let mut characters = self.rest().char_indices().skip(1);
+
+        while let Some((index, character)) = characters.next() {
+            match character {
+                '"' => {
+                    self.position += index + 1;
+
+                    return Ok(Token::String { text, quoted: true });
+                }
+                '\\' => match characters.next() {
+                    Some((_, 'n')) => text.push('\n'),
+                    Some((_, 't')) => text.push('\t'),
+                    Some((_, '\\')) => text.push('\\'),
+                    Some((_, '"')) => text.push('"'),
+                    Some((_, other)) => {
+                        text.push('\\');
+                        text.push(other);
+                        self.line += usize::from(other == '\n');
+                    }
+                    None => break,
+                },
+                '\n' => {
+                    text.push(character);
+                    self.line += 1;
+                }
+                _ => text.push(character),
+            }
+        }
+
+        Err(KeyValuesError::UnterminatedString(first_line))
+    }
+
+    fn read_conditional(&mut self) -> Result<Token, KeyValuesError> {
+        let rest = self.rest();
+
+        match rest.find([']', '\n']) {
+            Some(end) if rest[end..].starts_with(']') => {
+                self.position += end + 1;
+
+                Ok(Token::Conditional(rest[1..end].trim().to_string()))
+            }
+            _ => Err(KeyValuesError::UnterminatedConditional(self.line)),
+        }
+    }
+
+    fn unexpected(&self, token: Option<Token>) -> KeyValuesError {
+        match token {
+            Some(token) => KeyValuesError::UnexpectedToken(token.to_string(), self.line),
+            None => KeyValuesError::UnexpectedEnd,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conditions, KeyValue, KeyValues, KeyValuesError};
+    use tempdir::TempDir;
+    use test_case::test_case;
+
+    fn windows() -> Conditions {
+        Conditions::new(["WIN32", "WINDOWS"])
+    }
+
+    #[test]
+    fn test_parse_info_vdf() {
+        let key_values = KeyValues::parse("\"ahud\"\n{\n\t\"ui_version\"\t\"3\"\n}\n").unwrap();
+        let (name, value) = key_values.iter().next().unwrap();
+
+        assert_eq!("ahud", name);
+        assert_eq!(Some("3"), value.as_block().unwrap().get_str("ui_version"));
+    }
+
+    #[test]
+    fn test_parse_unquoted_tokens_and_comments() {
+        let text = "
+            // The layout of the HUD.
+            Resource/HudLayout.res
+            {
+                HudHealth // The health.
+                {
+                    xpos    c-250
+                    wide    100 // Not too wide.
+                }
+            }
+        ";
+
+        let key_values = KeyValues::parse(text).unwrap();
+        let health = key_values
+            .get_block("resource/hudlayout.res")
+            .and_then(|layout| layout.get_block("HudHealth"))
+            .unwrap();
+
+        assert_eq!(Some("c-250"), health.get_str("xpos"));
+        assert_eq!(Some("100"), health.get_str("WIDE"));
+        assert_eq!(2, health.len());
+    }
+
+    #[test_case(r#""a\"b""#, "a\"b" ; "quote")]
+    #[test_case(r#""a\\b""#, "a\\b" ; "backslash")]
+    #[test_case(r#""a\nb\tc""#, "a\nb\tc" ; "new line and tab")]
+    #[test_case(r#""resource\ui\hudlayout.res""#, "resource\\ui\\hudlayout.res" ; "path")]
+    #[test_case("\"a\n// b\"", "a\n// b" ; "comment in string")]
+    fn test_parse_escapes(value: &str, expected: &str) {
+        let key_values = KeyValues::parse(&format!("key {}", value)).unwrap();
+
+        assert_eq!(Some(expected), key_values.get_str("key"));
+    }
+
+    #[test]
+    fn test_parse_conditionals() {
+        let text = r#"
+            "fonts"
+            {
+                "font"  "Tahoma"    [$WIN32]
+                "font"  "Verdana"   [!$WIN32]
+                "tall"  "12"        [$X360||$WINDOWS]
+                "wide"  "10"        [$WIN32&&!$WINDOWS]
+                "osx" [$OSX]
+                {
+                    "font"  "Helvetica"
+                }
+            }
+        "#;
+
+        let windows_fonts = KeyValues::parse_with(text, &windows()).unwrap();
+        let windows_fonts = windows_fonts.get_block("fonts").unwrap();
+        let osx_fonts = KeyValues::parse_with(text, &Conditions::new(["OSX", "POSIX"])).unwrap();
+        let osx_fonts = osx_fonts.get_block("fonts").unwrap();
+
+        assert_eq!(Some("Tahoma"), windows_fonts.get_str("font"));
+        assert_eq!(Some("12"), windows_fonts.get_str("tall"));
+        assert_eq!(None, windows_fonts.get("wide"));
+        assert_eq!(None, windows_fonts.get("osx"));
+        assert_eq!(Some("Verdana"), osx_fonts.get_str("font"));
+        assert_eq!(None, osx_fonts.get("tall"));
+        assert_eq!(Some("Helvetica"), osx_fonts.get_block("osx").unwrap().get_str("font"));
+    }
+
+    #[test]
+    fn test_parse_repeated_keys() {
+        let key_values = KeyValues::parse("a 1 b 2 a 3").unwrap();
+
+        assert_eq!(
+            vec![
+                ("a", &KeyValue::String("1".into())),
+                ("b", &KeyValue::String("2".into())),
+                ("a", &KeyValue::String("3".into()))
+            ],
+            key_values.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some("1"), key_values.get_str("a"));
+    }
+
+    #[test]
+    fn test_parse_byte_order_mark() {
+        assert_eq!(
+            Some("3"),
+            KeyValues::parse("\u{FEFF}ui_version 3").unwrap().get_str("ui_version")
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_directives() {
+        let key_values = KeyValues::parse("#base \"base.res\"\nahud { }").unwrap();
+
+        assert_eq!(1, key_values.len());
+        assert!(key_values.get_block("ahud").unwrap().is_empty());
+    }
+
+    #[test_case("ahud {", KeyValuesError::UnexpectedEnd ; "block not closed")]
+    #[test_case("ahud { a 1 } }", KeyValuesError::UnexpectedToken("'}'".into(), 1) ; "unexpected brace")]
+    #[test_case("ahud\n{\n\"a\" \"1\n}", KeyValuesError::UnterminatedString(3) ; "string not closed")]
+    #[test_case("ahud { a 1 [$WIN32\n}", KeyValuesError::UnterminatedConditional(1) ; "conditional not closed")]
+    #[test_case("ahud", KeyValuesError::UnexpectedEnd ; "key without value")]
+    #[test_case("#base", KeyValuesError::UnexpectedEnd ; "directive without path")]
+    #[test_case(&"a {".repeat(1000), KeyValuesError::NestingTooDeep(1) ; "nested too deep")]
+    fn test_parse_invalid(text: &str, expected: KeyValuesError) {
+        assert_eq!(expected.to_string(), KeyValues::parse(text).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_load_with_base_and_include() {
+        let directory = TempDir::new("test_load_with_base_and_include").unwrap();
+        let scheme_directory = directory.path().join("scheme");
+        std::fs::create_dir_all(&scheme_directory).unwrap();
+        std::fs::write(
+            directory.path().join("clientscheme.res"),
+            "#base \"scheme\\base.res\"\n#include \"scheme/fonts.res\"\n#base \"missing.res\"\nScheme\n{\n\tColors\n\t{\n\t\tWhite \"255 255 255 255\"\n\t}\n}",
+        )
+        .unwrap();
+        std::fs::write(
+            scheme_directory.join("base.res"),
+            "Scheme { Colors { White \"250 250 250 255\" Black \"0 0 0 255\" } Borders { } }",
+        )
+        .unwrap();
+        std::fs::write(scheme_directory.join("fonts.res"), "Fonts { Default { name Arial } }").unwrap();
+
+        let key_values = KeyValues::load_with(directory.path().join("clientscheme.res"), &windows()).unwrap();
+        let scheme = key_values.get_block("Scheme").unwrap();
+        let colors = scheme.get_block("Colors").unwrap();
+
+        assert_eq!(Some("255 255 255 255"), colors.get_str("White"));
+        assert_eq!(Some("0 0 0 255"), colors.get_str("Black"));
+        assert!(scheme.get_block("Borders").is_some());
+        assert_eq!(
+            Some("Arial"),
+            key_values
+                .get_block("Fonts")
+                .and_then(|fonts| fonts.get_block("Default"))
+                .and_then(|font| font.get_str("name"))
+        );
+    }
+
+    #[test]
+    fn test_load_with_cyclic_includes() {
+        let directory = TempDir::new("test_load_with_cyclic_includes").unwrap();
+        std::fs::write(directory.path().join("a.res"), "#include b.res\na 1").unwrap();
+        std::fs::write(directory.path().join("b.res"), "#include a.res\nb 2").unwrap();
+
+        let key_values = KeyValues::load(directory.path().join("a.res")).unwrap();
+
+        assert_eq!(Some("1"), key_values.get_str("a"));
+        assert_eq!(Some("2"), key_values.get_str("b"));
+        assert_eq!(2, key_values.len());
+    }
+
+    #[test]
+    fn test_load_utf16() {
+        let directory = TempDir::new("test_load_utf16").unwrap();
+        let file_path = directory.path().join("chat_english.txt");
+        let mut content = vec![0xFF, 0xFE];
+        content.extend("lang { Language English }".encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&file_path, content).unwrap();
+
+        let key_values = KeyValues::load(&file_path).unwrap();
+
+        assert_eq!(Some("English"), key_values.get_block("lang").unwrap().get_str("Language"));
+    }
+
+    #[test]
+    fn test_load_invalid_included_file() {
+        let directory = TempDir::new("test_load_invalid_included_file").unwrap();
+        std::fs::write(directory.path().join("a.res"), "#base b.res\na 1").unwrap();
+        std::fs::write(directory.path().join("b.res"), "b {").unwrap();
+
+        assert!(matches!(
+            KeyValues::load(directory.path().join("a.res")),
+            Err(KeyValuesError::InvalidFile(file_path, _)) if file_path.ends_with("b.res")
+        ));
+    }
+}
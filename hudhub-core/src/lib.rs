@@ -1,10 +1,48 @@
+mod archive;
+mod bulk;
+mod cache;
+mod catalog;
 mod deployment;
+mod download;
+mod gamebanana;
+mod github;
+mod http;
+mod keyvalues;
 mod package;
 mod registry;
+mod resolver;
+mod share_link;
 mod source;
+mod update;
+mod vpk;
 
-pub use deployment::{install, uninstall, InstallError};
+#[cfg(feature = "rar")]
+pub use archive::RarExtractor;
+#[cfg(feature = "sevenz")]
+pub use archive::SevenZipExtractor;
+pub use archive::{
+    ArchiveEntry, ArchiveError, ArchiveExtractor, ArchiveExtractors, ExceededLimit, Extraction, ExtractionLimits,
+    TarCompression, TarExtractor, ZipExtractor,
+};
+pub use bulk::{fetch_packages, parse_source_list, scan_packages, FetchOutcome, DEFAULT_MAX_CONCURRENT_FETCHES};
+pub use cache::{CacheEntry, CacheError, DownloadCache, DEFAULT_CACHE_MAX_SIZE_BYTES};
+pub use catalog::{Catalog, CatalogEntry, CatalogError, CatalogLocation, DEFAULT_CATALOG_URL};
+pub use deployment::{install, install_with, uninstall, InstallError, InstallMode};
+pub use download::{DownloadProgress, ProgressSender};
+pub use gamebanana::{GameBanana, GameBananaError};
+pub use github::{GitHub, GitHubError, GitHubReference};
+pub use http::{HttpClient, HttpClientError, HttpError, HttpSettings};
+pub use keyvalues::{Conditions, KeyValue, KeyValues, KeyValuesError};
 pub use package::{PackageEntry, HudName, OpenHudDirectoryError, OpenPackageError, Package, ScanPackageError};
 pub use registry::{HudInfo, Install, Registry};
+pub use resolver::{
+    Artifact, DownloadUrlResolver, GameBananaResolver, GitHubResolver, LocalDirectoryResolver, LocalFileResolver,
+    ResolvedSource, SourceResolver, SourceResolvers,
+};
 pub use reqwest::Url;
-pub use source::{fetch_package, FetchError, Source};
+pub use source::{
+    check_for_update, fetch_hud_with, fetch_package, fetch_package_with, scan_package, scan_package_with, FetchError,
+    FetchSettings, Revision, Source, SourceMetadata,
+};
+pub use update::{check_for_updates, UpdateCheck, UpdateStatus};
+pub use vpk::{VpkArchive, VpkEntry, VpkError};
@@ -2,6 +2,10 @@
 //! Usually, an installation package contains one file info.vdf, but it can contain
 //! more than one if the package contains multiple HUDs.
 
+use crate::archive::ArchiveEntry;
+use crate::keyvalues::{decode_text, KeyValues, KeyValuesError};
+use crate::source::{Revision, SourceMetadata};
+use crate::vpk::{package_name, VpkArchive, VpkError};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
@@ -35,6 +39,17 @@ pub struct PackageEntry {
 
     /// The kind of entry.
     pub kind: PackageEntryKind,
+
+    /// The name the HUD gives itself in its info.vdf file. It is often not the name of its directory.
+    /// None if the info.vdf file could not be read, or was not extracted yet.
+    pub declared_name: Option<String>,
+
+    /// The version of the HUD format the HUD declares in its info.vdf file.
+    pub ui_version: Option<u32>,
+
+    /// The files of a .vpk file, sorted, such as `resource/ui/hudlayout.res`.
+    /// Empty for a directory, or for a .vpk file that was not extracted yet.
+    pub vpk_files: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -56,33 +71,127 @@ impl PackageEntry {
         }
     }
 
+    /// A broken info.vdf file does not prevent the HUD from being installed, many HUDs do not care about it.
     fn directory(directory_path: impl AsRef<Path>) -> Result<Self, OpenHudDirectoryError> {
         let path = directory_path.as_ref().to_path_buf();
         assert!(path.is_dir());
-        let name = path
-            .file_name()
-            .and_then(|name| name.to_str())
-            .ok_or(OpenHudDirectoryError::FailedToFindHudName)?;
 
-        Ok(Self {
-            path: path.clone(),
-            name: HudName::new(name),
-            kind: PackageEntryKind::Directory,
-        })
+        let mut entry = Self::new(path, PackageEntryKind::Directory)?;
+
+        if let Ok(info) = InfoVdf::read(&entry.path.join(INFO_VDF_FILE_NAME)) {
+            entry.declared_name = Some(info.name);
+            entry.ui_version = info.ui_version;
+        }
+
+        Ok(entry)
     }
 
+    /// The .vpk files also hold sounds, models or materials: only the ones that contain HUD files are HUDs.
+    /// Most .vpk HUDs have no info.vdf file, but it is read when they do.
     fn vpk_file(file_path: impl AsRef<Path>) -> Result<Self, OpenHudDirectoryError> {
         let path = file_path.as_ref().to_path_buf();
         assert!(path.is_file());
-        let name = path
-            .file_stem()
-            .and_then(|name| name.to_str())
-            .ok_or(OpenHudDirectoryError::FailedToFindHudName)?;
+
+        let archive = VpkArchive::open(&path).map_err(|error| OpenHudDirectoryError::InvalidVpkFile(path.clone(), error))?;
+
+        if !archive.is_hud() {
+            return Err(OpenHudDirectoryError::NoHudFiles(path));
+        }
+
+        let mut entry = Self::new(path, PackageEntryKind::VpkFile)?;
+
+        if let Some(info) = InfoVdf::read_vpk(&archive) {
+            entry.declared_name = Some(info.name);
+            entry.ui_version = info.ui_version;
+        }
+
+        entry.vpk_files = archive.entries().iter().map(|vpk_entry| vpk_entry.path.clone()).collect();
+        entry.vpk_files.sort();
+
+        Ok(entry)
+    }
+
+    /// The HUD of a directory is named after the directory, and the HUD of a .vpk file after the file, without the
+    /// `_dir` suffix of a .vpk file split in chunks: `ahud_dir.vpk` is named `ahud`.
+    fn new(path: PathBuf, kind: PackageEntryKind) -> Result<Self, OpenHudDirectoryError> {
+        let name = match kind {
+            PackageEntryKind::Directory => path.file_name().and_then(|name| name.to_str()),
+            PackageEntryKind::VpkFile => path.file_stem().and_then(|stem| stem.to_str()).map(package_name),
+        };
+        let name = name.ok_or(OpenHudDirectoryError::FailedToFindHudName)?;
 
         Ok(Self {
-            path: path.clone(),
             name: HudName::new(name),
-            kind: PackageEntryKind::VpkFile,
+            path,
+            kind,
+            declared_name: None,
+            ui_version: None,
+            vpk_files: Vec::new(),
+        })
+    }
+
+    /// The HUD of an entry listed from an archive, if the entry is the info.vdf file of a HUD or a .vpk file.
+    fn from_archive_entry(root_directory: &Path, archive_entry: &ArchiveEntry) -> Option<Self> {
+        if archive_entry.is_directory {
+            return None;
+        }
+
+        let path = &archive_entry.path;
+        let entry = if path.file_name().and_then(|name| name.to_str()) == Some(INFO_VDF_FILE_NAME) {
+            Self::new(root_directory.join(path.parent()?), PackageEntryKind::Directory)
+        } else if path.extension().and_then(|e| e.to_str()) == Some(VALVE_PACKAGE_FILE_EXTENSION) {
+            Self::new(root_directory.join(path), PackageEntryKind::VpkFile)
+        } else {
+            return None;
+        };
+
+        entry.ok()
+    }
+}
+
+/// What a HUD declares in its info.vdf file, such as:
+/// ```text
+/// "ahud"
+/// {
+///     "ui_version"    "3"
+/// }
+/// ```
+struct InfoVdf {
+    name: String,
+    ui_version: Option<u32>,
+}
+
+impl InfoVdf {
+    fn read(file_path: &Path) -> Result<Self, OpenHudDirectoryError> {
+        let key_values = KeyValues::load(file_path).map_err(|error| match error {
+            KeyValuesError::ReadFailed(file_path, error) => OpenHudDirectoryError::FailedToReadVdfFile(file_path, error),
+            KeyValuesError::InvalidFile(invalid_file_path, error) if invalid_file_path == file_path => {
+                OpenHudDirectoryError::InvalidVdfFile(invalid_file_path, *error)
+            }
+            error => OpenHudDirectoryError::InvalidVdfFile(file_path.to_path_buf(), error),
+        })?;
+
+        Self::from_key_values(&key_values)
+    }
+
+    /// The info.vdf file at the root of a .vpk file, if there is one and it is valid.
+    fn read_vpk(archive: &VpkArchive) -> Option<Self> {
+        let content = archive.read(INFO_VDF_FILE_NAME).ok()?;
+        let key_values = KeyValues::parse(&decode_text(&content)).ok()?;
+
+        Self::from_key_values(&key_values).ok()
+    }
+
+    fn from_key_values(key_values: &KeyValues) -> Result<Self, OpenHudDirectoryError> {
+        let (name, value) = key_values.iter().next().ok_or(OpenHudDirectoryError::FailedToFindHudName)?;
+        let ui_version = value
+            .as_block()
+            .and_then(|block| block.get_str("ui_version"))
+            .and_then(|ui_version| ui_version.trim().parse().ok());
+
+        Ok(Self {
+            name: name.to_string(),
+            ui_version,
         })
     }
 }
@@ -91,6 +200,12 @@ impl PackageEntry {
 pub struct Package {
     pub root_directory: PathBuf,
     pub entries: Vec<PackageEntry>,
+    /// The revision of the source the package was fetched from, if the source is versioned.
+    pub revision: Option<Revision>,
+    /// The information published by the source, if the source provides it.
+    pub metadata: Option<SourceMetadata>,
+    /// The SHA-256 of the archive the package was extracted from, in hexadecimal.
+    pub sha256: Option<String>,
 }
 
 impl Package {
@@ -100,9 +215,37 @@ impl Package {
         Ok(Self {
             root_directory: root_directory.clone(),
             entries: Self::scan(&root_directory)?,
+            revision: None,
+            metadata: None,
+            sha256: None,
         })
     }
 
+    /// A package listed from the entries of an archive, without extracting it.
+    /// The entries have the paths they will have once the archive is extracted to `root_directory`.
+    pub fn from_archive_entries(root_directory: impl Into<PathBuf>, archive_entries: &[ArchiveEntry]) -> Self {
+        let root_directory = root_directory.into();
+        let mut entries: Vec<PackageEntry> = Vec::new();
+
+        for archive_entry in archive_entries {
+            if let Some(entry) = PackageEntry::from_archive_entry(&root_directory, archive_entry) {
+                if !entries.iter().any(|other| other.path == entry.path) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Self {
+            root_directory,
+            entries,
+            revision: None,
+            metadata: None,
+            sha256: None,
+        }
+    }
+
     pub fn hud_names(&self) -> impl Iterator<Item = &HudName> {
         self.entries.iter().map(|directory| &directory.name)
     }
@@ -134,6 +277,15 @@ pub enum OpenHudDirectoryError {
     #[error("Failed to read .vdf file: {1}")]
     FailedToReadVdfFile(PathBuf, std::io::Error),
 
+    #[error("Invalid .vdf file: {1}")]
+    InvalidVdfFile(PathBuf, KeyValuesError),
+
+    #[error("Invalid .vpk file: {1}")]
+    InvalidVpkFile(PathBuf, VpkError),
+
+    #[error("The .vpk file '{0}' contains no HUD files")]
+    NoHudFiles(PathBuf),
+
     #[error("Unsupported type")]
     UnsupportedType,
 }
@@ -154,7 +306,8 @@ pub enum ScanPackageError {
 
 #[cfg(test)]
 mod slow_tests {
-    use crate::package::{PackageEntry, HudName, Package, INFO_VDF_FILE_NAME};
+    use crate::package::{PackageEntry, HudName, OpenHudDirectoryError, Package, INFO_VDF_FILE_NAME};
+    use crate::vpk::write_vpk;
     use std::path::Path;
     use tempdir::TempDir;
     use test_case::test_case;
@@ -193,5 +346,62 @@ mod slow_tests {
         assert_eq!(2, package.entries.len());
         assert_eq!(HudName("d0".into()), package.entries[0].name);
         assert_eq!(HudName("d1".into()), package.entries[1].name);
+        assert_eq!(Some("test0"), package.entries[0].declared_name.as_deref());
+        assert_eq!(Some("test1"), package.entries[1].declared_name.as_deref());
+    }
+
+    #[test_case("\"ahud\"\n{\n\t\"ui_version\"\t\"3\"\n}", Some("ahud"), Some(3) ; "quoted")]
+    #[test_case("// ahud\nahud { UI_VERSION 2 }", Some("ahud"), Some(2) ; "unquoted")]
+    #[test_case("\"ahud\" { }", Some("ahud"), None ; "without version")]
+    #[test_case("\"ahud\" {", None, None ; "invalid")]
+    #[test_case("", None, None ; "empty")]
+    fn test_open_hud_directory_info_vdf(content: &str, expected_name: Option<&str>, expected_ui_version: Option<u32>) {
+        let hud_dir = TempDir::new("test_open_hud_directory_info_vdf").unwrap();
+        std::fs::write(hud_dir.path().join(INFO_VDF_FILE_NAME), content).unwrap();
+
+        let entry = PackageEntry::from_path(hud_dir.path()).unwrap();
+
+        assert_eq!(expected_name, entry.declared_name.as_deref());
+        assert_eq!(expected_ui_version, entry.ui_version);
+    }
+
+    #[test]
+    fn test_open_package_vpk_files() {
+        let package_dir = TempDir::new("test_open_package_vpk_files").unwrap();
+        write_vpk(
+            &package_dir.path().join("ahud.vpk"),
+            2,
+            &[
+                ("info.vdf", b"\"A HUD\"\n{\n\t\"ui_version\"\t\"3\"\n}"),
+                ("resource/ui/hudlayout.res", b"\"Resource/UI/HudLayout.res\"\n{\n}"),
+            ],
+            None,
+        );
+        write_vpk(
+            &package_dir.path().join("hitsounds_dir.vpk"),
+            2,
+            &[("sound/ui/hitsound.wav", b"RIFF")],
+            Some(1),
+        );
+
+        let package = Package::open(package_dir.path()).unwrap();
+
+        assert_eq!(1, package.entries.len());
+        assert_eq!(HudName::new("ahud"), package.entries[0].name);
+        assert_eq!(Some("A HUD"), package.entries[0].declared_name.as_deref());
+        assert_eq!(Some(3), package.entries[0].ui_version);
+        assert_eq!(vec!["info.vdf", "resource/ui/hudlayout.res"], package.entries[0].vpk_files);
+    }
+
+    #[test]
+    fn test_open_vpk_file_without_hud_files() {
+        let package_dir = TempDir::new("test_open_vpk_file_without_hud_files").unwrap();
+        let file_path = package_dir.path().join("models.vpk");
+        write_vpk(&file_path, 1, &[("models/player/scout.mdl", b"IDST")], None);
+
+        assert!(matches!(
+            PackageEntry::from_path(&file_path),
+            Err(OpenHudDirectoryError::NoHudFiles(_))
+        ));
     }
 }
@@ -1,4 +1,4 @@
-use crate::source::Source;
+use crate::source::{Revision, Source, SourceMetadata};
 use crate::HudName;
 use chrono::{DateTime, Utc};
 use enum_as_inner::EnumAsInner;
@@ -20,9 +20,10 @@ impl Registry {
         self.info.values()
     }
 
-    pub fn add(&mut self, name: HudName, source: Source) {
+    /// Add a HUD, unless a HUD with the same name exists. Returns true if the HUD was added.
+    pub fn add(&mut self, name: HudName, source: Source) -> bool {
         if self.info.contains_key(&name) {
-            return;
+            return false;
         }
 
         self.info.insert(
@@ -30,9 +31,15 @@ impl Registry {
             HudInfo {
                 name,
                 source,
+                revision: None,
+                metadata: None,
+                sha256: None,
+                expected_sha256: None,
                 install: Install::None,
             },
         );
+
+        true
     }
 
     pub fn remove(&mut self, name: &HudName) -> Option<HudInfo> {
@@ -49,6 +56,32 @@ impl Registry {
             .find(|info| matches!(info.install, Install::Installed { .. }))
     }
 
+    pub fn set_revision(&mut self, name: &HudName, revision: Option<Revision>) {
+        if let Some(info) = self.info.get_mut(name) {
+            info.revision = revision;
+        }
+    }
+
+    pub fn set_metadata(&mut self, name: &HudName, metadata: Option<SourceMetadata>) {
+        if let Some(info) = self.info.get_mut(name) {
+            info.metadata = metadata;
+        }
+    }
+
+    /// Record the SHA-256 of the archive the HUD was fetched from.
+    pub fn set_sha256(&mut self, name: &HudName, sha256: Option<String>) {
+        if let Some(info) = self.info.get_mut(name) {
+            info.sha256 = sha256;
+        }
+    }
+
+    /// Pin the SHA-256 the archive of a HUD must have, or remove the pin.
+    pub fn set_expected_sha256(&mut self, name: &HudName, expected_sha256: Option<String>) {
+        if let Some(info) = self.info.get_mut(name) {
+            info.expected_sha256 = expected_sha256;
+        }
+    }
+
     pub fn set_install(&mut self, name: &HudName, install: Install) {
         if let Some(info) = self.info.get_mut(name) {
             info.install = install;
@@ -60,21 +93,43 @@ impl Registry {
 pub struct HudInfo {
     pub name: HudName,
     pub source: Source,
+    /// The revision of the source when the HUD was fetched.
+    pub revision: Option<Revision>,
+    /// The information published by the source.
+    pub metadata: Option<SourceMetadata>,
+    /// The SHA-256 of the archive when the HUD was fetched.
+    pub sha256: Option<String>,
+    /// The SHA-256 the archive must have. Fetching an archive with another checksum fails.
+    pub expected_sha256: Option<String>,
     pub install: Install,
 }
 
 #[derive(Clone, Debug, EnumAsInner, Serialize, Deserialize)]
 pub enum Install {
     None,
-    Installed { path: PathBuf, when: DateTime<Utc> },
+    Installed {
+        path: PathBuf,
+        when: DateTime<Utc>,
+        /// The revision of the source when the HUD was installed.
+        revision: Option<Revision>,
+    },
     Failed { error: String },
 }
 
 impl Install {
-    pub fn installed_now(path: impl Into<PathBuf>) -> Self {
+    pub fn installed_now(path: impl Into<PathBuf>, revision: Option<Revision>) -> Self {
         Self::Installed {
             path: path.into(),
             when: Utc::now(),
+            revision,
+        }
+    }
+
+    /// The revision of the source when the HUD was installed, if it is installed.
+    pub fn revision(&self) -> Option<&Revision> {
+        match self {
+            Install::Installed { revision, .. } => revision.as_ref(),
+            Install::None | Install::Failed { .. } => None,
         }
     }
 
@@ -0,0 +1,409 @@
+//! Resolvers turn sources into something to fetch.
+//! Each kind of source has a resolver. A resolver recognizes the inputs of the user it supports, resolves its
+//! sources to the artifact to fetch, and checks if its sources have updates.
+//! Supporting a new HUD provider means implementing [`SourceResolver`] and registering it in [`SourceResolvers`].
+
+use crate::download::check_modified;
+use crate::share_link::resolve_url as resolve_share_link;
+use crate::source::{FetchError, FetchSettings, Revision, Source, SourceMetadata};
+use crate::{gamebanana, github};
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// What has to be fetched to get the package of a source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Artifact {
+    /// A file to download. If no file name is given, it is found from the URL or the response.
+    Download { url: String, file_name: Option<String> },
+    /// An archive or a .vpk file on disk.
+    LocalFile(PathBuf),
+    /// A directory on disk, scanned in place.
+    LocalDirectory(PathBuf),
+}
+
+/// A source resolved to the artifact to fetch.
+#[derive(Clone, Debug)]
+pub struct ResolvedSource {
+    pub artifact: Artifact,
+    /// The revision of the source, if the source is versioned.
+    pub revision: Option<Revision>,
+    /// The information published by the source, if the source provides it.
+    pub metadata: Option<SourceMetadata>,
+    /// The name to give to the root directory of the archive, if the archive names it after its revision.
+    pub root_directory_name: Option<String>,
+}
+
+impl ResolvedSource {
+    pub fn new(artifact: Artifact) -> Self {
+        Self {
+            artifact,
+            revision: None,
+            metadata: None,
+            root_directory_name: None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait SourceResolver: Debug + Send + Sync {
+    /// Create a source from an input of the user, such as a URL, if this resolver supports it.
+    fn recognize(&self, input: &str) -> Option<Source>;
+
+    /// True if this resolver resolves the source.
+    fn can_resolve(&self, source: &Source) -> bool;
+
+    /// Resolve a source to the artifact to fetch.
+    async fn resolve(&self, source: &Source, settings: &FetchSettings) -> Result<ResolvedSource, FetchError>;
+
+    /// Check if the source moved since the revision was fetched, without downloading the package.
+    /// Returns the new revision if there is an update. Sources without revision never have updates.
+    async fn check_for_update(
+        &self,
+        _source: &Source,
+        _revision: Option<&Revision>,
+        _settings: &FetchSettings,
+    ) -> Result<Option<Revision>, FetchError> {
+        Ok(None)
+    }
+}
+
+/// The resolvers used to fetch the sources.
+/// The resolvers registered last are tried first. The download URL resolver is tried when no other resolver
+/// recognizes an input.
+#[derive(Clone, Debug)]
+pub struct SourceResolvers {
+    resolvers: Vec<Arc<dyn SourceResolver>>,
+    default_resolver: Arc<dyn SourceResolver>,
+}
+
+impl Default for SourceResolvers {
+    fn default() -> Self {
+        Self {
+            resolvers: vec![
+                Arc::new(LocalFileResolver),
+                Arc::new(LocalDirectoryResolver),
+                Arc::new(GameBananaResolver),
+                Arc::new(GitHubResolver),
+            ],
+            default_resolver: Arc::new(DownloadUrlResolver),
+        }
+    }
+}
+
+impl SourceResolvers {
+    pub fn register(&mut self, resolver: impl SourceResolver + 'static) {
+        self.resolvers.push(Arc::new(resolver));
+    }
+
+    /// Create a source from an input of the user, such as a URL.
+    pub fn recognize(&self, input: &str) -> Source {
+        self.iter()
+            .find_map(|resolver| resolver.recognize(input))
+            .unwrap_or(Source::None)
+    }
+
+    /// Find the resolver of a source.
+    pub fn find(&self, source: &Source) -> Result<&dyn SourceResolver, FetchError> {
+        if *source == Source::None {
+            return Err(FetchError::NoSource);
+        }
+
+        self.iter()
+            .find(|resolver| resolver.can_resolve(source))
+            .ok_or_else(|| FetchError::UnsupportedSource(format!("{:?}", source)))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &dyn SourceResolver> {
+        self.resolvers
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self.default_resolver))
+            .map(|resolver| resolver.as_ref())
+    }
+}
+
+/// Any URL, downloaded as is.
+#[derive(Debug)]
+pub struct DownloadUrlResolver;
+
+#[async_trait]
+impl SourceResolver for DownloadUrlResolver {
+    fn recognize(&self, input: &str) -> Option<Source> {
+        Some(Source::DownloadUrl(input.to_string()))
+    }
+
+    fn can_resolve(&self, source: &Source) -> bool {
+        matches!(source, Source::DownloadUrl(_))
+    }
+
+    async fn resolve(&self, source: &Source, _settings: &FetchSettings) -> Result<ResolvedSource, FetchError> {
+        let Source::DownloadUrl(url) = source else {
+            return Err(FetchError::UnsupportedSource(format!("{:?}", source)));
+        };
+
+        Ok(ResolvedSource::new(Artifact::Download {
+            url: url.clone(),
+            file_name: None,
+        }))
+    }
+
+    async fn check_for_update(
+        &self,
+        source: &Source,
+        revision: Option<&Revision>,
+        settings: &FetchSettings,
+    ) -> Result<Option<Revision>, FetchError> {
+        let (Source::DownloadUrl(url), Some(Revision::Http { etag, last_modified })) = (source, revision) else {
+            return Ok(None);
+        };
+        let url = resolve_share_link(url, &settings.http).await?.url;
+
+        check_modified(&url, etag.as_deref(), last_modified.as_deref(), settings).await
+    }
+}
+
+#[derive(Debug)]
+pub struct GitHubResolver;
+
+#[async_trait]
+impl SourceResolver for GitHubResolver {
+    fn recognize(&self, input: &str) -> Option<Source> {
+        github::parse_repository_url(input).map(|(owner, repo, reference)| Source::GitHub { owner, repo, reference })
+    }
+
+    fn can_resolve(&self, source: &Source) -> bool {
+        matches!(source, Source::GitHub { .. })
+    }
+
+    async fn resolve(&self, source: &Source, settings: &FetchSettings) -> Result<ResolvedSource, FetchError> {
+        let Source::GitHub { owner, repo, reference } = source else {
+            return Err(FetchError::UnsupportedSource(format!("{:?}", source)));
+        };
+        let resolved = settings.github.resolve(owner, repo, reference).await?;
+
+        Ok(ResolvedSource {
+            artifact: Artifact::Download {
+                url: resolved.archive_url.to_string(),
+                file_name: Some(resolved.archive_file_name),
+            },
+            revision: Some(Revision::Commit(resolved.commit_sha)),
+            metadata: None,
+            root_directory_name: Some(resolved.root_directory_name),
+        })
+    }
+
+    async fn check_for_update(
+        &self,
+        source: &Source,
+        revision: Option<&Revision>,
+        settings: &FetchSettings,
+    ) -> Result<Option<Revision>, FetchError> {
+        let Source::GitHub { owner, repo, reference } = source else {
+            return Ok(None);
+        };
+        let resolved = settings.github.resolve(owner, repo, reference).await?;
+        let latest_revision = Revision::Commit(resolved.commit_sha);
+
+        Ok(Some(latest_revision).filter(|latest_revision| Some(latest_revision) != revision))
+    }
+}
+
+#[derive(Debug)]
+pub struct GameBananaResolver;
+
+#[async_trait]
+impl SourceResolver for GameBananaResolver {
+    fn recognize(&self, input: &str) -> Option<Source> {
+        gamebanana::parse_mod_url(input).map(|mod_id| Source::GameBanana { mod_id, file_id: None })
+    }
+
+    fn can_resolve(&self, source: &Source) -> bool {
+        matches!(source, Source::GameBanana { .. })
+    }
+
+    async fn resolve(&self, source: &Source, settings: &FetchSettings) -> Result<ResolvedSource, FetchError> {
+        let Source::GameBanana { mod_id, file_id } = source else {
+            return Err(FetchError::UnsupportedSource(format!("{:?}", source)));
+        };
+        let resolved = settings.gamebanana.resolve(*mod_id, *file_id).await?;
+
+        Ok(ResolvedSource {
+            artifact: Artifact::Download {
+                url: resolved.download_url.to_string(),
+                file_name: Some(resolved.file_name),
+            },
+            revision: Some(Revision::GameBananaFile(resolved.file_id)),
+            metadata: Some(resolved.metadata),
+            root_directory_name: None,
+        })
+    }
+
+    async fn check_for_update(
+        &self,
+        source: &Source,
+        revision: Option<&Revision>,
+        settings: &FetchSettings,
+    ) -> Result<Option<Revision>, FetchError> {
+        let Source::GameBanana { mod_id, file_id } = source else {
+            return Ok(None);
+        };
+        let resolved = settings.gamebanana.resolve(*mod_id, *file_id).await?;
+        let latest_revision = Revision::GameBananaFile(resolved.file_id);
+
+        Ok(Some(latest_revision).filter(|latest_revision| Some(latest_revision) != revision))
+    }
+}
+
+/// A path to an archive or a .vpk file.
+#[derive(Debug)]
+pub struct LocalFileResolver;
+
+#[async_trait]
+impl SourceResolver for LocalFileResolver {
+    fn recognize(&self, input: &str) -> Option<Source> {
+        let path = PathBuf::from(input);
+
+        path.is_file().then_some(Source::LocalFile(path))
+    }
+
+    fn can_resolve(&self, source: &Source) -> bool {
+        matches!(source, Source::LocalFile(_))
+    }
+
+    async fn resolve(&self, source: &Source, _settings: &FetchSettings) -> Result<ResolvedSource, FetchError> {
+        let Source::LocalFile(file_path) = source else {
+            return Err(FetchError::UnsupportedSource(format!("{:?}", source)));
+        };
+
+        Ok(ResolvedSource::new(Artifact::LocalFile(file_path.clone())))
+    }
+}
+
+/// A path to a directory containing HUDs.
+#[derive(Debug)]
+pub struct LocalDirectoryResolver;
+
+#[async_trait]
+impl SourceResolver for LocalDirectoryResolver {
+    fn recognize(&self, input: &str) -> Option<Source> {
+        let path = PathBuf::from(input);
+
+        path.is_dir().then_some(Source::LocalDirectory(path))
+    }
+
+    fn can_resolve(&self, source: &Source) -> bool {
+        matches!(source, Source::LocalDirectory(_))
+    }
+
+    async fn resolve(&self, source: &Source, _settings: &FetchSettings) -> Result<ResolvedSource, FetchError> {
+        let Source::LocalDirectory(directory_path) = source else {
+            return Err(FetchError::UnsupportedSource(format!("{:?}", source)));
+        };
+
+        Ok(ResolvedSource::new(Artifact::LocalDirectory(directory_path.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Artifact, ResolvedSource, SourceResolver, SourceResolvers};
+    use crate::{fetch_package_with, FetchError, FetchSettings, HudName, Source};
+    use async_trait::async_trait;
+    use tempdir::TempDir;
+
+    /// Resolves `local:{path}` to a local directory.
+    #[derive(Debug)]
+    struct PrefixResolver;
+
+    #[async_trait]
+    impl SourceResolver for PrefixResolver {
+        fn recognize(&self, input: &str) -> Option<Source> {
+            input.strip_prefix("local:").map(|path| Source::Custom {
+                resolver: "prefix".into(),
+                location: path.into(),
+            })
+        }
+
+        fn can_resolve(&self, source: &Source) -> bool {
+            matches!(source, Source::Custom { resolver, .. } if resolver == "prefix")
+        }
+
+        async fn resolve(&self, source: &Source, _settings: &FetchSettings) -> Result<ResolvedSource, FetchError> {
+            let Source::Custom { location, .. } = source else {
+                return Err(FetchError::UnsupportedSource(format!("{:?}", source)));
+            };
+
+            Ok(ResolvedSource::new(Artifact::LocalDirectory(location.into())))
+        }
+    }
+
+    #[test]
+    fn test_recognize() {
+        let resolvers = SourceResolvers::default();
+        let directory = TempDir::new("test_recognize").unwrap();
+        let directory_path = directory.path().to_str().unwrap();
+
+        assert!(matches!(
+            resolvers.recognize("https://github.com/n0kk/ahud"),
+            Source::GitHub { .. }
+        ));
+        assert!(matches!(
+            resolvers.recognize("https://gamebanana.com/mods/409271"),
+            Source::GameBanana { .. }
+        ));
+        assert_eq!(
+            Source::LocalDirectory(directory.path().into()),
+            resolvers.recognize(directory_path)
+        );
+        assert_eq!(
+            Source::DownloadUrl("https://example.com/hud.zip".into()),
+            resolvers.recognize("https://example.com/hud.zip")
+        );
+    }
+
+    #[test]
+    fn test_find_without_source() {
+        let resolvers = SourceResolvers::default();
+
+        assert!(matches!(resolvers.find(&Source::None), Err(FetchError::NoSource)));
+    }
+
+    #[test]
+    fn test_find_unsupported_source() {
+        let resolvers = SourceResolvers::default();
+        let source = Source::Custom {
+            resolver: "prefix".into(),
+            location: "somewhere".into(),
+        };
+
+        assert!(matches!(resolvers.find(&source), Err(FetchError::UnsupportedSource(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_registered_resolver() {
+        let hud_directory = TempDir::new("test_registered_resolver_hud").unwrap();
+        let directory = TempDir::new("test_registered_resolver").unwrap();
+        std::fs::create_dir(hud_directory.path().join("ahud")).unwrap();
+        std::fs::write(hud_directory.path().join("ahud").join("info.vdf"), b"").unwrap();
+        let mut settings = FetchSettings::default();
+
+        settings.resolvers.register(PrefixResolver);
+
+        let source = settings
+            .resolvers
+            .recognize(&format!("local:{}", hud_directory.path().display()));
+        let package = fetch_package_with(source, directory.path(), &settings).await.unwrap();
+
+        assert_eq!(package.entries[0].name, HudName::new("ahud"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_without_source() {
+        let directory = TempDir::new("test_fetch_without_source").unwrap();
+        let result = fetch_package_with(Source::None, directory.path(), &FetchSettings::default()).await;
+
+        assert!(matches!(result, Err(FetchError::NoSource)));
+    }
+}
@@ -0,0 +1,433 @@
+//! Turn the links of file-sharing hosts into direct download links.
+//! HUD authors often publish a link to a page of a file-sharing host instead of a link to the archive.
+//! Some links are rewritten, others need the page to be downloaded to find the link of the file.
+
+use crate::http::HttpClient;
+use crate::FetchError;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use std::collections::HashMap;
+
+/// The hosts with a known way to get a direct download link.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ShareHost {
+    Dropbox,
+    GoogleDrive,
+    MediaFire,
+}
+
+impl ShareHost {
+    fn from_url(url: &Url) -> Option<Self> {
+        match url.host_str()?.trim_start_matches("www.") {
+            "dropbox.com" => Some(Self::Dropbox),
+            "drive.google.com" => Some(Self::GoogleDrive),
+            "mediafire.com" => Some(Self::MediaFire),
+            _ => None,
+        }
+    }
+}
+
+/// Rewrite a share link to the link downloading the file, without sending any request.
+/// Returns `None` if the link is not a share link, or if the link can not be rewritten.
+pub(crate) fn normalize_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+
+    match ShareHost::from_url(&url)? {
+        ShareHost::Dropbox => Some(normalize_dropbox_url(url).to_string()),
+        ShareHost::GoogleDrive => normalize_google_drive_url(&url).map(|url| url.to_string()),
+        ShareHost::MediaFire => None,
+    }
+}
+
+/// A share link resolved to the link downloading the file.
+pub(crate) struct ResolvedUrl {
+    pub url: String,
+    /// The response of the link when it serves the file already, so the file is not requested twice.
+    /// Its body is not read yet.
+    pub response: Option<reqwest::Response>,
+}
+
+impl ResolvedUrl {
+    fn new(url: String) -> Self {
+        Self { url, response: None }
+    }
+}
+
+/// What a link of a file-sharing host leads to.
+enum SharedPage {
+    /// A web page, with the URL it was served from once redirected.
+    Html(Url, String),
+    /// The file itself, its body is not read.
+    File(reqwest::Response),
+}
+
+/// Resolve a share link to the link downloading the file.
+/// The links of other hosts are returned unchanged.
+pub(crate) async fn resolve_url(url: &str, client: &HttpClient) -> Result<ResolvedUrl, FetchError> {
+    let Some(host) = Url::parse(url).ok().as_ref().and_then(ShareHost::from_url) else {
+        return Ok(ResolvedUrl::new(url.to_string()));
+    };
+    let url = normalize_url(url).unwrap_or_else(|| url.to_string());
+    let find_download_url = match host {
+        ShareHost::Dropbox => return Ok(ResolvedUrl::new(url)),
+        // Google Drive does not scan the large files for viruses, and asks to confirm the download.
+        ShareHost::GoogleDrive => find_google_drive_confirmed_url,
+        ShareHost::MediaFire => find_mediafire_download_url,
+    };
+
+    match get_shared_page(&url, client).await? {
+        SharedPage::Html(page_url, html) => find_download_url(&html, &page_url)
+            .map(ResolvedUrl::new)
+            .ok_or_else(|| crate::download::html_page_error(&url)),
+        SharedPage::File(response) => Ok(ResolvedUrl {
+            url,
+            response: Some(response),
+        }),
+    }
+}
+
+/// Download a page, unless the URL leads to a file.
+/// The body of a file is not read, the response is returned to download it.
+async fn get_shared_page(url: &str, client: &HttpClient) -> Result<SharedPage, FetchError> {
+    client
+        .send_and_read(client.get(url), |response| async move {
+            let is_html = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|content_type| content_type.trim_start().to_ascii_lowercase().starts_with("text/html"))
+                .unwrap_or(false);
+
+            if !response.status().is_success() {
+                return Err(FetchError::HttpStatus {
+                    url: url.to_string(),
+                    status: response.status(),
+                });
+            }
+
+            match is_html {
+                true => Ok(SharedPage::Html(response.url().clone(), response.text().await?)),
+                false => Ok(SharedPage::File(response)),
+            }
+        })
+        .await
+}
+
+/// Dropbox shows a preview page for `dl=0`, and downloads the file for `dl=1`.
+fn normalize_dropbox_url(mut url: Url) -> Url {
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| name != "dl")
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+    url.query_pairs_mut().clear().extend_pairs(query).append_pair("dl", "1");
+
+    url
+}
+
+/// Google Drive shows a preview page for `/file/d/{id}/view` and `/open?id={id}`.
+fn normalize_google_drive_url(url: &Url) -> Option<Url> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let file_id = match segments.as_slice() {
+        ["file", "d", file_id, ..] => file_id.to_string(),
+        ["uc"] | ["open"] => url.query_pairs().find(|(name, _)| name == "id")?.1.to_string(),
+        _ => return None,
+    };
+    let mut normalized_url = Url::parse("https://drive.google.com/uc").expect("valid Google Drive URL");
+
+    normalized_url
+        .query_pairs_mut()
+        .append_pair("export", "download")
+        .append_pair("id", &file_id);
+
+    Some(normalized_url)
+}
+
+/// Find the link confirming the download in the warning page of Google Drive.
+/// The current page has a form with hidden fields, the older page has a link with a `confirm` parameter.
+fn find_google_drive_confirmed_url(html: &str, page_url: &Url) -> Option<String> {
+    let form = find_tags_with_end(html, "form")
+        .into_iter()
+        .find(|(attributes, _)| attributes.get("id").map(String::as_str) == Some("download-form"));
+
+    if let Some((form, form_start)) = form {
+        let mut url = page_url.join(form.get("action")?).ok()?;
+        let form_end = html[form_start..]
+            .to_ascii_lowercase()
+            .find("</form")
+            .map(|end| form_start + end)?;
+
+        for input in find_tags(&html[form_start..form_end], "input") {
+            if let (Some("hidden"), Some(name), Some(value)) =
+                (input.get("type").map(String::as_str), input.get("name"), input.get("value"))
+            {
+                url.query_pairs_mut().append_pair(name, value);
+            }
+        }
+
+        return Some(url.to_string());
+    }
+
+    find_tags(html, "a")
+        .into_iter()
+        .filter_map(|attributes| attributes.get("href").cloned())
+        .find(|href| href.contains("confirm="))
+        .and_then(|href| page_url.join(&href).ok())
+        .map(|url| url.to_string())
+}
+
+/// Find the link of the download button in the page of a MediaFire file.
+fn find_mediafire_download_url(html: &str, page_url: &Url) -> Option<String> {
+    find_tags(html, "a")
+        .into_iter()
+        .find(|attributes| attributes.get("id").map(String::as_str) == Some("downloadButton"))
+        .and_then(|attributes| attributes.get("href").cloned())
+        .and_then(|href| page_url.join(&href).ok())
+        .map(|url| url.to_string())
+}
+
+/// Find the opening tags with a name, and return their attributes.
+/// This is far from a complete HTML parser, but it is enough to read the few attributes needed in known pages.
+fn find_tags(html: &str, tag_name: &str) -> Vec<HashMap<String, String>> {
+    find_tags_with_end(html, tag_name)
+        .into_iter()
+        .map(|(attributes, _)| attributes)
+        .collect()
+}
+
+/// Find the opening tags with a name, and return their attributes and the position of the end of each tag.
+fn find_tags_with_end(html: &str, tag_name: &str) -> Vec<(HashMap<String, String>, usize)> {
+    let lowercase_html = html.to_ascii_lowercase();
+    let opening = format!("<{}", tag_name);
+    let mut tags = Vec::new();
+    let mut position = 0;
+
+    while let Some(start) = lowercase_html[position..]
+        .find(&opening)
+        .map(|start| position + start + opening.len())
+    {
+        let is_tag_name_complete = html[start..]
+            .chars()
+            .next()
+            .map(|character| character.is_whitespace() || character == '>' || character == '/')
+            .unwrap_or(false);
+
+        position = start;
+
+        if !is_tag_name_complete {
+            continue;
+        }
+
+        let (attributes, end) = parse_attributes(&html[start..]);
+
+        position += end;
+        tags.push((attributes, position));
+    }
+
+    tags
+}
+
+/// Parse the attributes of a tag, until the end of the tag.
+/// Returns the attributes, and the position of the end of the tag.
+fn parse_attributes(input: &str) -> (HashMap<String, String>, usize) {
+    let mut attributes = HashMap::new();
+    let mut characters = input.char_indices().peekable();
+
+    loop {
+        while characters
+            .next_if(|(_, character)| character.is_whitespace() || *character == '/')
+            .is_some()
+        {}
+
+        let Some(&(index, character)) = characters.peek() else {
+            return (attributes, input.len());
+        };
+
+        if character == '>' {
+            return (attributes, index + 1);
+        }
+
+        let mut name = String::new();
+
+        while let Some((_, character)) =
+            characters.next_if(|(_, character)| !character.is_whitespace() && !"=>/".contains(*character))
+        {
+            name.push(character.to_ascii_lowercase());
+        }
+
+        while characters.next_if(|(_, character)| character.is_whitespace()).is_some() {}
+
+        let mut value = String::new();
+
+        if characters.next_if(|(_, character)| *character == '=').is_some() {
+            while characters.next_if(|(_, character)| character.is_whitespace()).is_some() {}
+
+            match characters.next_if(|(_, character)| *character == '"' || *character == '\'') {
+                Some((_, quote)) => {
+                    for (_, character) in characters.by_ref() {
+                        if character == quote {
+                            break;
+                        }
+
+                        value.push(character);
+                    }
+                }
+                None => {
+                    while let Some((_, character)) =
+                        characters.next_if(|(_, character)| !character.is_whitespace() && *character != '>')
+                    {
+                        value.push(character);
+                    }
+                }
+            }
+        }
+
+        if !name.is_empty() {
+            attributes.insert(name, decode_entities(&value));
+        }
+    }
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#47;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_google_drive_confirmed_url, find_mediafire_download_url, get_shared_page, normalize_url, resolve_url,
+        SharedPage,
+    };
+    use crate::download::start_download_from_response;
+    use crate::{FetchSettings, HttpClient};
+    use reqwest::Url;
+    use tempdir::TempDir;
+    use test_case::test_case;
+
+    const GOOGLE_DRIVE_VIRUS_SCAN_WARNING: &str =
+        include_str!("../fixtures/share_links/google_drive_virus_scan_warning.html");
+    const GOOGLE_DRIVE_CONFIRM_LINK: &str = include_str!("../fixtures/share_links/google_drive_confirm_link.html");
+    const MEDIAFIRE_LANDING_PAGE: &str = include_str!("../fixtures/share_links/mediafire_landing_page.html");
+
+    #[test_case(
+        "https://www.dropbox.com/s/cwwmppnn3nn68av/3HUD.7z?dl=0",
+        Some("https://www.dropbox.com/s/cwwmppnn3nn68av/3HUD.7z?dl=1")
+    )]
+    #[test_case(
+        "https://www.dropbox.com/s/cwwmppnn3nn68av/3HUD.7z",
+        Some("https://www.dropbox.com/s/cwwmppnn3nn68av/3HUD.7z?dl=1")
+    )]
+    #[test_case(
+        "https://www.dropbox.com/scl/fi/a1b2c3/3HUD.7z?rlkey=xyz&dl=0",
+        Some("https://www.dropbox.com/scl/fi/a1b2c3/3HUD.7z?rlkey=xyz&dl=1")
+    )]
+    #[test_case(
+        "https://drive.google.com/file/d/1xYzAbCdEf/view?usp=sharing",
+        Some("https://drive.google.com/uc?export=download&id=1xYzAbCdEf")
+    )]
+    #[test_case(
+        "https://drive.google.com/open?id=1xYzAbCdEf",
+        Some("https://drive.google.com/uc?export=download&id=1xYzAbCdEf")
+    )]
+    #[test_case("https://drive.google.com/drive/folders/1xYzAbCdEf", None)]
+    #[test_case("https://www.mediafire.com/file/q9r8s7t6u5v4w3x/7HUD_5.11.zip/file", None)]
+    #[test_case("https://github.com/n0kk/ahud/archive/refs/heads/master.zip", None)]
+    fn test_normalize_url(input: &str, expected: Option<&str>) {
+        assert_eq!(expected.map(String::from), normalize_url(input))
+    }
+
+    #[test]
+    fn test_google_drive_virus_scan_warning() {
+        let page_url =
+            Url::parse("https://drive.google.com/uc?export=download&id=1xYzAbCdEfGhIjKlMnOpQrStUvWxYz012").unwrap();
+
+        assert_eq!(
+            Some("https://drive.usercontent.google.com/download?id=1xYzAbCdEfGhIjKlMnOpQrStUvWxYz012&export=download&confirm=t&uuid=2f9c1c4e-7c2a-4b8e-9d61-5b0a3f1e8c7d".to_string()),
+            find_google_drive_confirmed_url(GOOGLE_DRIVE_VIRUS_SCAN_WARNING, &page_url)
+        );
+    }
+
+    #[test]
+    fn test_google_drive_confirm_link() {
+        let page_url = Url::parse("https://drive.google.com/uc?export=download&id=0B4qR7sTuVwXyZ1aBcDeFgHiJkL").unwrap();
+
+        assert_eq!(
+            Some("https://drive.google.com/uc?export=download&confirm=Q3xT&id=0B4qR7sTuVwXyZ1aBcDeFgHiJkL".to_string()),
+            find_google_drive_confirmed_url(GOOGLE_DRIVE_CONFIRM_LINK, &page_url)
+        );
+    }
+
+    #[test]
+    fn test_google_drive_form_in_uppercase_with_single_quotes() {
+        let page_url = Url::parse("https://drive.google.com/uc?export=download&id=1xYz").unwrap();
+        let html = "<FORM ID='download-form' ACTION='https://drive.usercontent.google.com/download' METHOD='get'>\
+            <INPUT TYPE='hidden' NAME='id' VALUE='1xYz'><INPUT TYPE='hidden' NAME='confirm' VALUE='t'></FORM>\
+            <form action='/other'><input type='hidden' name='other' value='1'></form>";
+
+        assert_eq!(
+            Some("https://drive.usercontent.google.com/download?id=1xYz&confirm=t".to_string()),
+            find_google_drive_confirmed_url(html, &page_url)
+        );
+    }
+
+    #[test]
+    fn test_mediafire_landing_page() {
+        let page_url = Url::parse("https://www.mediafire.com/file/q9r8s7t6u5v4w3x/7HUD_5.11.zip/file").unwrap();
+
+        assert_eq!(
+            Some("https://download2390.mediafire.com/abcd1234efgh/q9r8s7t6u5v4w3x/7HUD+5.11.zip".to_string()),
+            find_mediafire_download_url(MEDIAFIRE_LANDING_PAGE, &page_url)
+        );
+    }
+
+    #[test]
+    fn test_pages_without_download_link() {
+        let page_url = Url::parse("https://www.mediafire.com/file/q9r8s7t6u5v4w3x/7HUD_5.11.zip/file").unwrap();
+
+        assert_eq!(None, find_mediafire_download_url(GOOGLE_DRIVE_VIRUS_SCAN_WARNING, &page_url));
+        assert_eq!(None, find_google_drive_confirmed_url(MEDIAFIRE_LANDING_PAGE, &page_url));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_url_of_other_host() {
+        let url = "https://github.com/n0kk/ahud/archive/refs/heads/master.zip";
+
+        assert_eq!(url, resolve_url(url, &HttpClient::default()).await.unwrap().url);
+    }
+
+    #[tokio::test]
+    async fn test_shared_file_is_requested_once() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/hud.zip")
+            .with_header("content-type", "application/zip")
+            .with_body("hud")
+            .expect(1)
+            .create_async()
+            .await;
+        let url = format!("{}/hud.zip", server.url());
+        let directory = TempDir::new("test_shared_file_is_requested_once").unwrap();
+        let file_path = directory.path().join("hud.zip");
+        let settings = FetchSettings::default();
+
+        let SharedPage::File(response) = get_shared_page(&url, &settings.http).await.unwrap() else {
+            panic!("the file is not a page");
+        };
+
+        start_download_from_response(&url, response, &settings)
+            .unwrap()
+            .write_to(&file_path, &settings)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(b"hud".to_vec(), std::fs::read(&file_path).unwrap());
+    }
+}
@@ -1,5 +1,18 @@
-use crate::source::archives::{extract_archive, ArchiveError};
-use crate::{OpenPackageError, Package};
+use crate::archive::{is_vpk_file, ArchiveEntry, ArchiveError, ArchiveExtractors, ExtractionLimits, VPK_FILE_EXTENSION};
+use crate::bulk::DEFAULT_MAX_CONCURRENT_FETCHES;
+use crate::cache::{CacheError, DownloadCache};
+use crate::download::{
+    html_page_error, is_html_file, parse_content_disposition_file_name, sha256_file, start_download,
+    start_download_from_response, ProgressSender,
+};
+use crate::gamebanana::{GameBanana, GameBananaError};
+use crate::github::{GitHub, GitHubError, GitHubReference};
+use crate::http::{HttpClient, HttpError};
+use crate::package::PackageEntryKind;
+use crate::resolver::{Artifact, SourceResolvers};
+use crate::share_link::resolve_url as resolve_share_link;
+use crate::vpk::is_chunk_file;
+use crate::{HudName, OpenPackageError, Package, PackageEntry};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -7,6 +20,109 @@ use std::path::{Path, PathBuf};
 pub enum Source {
     None,
     DownloadUrl(String),
+    /// A GitHub repository, tracking a branch, a tag or the latest release.
+    GitHub {
+        owner: String,
+        repo: String,
+        reference: GitHubReference,
+    },
+    /// An archive or a .vpk file already on disk.
+    LocalFile(PathBuf),
+    /// A directory on disk containing one or more HUDs, such as a git checkout.
+    /// It is scanned in place, without being copied first.
+    LocalDirectory(PathBuf),
+    /// A mod published on GameBanana. If no file is specified, the newest file of the mod is used.
+    GameBanana {
+        mod_id: u64,
+        file_id: Option<u64>,
+    },
+    /// A source handled by a resolver registered by the application, identified by the name of its resolver.
+    Custom {
+        resolver: String,
+        location: String,
+    },
+}
+
+impl Source {
+    /// Create a source from a URL pasted by the user, using the built-in resolvers.
+    /// GitHub repository pages and GameBanana mod pages are recognized, any other URL is downloaded directly.
+    pub fn from_url(url: &str) -> Self {
+        SourceResolvers::default().recognize(url)
+    }
+}
+
+/// Identifies the version of a package fetched from a source.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Revision {
+    /// The SHA of the commit a git reference pointed to.
+    Commit(String),
+    /// The identifier of a file of a GameBanana mod.
+    GameBananaFile(u64),
+    /// The validators of a downloaded file, as sent by the server.
+    Http {
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// Information about a HUD, as published by its source.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SourceMetadata {
+    pub title: String,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub screenshot_urls: Vec<String>,
+}
+
+/// The services used to resolve sources.
+#[derive(Clone, Debug)]
+pub struct FetchSettings {
+    /// The client used to download the files.
+    pub http: HttpClient,
+    pub github: GitHub,
+    pub gamebanana: GameBanana,
+    /// Receives the progress of the downloads, each time a chunk is written to disk.
+    pub progress_sender: Option<ProgressSender>,
+    /// Where the interrupted downloads are kept to be resumed. If none, downloads are never resumed.
+    pub partial_downloads_directory: Option<PathBuf>,
+    /// Stores the downloaded files, so the same file is not downloaded twice.
+    pub cache: Option<DownloadCache>,
+    /// The SHA-256 the archive must have, in hexadecimal.
+    /// A local directory has no archive, so it is never verified.
+    pub expected_sha256: Option<String>,
+    /// The resolvers of the sources.
+    pub resolvers: SourceResolvers,
+    /// How many sources are fetched at once when fetching a list of sources.
+    pub max_concurrent_fetches: usize,
+    /// The limits of the extraction of the archives, protecting against zip bombs.
+    pub extraction_limits: ExtractionLimits,
+    /// The extractors of the formats of archives.
+    pub extractors: ArchiveExtractors,
+}
+
+impl Default for FetchSettings {
+    fn default() -> Self {
+        Self::new(HttpClient::default())
+    }
+}
+
+impl FetchSettings {
+    /// Settings sending all the requests with the same client.
+    pub fn new(http: HttpClient) -> Self {
+        Self {
+            github: GitHub::default().with_client(http.clone()),
+            gamebanana: GameBanana::default().with_client(http.clone()),
+            http,
+            progress_sender: None,
+            partial_downloads_directory: None,
+            cache: None,
+            expected_sha256: None,
+            resolvers: SourceResolvers::default(),
+            max_concurrent_fetches: DEFAULT_MAX_CONCURRENT_FETCHES,
+            extraction_limits: ExtractionLimits::default(),
+            extractors: ArchiveExtractors::default(),
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -23,139 +139,502 @@ pub enum FetchError {
     #[error(transparent)]
     GetFailed(#[from] reqwest::Error),
 
+    #[error("The server answered '{status}' for '{url}'")]
+    HttpStatus { url: String, status: reqwest::StatusCode },
+
+    #[error("'{url}' is a web page, not an archive. {}", direct_download_hint(.direct_download_url))]
+    HtmlPage {
+        url: String,
+        /// The link downloading the archive directly, if it can be guessed.
+        direct_download_url: Option<String>,
+    },
+
     #[error("This URL is not a download URL")]
     InvalidUrl(String),
 
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    GitHubFailed(#[from] GitHubError),
+
+    #[error("File not found '{0}'")]
+    FileNotFound(PathBuf),
+
+    #[error(transparent)]
+    GameBananaFailed(#[from] GameBananaError),
+
+    #[error("The server can not resume the download of '{0}'")]
+    RangeNotSupported(String),
+
+    #[error("The content of '{0}' changed while it was downloaded")]
+    ContentChanged(String),
+
+    #[error(transparent)]
+    CacheFailed(#[from] CacheError),
+
+    #[error("No data received from '{0}' for too long")]
+    ReadTimeout(String),
+
+    #[error("Checksum mismatch: expected SHA-256 {expected}, but the archive has {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("This HUD has no source")]
+    NoSource,
+
+    #[error("No resolver for the source {0}")]
+    UnsupportedSource(String),
+}
+
+impl From<HttpError> for FetchError {
+    fn from(error: HttpError) -> Self {
+        match error {
+            HttpError::RequestFailed(error) => FetchError::GetFailed(error),
+            HttpError::Timeout(url) => FetchError::ReadTimeout(url),
+        }
+    }
+}
+
+fn direct_download_hint(direct_download_url: &Option<String>) -> String {
+    match direct_download_url {
+        Some(direct_download_url) => format!("Try the direct download link '{}'.", direct_download_url),
+        None => "Use a link downloading the archive directly.".to_string(),
+    }
 }
 
 pub async fn fetch_package(source: Source, directory: impl AsRef<Path>) -> Result<Package, FetchError> {
-    let package_root_directory = match source {
-        Source::None => {
-            panic!("Trying to fetch a package without source")
+    fetch_package_with(source, directory, &FetchSettings::default()).await
+}
+
+pub async fn fetch_package_with(
+    source: Source,
+    directory: impl AsRef<Path>,
+    settings: &FetchSettings,
+) -> Result<Package, FetchError> {
+    let directory = directory.as_ref();
+    let fetched = fetch_source(source, directory, settings).await?;
+    let package_root_directory = match fetched.archive_file_path() {
+        Some(archive_file_path) => open_archive(&fetched, archive_file_path, directory, settings)?,
+        None => fetched.path.clone(),
+    };
+
+    fetched.into_package(Package::open(package_root_directory)?)
+}
+
+pub async fn scan_package(source: Source, directory: impl AsRef<Path>) -> Result<Package, FetchError> {
+    scan_package_with(source, directory, &FetchSettings::default()).await
+}
+
+/// Find the HUDs of a source from the list of the entries of its archive, without extracting it.
+/// The entries of the package have the paths they will have once the archive is extracted to `directory`.
+/// The .vpk files are extracted alone to a temporary directory, to keep only the ones that contain HUD files.
+/// An archive containing archives is extracted, as the HUDs may be in the archives it contains.
+pub async fn scan_package_with(
+    source: Source,
+    directory: impl AsRef<Path>,
+    settings: &FetchSettings,
+) -> Result<Package, FetchError> {
+    let directory = directory.as_ref();
+    let fetched = fetch_source(source, directory, settings).await?;
+    let package = match fetched.archive_file_path() {
+        Some(archive_file_path) => {
+            let extractors = &settings.extractors;
+            let mut archive_entries = extractors.list(archive_file_path)?;
+            let rename = fetched.root_directory_rename(extractors);
+
+            if let Some(rename) = &rename {
+                archive_entries = archive_entries.into_iter().map(|entry| rename.rename_entry(entry)).collect();
+            }
+
+            match archive_entries.iter().any(|entry| extractors.is_nested_archive(&entry.path)) {
+                true => Package::open(open_archive(&fetched, archive_file_path, directory, settings)?)?,
+                false => {
+                    let package = Package::from_archive_entries(directory, &archive_entries);
+                    open_listed_vpk_files(package, archive_file_path, directory, rename.as_ref(), settings)?
+                }
+            }
         }
-        Source::DownloadUrl(url) => {
-            let archive_file_path = download_url(&url, &directory).await?;
+        None => Package::open(&fetched.path)?,
+    };
+
+    fetched.into_package(package)
+}
+
+/// Fetch a source, and extract only the files of one of its HUDs, such as the HUD being installed from an archive
+/// containing many. The whole archive is extracted if the HUD is not found in the list of its entries.
+pub async fn fetch_hud_with(
+    source: Source,
+    name: &HudName,
+    directory: impl AsRef<Path>,
+    settings: &FetchSettings,
+) -> Result<Package, FetchError> {
+    let directory = directory.as_ref();
+    let fetched = fetch_source(source, directory, settings).await?;
+    let extractors = &settings.extractors;
+    let package_root_directory = match fetched.archive_file_path() {
+        Some(archive_file_path) => {
+            let rename = fetched.root_directory_rename(extractors);
+
+            match find_hud_subtrees(extractors, archive_file_path, name, directory, rename.as_ref()) {
+                Some(subtrees) => {
+                    let limits = &settings.extraction_limits;
+
+                    for subtree in &subtrees {
+                        extractors.extract_subtree(archive_file_path, directory, subtree, limits)?;
+                    }
+
+                    if let Some(rename) = rename {
+                        rename.apply(directory)?;
+                    }
 
-            extract_archive(&archive_file_path, &directory)?
+                    directory.to_path_buf()
+                }
+                None => open_archive(&fetched, archive_file_path, directory, settings)?,
+            }
         }
+        None => fetched.path.clone(),
     };
 
-    Ok(Package::open(package_root_directory)?)
+    fetched.into_package(Package::open(package_root_directory)?)
 }
 
-mod archives {
-    #[derive(thiserror::Error, Debug)]
-    pub enum ArchiveError {
-        #[error("Unsupported archive type.")]
-        UnsupportedArchiveType(PathBuf),
-        #[error("Reading archive failed: '{1}'")]
-        ReadFailed(PathBuf, Box<dyn std::error::Error>),
-        #[error("Creating directory failed: '{1}'")]
-        CreateDirectoryFailed(PathBuf, std::io::Error),
-        #[error("Failed to write file: '{1}'")]
-        CreateFileFailed(PathBuf, std::io::Error),
-        #[error("Failed to copy file: '{1}'")]
-        CopyFileFailed(PathBuf, std::io::Error),
+/// The paths in an archive of the directory or the .vpk file of a HUD, followed by the chunk files of a .vpk file
+/// split in chunks.
+fn find_hud_subtrees(
+    extractors: &ArchiveExtractors,
+    archive_file_path: &Path,
+    name: &HudName,
+    directory: &Path,
+    rename: Option<&RootDirectoryRename>,
+) -> Option<Vec<PathBuf>> {
+    let mut archive_entries = extractors.list(archive_file_path).ok()?;
+
+    if let Some(rename) = rename {
+        archive_entries = archive_entries.into_iter().map(|entry| rename.rename_entry(entry)).collect();
     }
 
-    use std::path::{Path, PathBuf};
+    let package = Package::from_archive_entries(directory, &archive_entries);
+    let hud_entry = package.find_hud(name)?;
+    let chunk_file_paths = archive_entries
+        .iter()
+        .map(|entry| directory.join(&entry.path))
+        .filter(|path| matches!(hud_entry.kind, PackageEntryKind::VpkFile) && is_chunk_file(&hud_entry.path, path));
+
+    std::iter::once(hud_entry.path.clone())
+        .chain(chunk_file_paths)
+        .map(|path| archive_subtree(&path, directory, rename))
+        .collect()
+}
 
-    #[derive(thiserror::Error, Debug)]
-    #[error("Failed to unrar archive: {0}")]
-    struct RarError(String);
-
-    pub fn extract_archive(
-        archive_file_path: &Path,
-        destination_directory: impl AsRef<Path>,
-    ) -> Result<PathBuf, ArchiveError> {
-        match archive_file_path.extension().and_then(|extension| extension.to_str()) {
-            Some("zip") => extract_zip(archive_file_path, destination_directory),
-            Some("7z") => extract_7z(archive_file_path, destination_directory),
-            Some("rar") => extract_rar(archive_file_path, destination_directory),
-            _ => Err(ArchiveError::UnsupportedArchiveType(archive_file_path.to_path_buf())),
+/// The path in an archive of a path it has once extracted to `directory`.
+fn archive_subtree(path: &Path, directory: &Path, rename: Option<&RootDirectoryRename>) -> Option<PathBuf> {
+    let subtree = path.strip_prefix(directory).ok()?;
+
+    Some(match rename {
+        Some(rename) => rename.archive_path(subtree),
+        None => subtree.to_path_buf(),
+    })
+}
+
+/// Open the .vpk files of a package listed from an archive, as [`Package::open`] does once it is extracted: the .vpk
+/// files without HUD files, such as sounds, are not HUDs. Each .vpk file is extracted alone to read its tree.
+fn open_listed_vpk_files(
+    mut package: Package,
+    archive_file_path: &Path,
+    directory: &Path,
+    rename: Option<&RootDirectoryRename>,
+    settings: &FetchSettings,
+) -> Result<Package, FetchError> {
+    let mut entries = Vec::with_capacity(package.entries.len());
+
+    for entry in package.entries {
+        if !matches!(entry.kind, PackageEntryKind::VpkFile) {
+            entries.push(entry);
+            continue;
+        }
+
+        let Some(subtree) = archive_subtree(&entry.path, directory, rename) else {
+            continue;
+        };
+        let vpk_directory = tempdir::TempDir::new("scan_vpk")?;
+        let limits = &settings.extraction_limits;
+        settings
+            .extractors
+            .extract_subtree(archive_file_path, vpk_directory.path(), &subtree, limits)?;
+
+        if let Ok(vpk_entry) = PackageEntry::from_path(vpk_directory.path().join(&subtree)) {
+            entries.push(PackageEntry {
+                path: entry.path,
+                name: entry.name,
+                ..vpk_entry
+            });
         }
     }
 
-    fn extract_zip(archive_file_path: &Path, destination_directory: impl AsRef<Path>) -> Result<PathBuf, ArchiveError> {
-        let destination_directory = destination_directory.as_ref();
-        let archive_file = std::fs::File::open(archive_file_path)
-            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e)))?;
-        let mut archive = zip::ZipArchive::new(archive_file)
-            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e)))?;
+    package.entries = entries;
+
+    Ok(package)
+}
+
+/// A source fetched to disk, before it is extracted.
+struct FetchedSource {
+    /// An archive, a .vpk file, or a directory.
+    path: PathBuf,
+    revision: Option<Revision>,
+    metadata: Option<SourceMetadata>,
+    sha256: Option<String>,
+    /// The name to give to the root directory of the archive, see [`crate::ResolvedSource::root_directory_name`].
+    root_directory_name: Option<String>,
+}
+
+impl FetchedSource {
+    /// The archive to extract, unless the source is a directory or a .vpk file.
+    fn archive_file_path(&self) -> Option<&Path> {
+        (self.path.is_file() && !is_vpk_file(&self.path)).then_some(self.path.as_path())
+    }
+
+    /// How to rename the root directory of the archive, if the source gives it a name.
+    fn root_directory_rename(&self, extractors: &ArchiveExtractors) -> Option<RootDirectoryRename> {
+        let name = self.root_directory_name.as_deref()?;
+        let archive_entries = extractors.list(self.archive_file_path()?).ok()?;
+
+        RootDirectoryRename::find(&archive_entries, name)
+    }
+
+    fn into_package(self, mut package: Package) -> Result<Package, FetchError> {
+        package.revision = self.revision;
+        package.metadata = self.metadata;
+        package.sha256 = self.sha256;
+
+        Ok(package)
+    }
+}
 
-        for i in 0..archive.len() {
-            let mut zip_file = archive.by_index(i).unwrap();
-            let zip_file_name = match zip_file.enclosed_name() {
-                Some(path) => path,
-                None => continue,
+/// The root directory of an archive, renamed once extracted, such as the directory named after a commit in the
+/// archive of a GitHub repository.
+struct RootDirectoryRename {
+    archive_path: PathBuf,
+    path: PathBuf,
+}
+
+impl RootDirectoryRename {
+    /// Returns none unless all the entries of the archive are in one root directory.
+    fn find(archive_entries: &[ArchiveEntry], name: &str) -> Option<Self> {
+        let root_directory = archive_entries.first()?.path.components().next()?;
+        let archive_path = PathBuf::from(root_directory.as_os_str());
+        let is_in_root_directory = |entry: &ArchiveEntry| {
+            entry.path.starts_with(&archive_path) && (entry.is_directory || entry.path != archive_path)
+        };
+
+        archive_entries.iter().all(is_in_root_directory).then(|| Self {
+            archive_path,
+            path: PathBuf::from(name),
+        })
+    }
+
+    /// The entry with the path it has once the root directory is renamed.
+    fn rename_entry(&self, mut entry: ArchiveEntry) -> ArchiveEntry {
+        if let Ok(relative_path) = entry.path.strip_prefix(&self.archive_path) {
+            entry.path = join_relative(&self.path, relative_path);
+        }
+
+        entry
+    }
+
+    /// The path in the archive of a path in the renamed root directory.
+    fn archive_path(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.path) {
+            Ok(relative_path) => join_relative(&self.archive_path, relative_path),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Rename the root directory extracted to `directory`, replacing the one extracted before from the same source.
+    fn apply(&self, directory: &Path) -> Result<(), std::io::Error> {
+        if self.archive_path == self.path {
+            return Ok(());
+        }
+
+        let path = directory.join(&self.path);
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::rename(directory.join(&self.archive_path), path)
+    }
+}
+
+/// Join a path that may be empty, without adding a trailing separator.
+fn join_relative(path: &Path, relative_path: &Path) -> PathBuf {
+    match relative_path.as_os_str().is_empty() {
+        true => path.to_path_buf(),
+        false => path.join(relative_path),
+    }
+}
+
+/// Resolve a source, and download its archive to `directory` if it is not on disk already.
+async fn fetch_source(source: Source, directory: &Path, settings: &FetchSettings) -> Result<FetchedSource, FetchError> {
+    let resolved = settings.resolvers.find(&source)?.resolve(&source, settings).await?;
+    let mut sha256 = None;
+    let mut revision = resolved.revision;
+    let path = match resolved.artifact {
+        Artifact::Download { url, file_name } => {
+            let archive_file_path = match file_name {
+                Some(file_name) => {
+                    let archive_file_path = directory.join(file_name);
+
+                    download_file(&url, &archive_file_path, settings).await?;
+
+                    archive_file_path
+                }
+                None => {
+                    let (archive_file_path, download_revision) = download_url(&url, directory, settings).await?;
+
+                    revision = revision.or(download_revision);
+
+                    archive_file_path
+                }
             };
-            let destination_path = destination_directory.join(zip_file_name);
 
-            if zip_file.name().ends_with("/") {
-                std::fs::create_dir_all(&destination_path)
-                    .map_err(|e| ArchiveError::CreateDirectoryFailed(destination_path.to_path_buf(), e))?;
-            } else {
-                let mut out_file = std::fs::File::create(&destination_path)
-                    .map_err(|e| ArchiveError::CreateFileFailed(destination_path.to_path_buf(), e))?;
+            sha256 = Some(verify_checksum(&archive_file_path, settings).await?);
 
-                std::io::copy(&mut zip_file, &mut out_file)
-                    .map_err(|e| ArchiveError::CopyFileFailed(destination_path.to_path_buf(), e))?;
+            archive_file_path
+        }
+        Artifact::LocalFile(file_path) => {
+            if !file_path.is_file() {
+                return Err(FetchError::FileNotFound(file_path));
             }
+
+            sha256 = Some(verify_checksum(&file_path, settings).await?);
+
+            file_path
         }
+        Artifact::LocalDirectory(directory_path) => {
+            if !directory_path.is_dir() {
+                return Err(FetchError::InvalidDirectory(directory_path, "Not a directory".into()));
+            }
 
-        Ok(destination_directory.to_path_buf())
-    }
+            directory_path
+        }
+    };
 
-    fn extract_7z(archive_file_path: &Path, destination_directory: impl AsRef<Path>) -> Result<PathBuf, ArchiveError> {
-        let destination_directory = destination_directory.as_ref();
+    Ok(FetchedSource {
+        path,
+        revision,
+        metadata: resolved.metadata,
+        sha256,
+        root_directory_name: resolved.root_directory_name,
+    })
+}
 
-        sevenz_rust::decompress_file(archive_file_path, destination_directory)
-            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(e)))?;
+/// How deep archives in archives are extracted, such as a zip containing one zip per variant of a HUD.
+const MAX_NESTED_ARCHIVE_DEPTH: usize = 3;
 
-        Ok(destination_directory.to_path_buf())
+/// Extract an archive and the archives it contains, and returns the directory to scan.
+fn open_archive(
+    fetched: &FetchedSource,
+    archive_file_path: &Path,
+    directory: &Path,
+    settings: &FetchSettings,
+) -> Result<PathBuf, FetchError> {
+    let extractors = &settings.extractors;
+    let limits = &settings.extraction_limits;
+    let package_root_directory = extractors.extract(archive_file_path, directory, limits)?;
+
+    if let Some(rename) = fetched.root_directory_rename(extractors) {
+        rename.apply(directory)?;
     }
 
-    fn extract_rar(archive_file_path: &Path, destination_directory: impl AsRef<Path>) -> Result<PathBuf, ArchiveError> {
-        let destination_directory = destination_directory.as_ref();
+    extractors.extract_nested(&package_root_directory, archive_file_path, MAX_NESTED_ARCHIVE_DEPTH, limits);
+
+    Ok(package_root_directory)
+}
 
-        unrar::Archive::new(archive_file_path)
-            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(RarError(e.to_string()))))?
-            .extract_to(destination_directory)
-            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(RarError(e.to_string()))))?
-            .process()
-            .map_err(|e| ArchiveError::ReadFailed(archive_file_path.to_path_buf(), Box::new(RarError(e.to_string()))))?;
+/// Compute the SHA-256 of a downloaded archive, and compare it with the expected one if there is one.
+/// Returns the SHA-256 in hexadecimal.
+async fn verify_checksum(archive_file_path: &Path, settings: &FetchSettings) -> Result<String, FetchError> {
+    let actual = sha256_file(archive_file_path).await?;
 
-        Ok(destination_directory.to_path_buf())
+    match settings.expected_sha256.as_ref() {
+        Some(expected) if !expected.eq_ignore_ascii_case(&actual) => Err(FetchError::ChecksumMismatch {
+            expected: expected.clone(),
+            actual,
+        }),
+        _ => Ok(actual),
     }
 }
 
+/// Check if a source moved since the revision was fetched, without downloading anything.
+/// Returns the new revision if there is an update, or `None` if the source did not move or is not versioned.
+pub async fn check_for_update(
+    source: &Source,
+    revision: Option<&Revision>,
+    settings: &FetchSettings,
+) -> Result<Option<Revision>, FetchError> {
+    settings
+        .resolvers
+        .find(source)?
+        .check_for_update(source, revision, settings)
+        .await
+}
+
 /// Try to find the file name, either from the URL pasted by the user, or from
-/// the URL as returned by the GET response. It also try to get the value for the entry
-/// CONTENT_DISPOSITION in the response's headers.
+/// the URL as returned by the GET response. It also try to get the file name from the
+/// header CONTENT_DISPOSITION in the response's headers.
+/// The name may have no extension, the type of the archive is detected from its content.
 fn get_file_name(url: &str, response: &reqwest::Response) -> Option<String> {
-    if let Some(file_name) = extract_file_name(url) {
-        if is_valid_filename_with_extension(&file_name) {
-            return Some(file_name);
+    let url_file_name = extract_file_name(url);
+
+    if let Some(file_name) = url_file_name.as_ref() {
+        if is_valid_filename_with_extension(file_name) {
+            return Some(file_name.clone());
         }
     }
 
-    if let Some(file_name) = extract_file_name(response.url().path()) {
-        if is_valid_filename_with_extension(&file_name) {
-            return Some(file_name);
+    let response_url_file_name = extract_file_name(response.url().path());
+
+    if let Some(file_name) = response_url_file_name.as_ref() {
+        if is_valid_filename_with_extension(file_name) {
+            return Some(file_name.clone());
         }
     }
 
-    if let Some(file_name) = response.headers().get(reqwest::header::CONTENT_DISPOSITION) {
-        if let Ok(file_name) = file_name.to_str() {
-            if is_valid_filename_with_extension(file_name) {
-                return Some(file_name.to_string());
-            }
-        }
+    let content_disposition_file_name = response
+        .headers()
+        .get(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_disposition_file_name);
+
+    content_disposition_file_name
+        .or(response_url_file_name)
+        .or(url_file_name)
+        .filter(|file_name| !file_name.is_empty() && file_name != "." && file_name != "..")
+}
+
+/// Give its extension to an archive named without, so the file has a meaningful name once installed.
+fn add_missing_extension(archive_file_path: PathBuf, extractors: &ArchiveExtractors) -> Result<PathBuf, FetchError> {
+    if archive_file_path.extension().is_some() {
+        return Ok(archive_file_path);
     }
 
-    None
+    let extension = match is_vpk_file(&archive_file_path) {
+        true => Some(VPK_FILE_EXTENSION),
+        false => extractors
+            .find(&archive_file_path)
+            .and_then(|extractor| extractor.extensions().first().copied()),
+    };
+
+    match extension {
+        Some(extension) => {
+            let renamed_file_path = archive_file_path.with_extension(extension);
+
+            std::fs::rename(&archive_file_path, &renamed_file_path)?;
+
+            Ok(renamed_file_path)
+        }
+        None => Ok(archive_file_path),
+    }
 }
 
 fn is_valid_filename_with_extension(file_name: &str) -> bool {
@@ -176,23 +655,64 @@ fn extract_file_name(url: &str) -> Option<String> {
     })
 }
 
-async fn download_url(url: &str, directory: impl AsRef<Path>) -> Result<PathBuf, FetchError> {
+/// Download a URL, naming the file from the response.
+/// Returns the path of the file, and the validators of the content if the server sent any.
+async fn download_url(
+    url: &str,
+    directory: impl AsRef<Path>,
+    settings: &FetchSettings,
+) -> Result<(PathBuf, Option<Revision>), FetchError> {
     let directory = directory.as_ref();
-    let response = reqwest::get(url).await?;
-    let file_name = get_file_name(url, &response).ok_or(FetchError::InvalidUrl(url.to_string()))?;
+    let resolved = resolve_share_link(url, &settings.http).await?;
+    let url = &resolved.url;
+    let download = match resolved.response {
+        Some(response) => start_download_from_response(url, response, settings)?,
+        None => start_download(url, settings).await?,
+    };
+    let file_name = match download.response() {
+        Some(response) => get_file_name(url, response),
+        None => download.cached_file_name().map(|file_name| file_name.to_string()),
+    }
+    .ok_or(FetchError::InvalidUrl(url.to_string()))?;
     let archive_file_path = directory.join(file_name);
-    let content = response.bytes().await?;
+    let revision = download.revision();
+
+    download.write_to(&archive_file_path, settings).await?;
 
-    tokio::fs::write(&archive_file_path, content).await?;
+    if is_html_file(&archive_file_path) {
+        // A web page cached by a previous version is evicted, so the URL is downloaded again next time.
+        if let Some(cache) = settings.cache.as_ref() {
+            cache.remove(url).await?;
+        }
+
+        return Err(html_page_error(url));
+    }
 
-    Ok(archive_file_path)
+    Ok((add_missing_extension(archive_file_path, &settings.extractors)?, revision))
+}
+
+async fn download_file(url: &str, file_path: &Path, settings: &FetchSettings) -> Result<(), FetchError> {
+    start_download(url, settings).await?.write_to(file_path, settings).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::extract_file_name;
-    use test_case::test_case;
     use super::is_valid_filename_with_extension;
+    use super::{
+        check_for_update, fetch_hud_with, fetch_package, fetch_package_with, scan_package, scan_package_with, FetchError,
+        FetchSettings, Revision, Source,
+    };
+    use crate::archive::{archive_fixture, create_zip};
+    use crate::cache::DownloadCache;
+    use crate::gamebanana::GameBanana;
+    use crate::github::{GitHub, GitHubReference};
+    use crate::vpk::write_vpk;
+    use crate::{DownloadProgress, HudName, Url};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use tempdir::TempDir;
+    use test_case::test_case;
 
     #[test_case(
         "https://github.com/n0kk/ahud/archive/refs/heads/master.zip",
@@ -210,6 +730,626 @@ mod tests {
     fn test_is_valid_filename_with_extension(input: &str, expected: bool) {
         assert_eq!(expected, is_valid_filename_with_extension(input))
     }
+
+    #[test_case("https://github.com/n0kk/ahud", Source::GitHub { owner: "n0kk".into(), repo: "ahud".into(), reference: GitHubReference::DefaultBranch })]
+    #[test_case(
+        "https://github.com/n0kk/ahud/archive/refs/heads/master.zip",
+        Source::DownloadUrl("https://github.com/n0kk/ahud/archive/refs/heads/master.zip".into())
+    )]
+    #[test_case("https://gamebanana.com/mods/409271", Source::GameBanana { mod_id: 409271, file_id: None })]
+    fn test_source_from_url(input: &str, expected: Source) {
+        assert_eq!(expected, Source::from_url(input))
+    }
+
+    /// The content of the info.vdf file of a HUD.
+    const INFO_VDF: &[u8] = b"\"hud\"\n{\n}";
+
+    async fn github_server(commit_sha: &str) -> (mockito::ServerGuard, FetchSettings) {
+        let mut server = mockito::Server::new_async().await;
+
+        server
+            .mock("GET", "/repos/n0kk/ahud/commits/master")
+            .with_body(format!(r#"{{"sha": "{}"}}"#, commit_sha))
+            .create_async()
+            .await;
+        server
+            .mock("GET", format!("/n0kk/ahud/zip/{}", commit_sha).as_str())
+            .with_body(create_zip(&[
+                (&format!("ahud-{}/", commit_sha), b""),
+                (&format!("ahud-{}/info.vdf", commit_sha), INFO_VDF),
+            ]))
+            .create_async()
+            .await;
+
+        let url = Url::parse(&server.url()).unwrap();
+        let settings = FetchSettings {
+            github: GitHub::new(url.clone(), url),
+            ..Default::default()
+        };
+
+        (server, settings)
+    }
+
+    fn ahud_master() -> Source {
+        Source::GitHub {
+            owner: "n0kk".into(),
+            repo: "ahud".into(),
+            reference: GitHubReference::Branch("master".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github() {
+        let (_server, settings) = github_server("0123456789abcdef").await;
+        let directory = TempDir::new("test_fetch_github").unwrap();
+        let package = fetch_package_with(ahud_master(), directory.path(), &settings).await.unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].name, HudName::new("ahud-master"));
+        assert_eq!(package.revision, Some(Revision::Commit("0123456789abcdef".into())));
+    }
+
+    #[tokio::test]
+    async fn test_scan_github() {
+        let (_server, settings) = github_server("0123456789abcdef").await;
+        let directory = TempDir::new("test_scan_github").unwrap();
+        let package = scan_package_with(ahud_master(), directory.path(), &settings).await.unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].name, HudName::new("ahud-master"));
+        assert_eq!(package.entries[0].path, directory.path().join("ahud-master"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_hud() {
+        let (_server, settings) = github_server("0123456789abcdef").await;
+        let directory = TempDir::new("test_fetch_github_hud").unwrap();
+        let name = HudName::new("ahud-master");
+        let package = fetch_hud_with(ahud_master(), &name, directory.path(), &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].path, directory.path().join("ahud-master"));
+        assert!(package.entries[0].path.join("info.vdf").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_local_zip() {
+        let directory = TempDir::new("test_fetch_local_zip").unwrap();
+        let archive_directory = TempDir::new("test_fetch_local_zip_archive").unwrap();
+        let archive_file_path = archive_directory.path().join("ahud.zip");
+        std::fs::write(&archive_file_path, create_zip(&[("ahud/", b""), ("ahud/info.vdf", INFO_VDF)])).unwrap();
+
+        let package = fetch_package(Source::LocalFile(archive_file_path.clone()), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].name, HudName::new("ahud"));
+        assert!(archive_file_path.is_file());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_local_vpk() {
+        let directory = TempDir::new("test_fetch_local_vpk").unwrap();
+        let vpk_directory = TempDir::new("test_fetch_local_vpk_file").unwrap();
+        let vpk_file_path = vpk_directory.path().join("minhud_plus.vpk");
+        write_hud_vpk(&vpk_file_path);
+        write_hud_vpk(&vpk_directory.path().join("other.vpk"));
+
+        let package = fetch_package(Source::LocalFile(vpk_file_path.clone()), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].name, HudName::new("minhud_plus"));
+        assert_eq!(package.entries[0].path, vpk_file_path);
+    }
+
+    /// The content of a .vpk file with a hudlayout.res file.
+    fn hud_vpk() -> Vec<u8> {
+        let directory = TempDir::new("hud_vpk").unwrap();
+        let file_path = directory.path().join("hud.vpk");
+        write_vpk(
+            &file_path,
+            1,
+            &[("scripts/hudlayout.res", b"\"Resource/HudLayout.res\"\n{\n}")],
+            None,
+        );
+
+        std::fs::read(file_path).unwrap()
+    }
+
+    fn write_hud_vpk(file_path: &Path) {
+        std::fs::write(file_path, hud_vpk()).unwrap();
+    }
+
+    const HUD_VPK_SHA256: &str = "62963942cfd838b2256a6171b72ed9d336aacfea491c7499fc6994c4a04e9ae9";
+
+    #[tokio::test]
+    async fn test_fetch_local_vpk_without_hud_files() {
+        let directory = TempDir::new("test_fetch_local_vpk_without_hud_files").unwrap();
+        let vpk_file_path = directory.path().join("hitsounds.vpk");
+        write_vpk(&vpk_file_path, 2, &[("sound/ui/hitsound.wav", b"RIFF")], None);
+
+        let package = fetch_package(Source::LocalFile(vpk_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert!(package.entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_records_checksum() {
+        let directory = TempDir::new("test_fetch_records_checksum").unwrap();
+        let vpk_file_path = directory.path().join("minhud_plus.vpk");
+        write_hud_vpk(&vpk_file_path);
+
+        let package = fetch_package(Source::LocalFile(vpk_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(Some(HUD_VPK_SHA256), package.sha256.as_deref());
+    }
+
+    #[test_case(&HUD_VPK_SHA256.to_uppercase(), true ; "matching")]
+    #[test_case("0000000000000000000000000000000000000000000000000000000000000000", false ; "mismatching")]
+    #[tokio::test]
+    async fn test_fetch_pinned_checksum(expected_sha256: &str, is_ok: bool) {
+        let directory = TempDir::new("test_fetch_pinned_checksum").unwrap();
+        let vpk_file_path = directory.path().join("minhud_plus.vpk");
+        write_hud_vpk(&vpk_file_path);
+        let settings = FetchSettings {
+            expected_sha256: Some(expected_sha256.to_string()),
+            ..Default::default()
+        };
+
+        let result = fetch_package_with(Source::LocalFile(vpk_file_path), directory.path(), &settings).await;
+
+        match is_ok {
+            true => assert!(result.is_ok()),
+            false => assert!(matches!(
+                result,
+                Err(FetchError::ChecksumMismatch { actual, .. }) if actual == HUD_VPK_SHA256
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_local_file_not_found() {
+        let directory = TempDir::new("test_fetch_local_file_not_found").unwrap();
+        let result = fetch_package(Source::LocalFile(directory.path().join("nothing.zip")), directory.path()).await;
+
+        assert!(matches!(result, Err(FetchError::FileNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_local_directory() {
+        let directory = TempDir::new("test_fetch_local_directory").unwrap();
+        let hud_directory = TempDir::new("test_fetch_local_directory_hud").unwrap();
+        std::fs::create_dir(hud_directory.path().join("ahud")).unwrap();
+        std::fs::write(hud_directory.path().join("ahud").join("info.vdf"), b"").unwrap();
+
+        let package = fetch_package(Source::LocalDirectory(hud_directory.path().to_path_buf()), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].path, hud_directory.path().join("ahud"));
+        assert!(std::fs::read_dir(directory.path()).unwrap().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gamebanana() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/Mod/409271/ProfilePage")
+            .with_body(include_str!("../fixtures/gamebanana/profile_page.json").replace("{server}", &server.url()))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/dl/815166")
+            .with_body(create_zip(&[("Black-Mesa-HUD/", b""), ("Black-Mesa-HUD/info.vdf", INFO_VDF)]))
+            .create_async()
+            .await;
+        let settings = FetchSettings {
+            gamebanana: GameBanana::new(Url::parse(&server.url()).unwrap()),
+            ..Default::default()
+        };
+        let directory = TempDir::new("test_fetch_gamebanana").unwrap();
+        let source = Source::GameBanana {
+            mod_id: 409271,
+            file_id: None,
+        };
+        let package = fetch_package_with(source, directory.path(), &settings).await.unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].name, HudName::new("Black-Mesa-HUD"));
+        assert_eq!(package.revision, Some(Revision::GameBananaFile(815166)));
+        assert_eq!(package.metadata.unwrap().author, Some("Lucifer".into()));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_progress() {
+        let mut server = mockito::Server::new_async().await;
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.add_directory("ahud", options).unwrap();
+        writer.start_file("ahud/info.vdf", options).unwrap();
+        writer.start_file("ahud/materials.bin", options).unwrap();
+        writer.write_all(&vec![0u8; 256 * 1024]).unwrap();
+        let content = writer.finish().unwrap().into_inner();
+        server.mock("GET", "/ahud.zip").with_body(&content).create_async().await;
+
+        let (progress_sender, mut progress_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let settings = FetchSettings {
+            progress_sender: Some(progress_sender),
+            ..Default::default()
+        };
+        let directory = TempDir::new("test_fetch_progress").unwrap();
+        let source = Source::DownloadUrl(format!("{}/ahud.zip", server.url()));
+
+        fetch_package_with(source, directory.path(), &settings).await.unwrap();
+        drop(settings);
+
+        let mut last_progress: Option<DownloadProgress> = None;
+
+        while let Some(progress) = progress_receiver.recv().await {
+            if let Some(last_progress) = last_progress.as_ref() {
+                assert!(progress.downloaded_bytes > last_progress.downloaded_bytes);
+            }
+
+            last_progress = Some(progress);
+        }
+
+        let last_progress = last_progress.unwrap();
+
+        assert_eq!(content.len() as u64, last_progress.downloaded_bytes);
+        assert_eq!(Some(content.len() as u64), last_progress.total_bytes);
+        assert_eq!(Some(1.0), last_progress.ratio());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_nested_archives() {
+        let directory = TempDir::new("test_fetch_nested_archives").unwrap();
+        let archive_file_path = directory.path().join("variants.zip");
+        let dark = create_zip(&[("ahud_dark/", b""), ("ahud_dark/info.vdf", INFO_VDF)]);
+        let light = create_zip(&[("ahud_light/", b""), ("ahud_light/info.vdf", INFO_VDF)]);
+        let tarball = std::fs::read(archive_fixture("ahud.tar.gz")).unwrap();
+        std::fs::write(
+            &archive_file_path,
+            create_zip(&[
+                ("dark.zip", &dark),
+                ("variants/", b""),
+                ("variants/light.zip", &light),
+                ("ahud.tar.gz", &tarball),
+                ("readme.txt", b"Pick a variant"),
+            ]),
+        )
+        .unwrap();
+
+        let package = fetch_package(Source::LocalFile(archive_file_path), directory.path())
+            .await
+            .unwrap();
+        let mut hud_paths: Vec<PathBuf> = package
+            .entries
+            .iter()
+            .map(|entry| entry.path.strip_prefix(directory.path()).unwrap().to_path_buf())
+            .collect();
+
+        hud_paths.sort();
+
+        assert_eq!(
+            vec![
+                Path::new("ahud.tar.gz").join("ahud"),
+                Path::new("dark.zip").join("ahud_dark"),
+                Path::new("variants").join("light.zip").join("ahud_light"),
+            ],
+            hud_paths
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_nested_archives_depth_is_bounded() {
+        let directory = TempDir::new("test_fetch_nested_archives_depth").unwrap();
+        let archive_file_path = directory.path().join("package.zip");
+        let mut archive = create_zip(&[("deepest/", b""), ("deepest/info.vdf", INFO_VDF)]);
+
+        for depth in 0..4 {
+            archive = create_zip(&[(&format!("level{}.zip", depth), &archive)]);
+        }
+
+        std::fs::write(&archive_file_path, archive).unwrap();
+
+        let package = fetch_package(Source::LocalFile(archive_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert!(package.entries.is_empty());
+        assert!(directory
+            .path()
+            .join("level3.zip")
+            .join("level2.zip")
+            .join("level1.zip")
+            .join("level0.zip")
+            .is_file());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_nested_archive_failing_to_extract() {
+        let directory = TempDir::new("test_fetch_nested_archive_failing").unwrap();
+        let archive_file_path = directory.path().join("ahud.zip");
+        std::fs::write(
+            &archive_file_path,
+            create_zip(&[
+                ("ahud/", b""),
+                ("ahud/info.vdf", b""),
+                ("__MACOSX/", b""),
+                ("__MACOSX/._ahud.zip", b"not an archive"),
+            ]),
+        )
+        .unwrap();
+
+        let package = fetch_package(Source::LocalFile(archive_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(package.entries[0].name, HudName::new("ahud"));
+        assert!(directory.path().join("__MACOSX").join("._ahud.zip").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_scan_package_without_extracting() {
+        let directory = TempDir::new("test_scan_package_without_extracting").unwrap();
+
+        let package = scan_package(Source::LocalFile(archive_fixture("huds.zip")), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![HudName::new("ahud"), HudName::new("bhud"), HudName::new("chud")],
+            package.hud_names().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(directory.path().join("pack").join("ahud"), package.entries[0].path);
+        assert_eq!(0, std::fs::read_dir(directory.path()).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_scan_package_skips_vpk_files_without_hud_files() {
+        let directory = TempDir::new("test_scan_package_skips_vpk_files_without_hud_files").unwrap();
+        let vpk_directory = TempDir::new("test_scan_package_skips_vpk_files_without_hud_files").unwrap();
+        let ahud_file_path = vpk_directory.path().join("ahud.vpk");
+        let sounds_file_path = vpk_directory.path().join("sounds.vpk");
+        write_vpk(
+            &ahud_file_path,
+            2,
+            &[("resource/ui/hudlayout.res", b"\"Resource/UI/HudLayout.res\"\n{\n}")],
+            None,
+        );
+        write_vpk(&sounds_file_path, 2, &[("sound/ui/hitsound.wav", b"RIFF")], None);
+        let archive_file_path = vpk_directory.path().join("huds.zip");
+        let archive = create_zip(&[
+            ("ahud.vpk", &std::fs::read(&ahud_file_path).unwrap()),
+            ("sounds.vpk", &std::fs::read(&sounds_file_path).unwrap()),
+        ]);
+        std::fs::write(&archive_file_path, archive).unwrap();
+
+        let package = scan_package(Source::LocalFile(archive_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(vec![&HudName::new("ahud")], package.hud_names().collect::<Vec<_>>());
+        assert_eq!(directory.path().join("ahud.vpk"), package.entries[0].path);
+        assert_eq!(vec!["resource/ui/hudlayout.res"], package.entries[0].vpk_files);
+        assert_eq!(0, std::fs::read_dir(directory.path()).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_scan_package_with_nested_archives() {
+        let directory = TempDir::new("test_scan_package_with_nested_archives").unwrap();
+        let archive_file_path = directory.path().join("variants.zip");
+        let dark = create_zip(&[("ahud_dark/", b""), ("ahud_dark/info.vdf", INFO_VDF)]);
+        std::fs::write(&archive_file_path, create_zip(&[("ahud_dark.zip", &dark)])).unwrap();
+
+        let package = scan_package(Source::LocalFile(archive_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(vec![&HudName::new("ahud_dark")], package.hud_names().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_hud_extracts_only_its_files() {
+        let directory = TempDir::new("test_fetch_hud_extracts_only_its_files").unwrap();
+        let source = Source::LocalFile(archive_fixture("huds.zip"));
+
+        let package = fetch_hud_with(source, &HudName::new("ahud"), directory.path(), &FetchSettings::default())
+            .await
+            .unwrap();
+
+        let pack_directory = directory.path().join("pack");
+
+        assert_eq!(vec![&HudName::new("ahud")], package.hud_names().collect::<Vec<_>>());
+        assert!(pack_directory
+            .join("ahud")
+            .join("resource")
+            .join("ui")
+            .join("hudlayout.res")
+            .is_file());
+        assert!(!pack_directory.join("bhud").exists());
+        assert!(!pack_directory.join("chud.vpk").exists());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_hud_vpk_file() {
+        let directory = TempDir::new("test_fetch_hud_vpk_file").unwrap();
+        let source = Source::LocalFile(archive_fixture("huds.zip"));
+
+        let package = fetch_hud_with(source, &HudName::new("chud"), directory.path(), &FetchSettings::default())
+            .await
+            .unwrap();
+
+        assert_eq!(vec![&HudName::new("chud")], package.hud_names().collect::<Vec<_>>());
+        assert_eq!(vec!["resource/ui/hudlayout.res"], package.entries[0].vpk_files);
+        assert!(!directory.path().join("pack").join("ahud").exists());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_mislabeled_archive() {
+        let directory = TempDir::new("test_fetch_mislabeled_archive").unwrap();
+        let archive_directory = TempDir::new("test_fetch_mislabeled_archive_file").unwrap();
+        let archive_file_path = archive_directory.path().join("ahud.rar");
+        std::fs::write(&archive_file_path, create_zip(&[("ahud/", b""), ("ahud/info.vdf", INFO_VDF)])).unwrap();
+
+        let package = fetch_package(Source::LocalFile(archive_file_path), directory.path())
+            .await
+            .unwrap();
+
+        assert_eq!(package.entries[0].name, HudName::new("ahud"));
+    }
+
+    #[test_case(Some("attachment; filename*=UTF-8''minhud_plus.vpk"), "minhud_plus" ; "content disposition")]
+    #[test_case(None, "815166" ; "detected from content")]
+    #[tokio::test]
+    async fn test_fetch_extensionless_link(content_disposition: Option<&str>, expected_hud_name: &str) {
+        let mut server = mockito::Server::new_async().await;
+        let mut mock = server.mock("GET", "/dl/815166").with_body(hud_vpk());
+
+        if let Some(content_disposition) = content_disposition {
+            mock = mock.with_header("content-disposition", content_disposition);
+        }
+
+        mock.create_async().await;
+
+        let directory = TempDir::new("test_fetch_extensionless_link").unwrap();
+        let source = Source::DownloadUrl(format!("{}/dl/815166", server.url()));
+        let package = fetch_package(source, directory.path()).await.unwrap();
+
+        assert_eq!(package.entries.len(), 1);
+        assert_eq!(package.entries[0].name, HudName::new(expected_hud_name));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_records_http_validators() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/ahud.zip")
+            .with_header("etag", "\"1\"")
+            .with_header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+            .with_body(create_zip(&[("ahud/", b""), ("ahud/info.vdf", INFO_VDF)]))
+            .create_async()
+            .await;
+        let directory = TempDir::new("test_fetch_records_http_validators").unwrap();
+        let source = Source::DownloadUrl(format!("{}/ahud.zip", server.url()));
+        let package = fetch_package(source, directory.path()).await.unwrap();
+
+        assert_eq!(
+            package.revision,
+            Some(Revision::Http {
+                etag: Some("\"1\"".into()),
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".into()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_http_error() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/ahud.zip").with_status(404).create_async().await;
+        let directory = TempDir::new("test_fetch_http_error").unwrap();
+        let source = Source::DownloadUrl(format!("{}/ahud.zip", server.url()));
+
+        let result = fetch_package(source, directory.path()).await;
+
+        assert!(matches!(result, Err(FetchError::HttpStatus { status, .. }) if status.as_u16() == 404));
+    }
+
+    #[test_case(Some("text/html; charset=utf-8") ; "with content type")]
+    #[test_case(None ; "without content type")]
+    #[tokio::test]
+    async fn test_fetch_html_page(content_type: Option<&str>) {
+        let mut server = mockito::Server::new_async().await;
+        let mut mock = server
+            .mock("GET", "/ahud.zip")
+            .with_body("\n<!DOCTYPE html><html><body>Download</body></html>");
+
+        if let Some(content_type) = content_type {
+            mock = mock.with_header("content-type", content_type);
+        }
+
+        mock.create_async().await;
+
+        let directory = TempDir::new("test_fetch_html_page").unwrap();
+        let source = Source::DownloadUrl(format!("{}/ahud.zip", server.url()));
+
+        let result = fetch_package(source, directory.path()).await;
+
+        assert!(matches!(result, Err(FetchError::HtmlPage { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_html_page_is_not_cached() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/ahud.zip")
+            .with_body("<!DOCTYPE html><html><body>Download</body></html>")
+            .create_async()
+            .await;
+        let directory = TempDir::new("test_fetch_html_page_is_not_cached").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 1024);
+        let settings = FetchSettings {
+            cache: Some(cache.clone()),
+            ..Default::default()
+        };
+        let source = Source::DownloadUrl(format!("{}/ahud.zip", server.url()));
+
+        let result = fetch_package_with(source, directory.path(), &settings).await;
+
+        assert!(matches!(result, Err(FetchError::HtmlPage { .. })));
+        assert!(cache.entries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cached_html_page_is_evicted() {
+        let directory = TempDir::new("test_fetch_cached_html_page_is_evicted").unwrap();
+        let cache = DownloadCache::new(directory.path().join("cache"), 1024);
+        let html_file_path = directory.path().join("ahud.html");
+        std::fs::write(&html_file_path, "<html><body>Download</body></html>").unwrap();
+        let url = "http://localhost/ahud.zip";
+        cache.insert(url, None, None, &html_file_path).await.unwrap();
+        let settings = FetchSettings {
+            cache: Some(cache.clone()),
+            ..Default::default()
+        };
+
+        let result = fetch_package_with(Source::DownloadUrl(url.into()), directory.path(), &settings).await;
+
+        assert!(matches!(result, Err(FetchError::HtmlPage { .. })));
+        assert!(cache.entries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_for_update_github() {
+        let (_server, settings) = github_server("fedcba9876543210").await;
+        let installed_revision = Revision::Commit("0123456789abcdef".into());
+        let update = check_for_update(&ahud_master(), Some(&installed_revision), &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(update, Some(Revision::Commit("fedcba9876543210".into())));
+    }
+
+    #[tokio::test]
+    async fn test_check_for_update_github_up_to_date() {
+        let (_server, settings) = github_server("fedcba9876543210").await;
+        let installed_revision = Revision::Commit("fedcba9876543210".into());
+        let update = check_for_update(&ahud_master(), Some(&installed_revision), &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(update, None);
+    }
 }
 
 #[cfg(test)]
@@ -271,7 +1411,9 @@ mod slow_tests {
     #[tokio::test]
     async fn test_fetch_masterconfig() {
         let directory = TempDir::new("test_fetch_masterconfig").unwrap();
-        let source = Source::DownloadUrl("https://codeload.github.com/p3tr1ch0r/insomniaHUD/legacy.zip/9753cfb9d655a617d4527cce37fca079f740378f".into());
+        let source = Source::DownloadUrl(
+            "https://codeload.github.com/p3tr1ch0r/insomniaHUD/legacy.zip/9753cfb9d655a617d4527cce37fca079f740378f".into(),
+        );
         let package = fetch_package(source, directory.path()).await.unwrap();
 
         assert_eq!(package.entries.len(), 1);
@@ -0,0 +1,150 @@
+//! Detect the HUDs whose source has a newer version than the one fetched.
+//! The revision recorded when a HUD was installed, or added if it is not installed, is compared with the revision
+//! its source points to now. Nothing is downloaded.
+
+use crate::source::{check_for_update, FetchSettings, Revision, Source};
+use crate::{HudName, Registry};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UpdateStatus {
+    UpToDate,
+    /// The source moved to a new revision.
+    Available(Revision),
+    /// The source could not be checked.
+    Failed(String),
+}
+
+/// The result of the check of a HUD.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpdateCheck {
+    pub name: HudName,
+    pub status: UpdateStatus,
+}
+
+impl UpdateCheck {
+    pub fn is_update_available(&self) -> bool {
+        matches!(self.status, UpdateStatus::Available(_))
+    }
+}
+
+/// Check the source of each HUD of the registry.
+/// The HUDs without source are skipped.
+pub async fn check_for_updates(registry: &Registry, settings: &FetchSettings) -> Vec<UpdateCheck> {
+    let mut checks = Vec::new();
+
+    for info in registry.iter().filter(|info| info.source != Source::None) {
+        let revision = info.install.revision().or(info.revision.as_ref());
+        let status = match check_for_update(&info.source, revision, settings).await {
+            Ok(Some(revision)) => UpdateStatus::Available(revision),
+            Ok(None) => UpdateStatus::UpToDate,
+            Err(error) => UpdateStatus::Failed(error.to_string()),
+        };
+
+        checks.push(UpdateCheck {
+            name: info.name.clone(),
+            status,
+        });
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_for_updates, UpdateStatus};
+    use crate::{FetchSettings, HudName, Install, Registry, Revision, Source};
+    use mockito::Matcher;
+
+    fn registry(url: String, etag: &str) -> Registry {
+        let mut registry = Registry::new();
+        let name = HudName::new("ahud");
+
+        registry.add(name.clone(), Source::DownloadUrl(url));
+        registry.set_install(
+            &name,
+            Install::installed_now(
+                "/huds/ahud",
+                Some(Revision::Http {
+                    etag: Some(etag.into()),
+                    last_modified: None,
+                }),
+            ),
+        );
+
+        registry
+    }
+
+    #[tokio::test]
+    async fn test_check_for_updates_not_modified() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("HEAD", "/ahud.zip")
+            .match_header("if-none-match", "\"1\"")
+            .with_status(304)
+            .create_async()
+            .await;
+        let registry = registry(format!("{}/ahud.zip", server.url()), "\"1\"");
+
+        let checks = check_for_updates(&registry, &FetchSettings::default()).await;
+
+        mock.assert_async().await;
+        assert_eq!(UpdateStatus::UpToDate, checks[0].status);
+    }
+
+    #[tokio::test]
+    async fn test_check_for_updates_modified() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("HEAD", "/ahud.zip")
+            .with_header("etag", "\"2\"")
+            .create_async()
+            .await;
+        let registry = registry(format!("{}/ahud.zip", server.url()), "\"1\"");
+
+        let checks = check_for_updates(&registry, &FetchSettings::default()).await;
+
+        assert!(checks[0].is_update_available());
+        assert_eq!(
+            UpdateStatus::Available(Revision::Http {
+                etag: Some("\"2\"".into()),
+                last_modified: None,
+            }),
+            checks[0].status
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_for_updates_without_validator() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("HEAD", "/ahud.zip")
+            .match_header("if-none-match", Matcher::Any)
+            .create_async()
+            .await;
+        let registry = registry(format!("{}/ahud.zip", server.url()), "\"1\"");
+
+        let checks = check_for_updates(&registry, &FetchSettings::default()).await;
+
+        assert_eq!(UpdateStatus::UpToDate, checks[0].status);
+    }
+
+    #[tokio::test]
+    async fn test_check_for_updates_skips_huds_without_source() {
+        let mut registry = Registry::new();
+
+        registry.add(HudName::new("ahud"), Source::None);
+
+        assert!(check_for_updates(&registry, &FetchSettings::default()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_for_updates_failed() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("HEAD", "/ahud.zip").with_status(404).create_async().await;
+        let registry = registry(format!("{}/ahud.zip", server.url()), "\"1\"");
+
+        let checks = check_for_updates(&registry, &FetchSettings::default()).await;
+
+        assert!(matches!(checks[0].status, UpdateStatus::Failed(_)));
+    }
+}
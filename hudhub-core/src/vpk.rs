@@ -0,0 +1,621 @@
+//! Read the Valve pak files, the .vpk files the Source engine loads like directories.
+//! A HUD is sometimes distributed as a single .vpk file instead of a directory.
+//!
+//! A .vpk file starts with a header and the tree of its files, grouped by extension then by directory.
+//! The data of the files follows the tree, or is in chunk files next to it for the big packages:
+//! `pak01_dir.vpk` holds the tree, and `pak01_000.vpk`, `pak01_001.vpk`, ... hold the data.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// The signature of the Valve pak files, 0x55AA1234 in little endian.
+pub(crate) const VPK_SIGNATURE: [u8; 4] = [0x34, 0x12, 0xAA, 0x55];
+
+/// The size of the header of version 1: signature, version and tree size.
+const HEADER_SIZE_V1: u64 = 12;
+
+/// The size of the header of version 2, which adds the sizes of the data and checksum sections.
+const HEADER_SIZE_V2: u64 = 28;
+
+/// The archive index of the files whose data follows the tree, instead of being in a chunk file.
+const EMBEDDED_ARCHIVE_INDEX: u16 = 0x7FFF;
+
+const ENTRY_TERMINATOR: u16 = 0xFFFF;
+
+/// The names of the tree are a single space when they are empty, such as the directory of the root files.
+const EMPTY_NAME: &str = " ";
+
+/// The suffix of the file that holds the tree of a package split in chunks.
+const DIRECTORY_FILE_SUFFIX: &str = "_dir";
+
+#[derive(thiserror::Error, Debug)]
+pub enum VpkError {
+    #[error("Failed to read '{0}': {1}")]
+    ReadFailed(PathBuf, std::io::Error),
+
+    #[error("'{0}' is not a .vpk file")]
+    InvalidSignature(PathBuf),
+
+    #[error("Unsupported version {1} of .vpk file '{0}'")]
+    UnsupportedVersion(PathBuf, u32),
+
+    #[error("The tree of the .vpk file '{0}' is invalid")]
+    InvalidTree(PathBuf),
+
+    #[error("The file '{0}' is not in the .vpk file")]
+    FileNotFound(String),
+
+    #[error("The data of '{1}' is truncated in '{0}'")]
+    TruncatedData(PathBuf, String),
+
+    #[error("The checksum of '{0}' does not match")]
+    ChecksumMismatch(String),
+}
+
+/// A file of a .vpk file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VpkEntry {
+    /// The path of the file in the package, separated by `/`, such as `resource/ui/hudlayout.res`.
+    pub path: String,
+
+    /// The size of the file in bytes.
+    pub size: u64,
+
+    crc: u32,
+    /// The first bytes of the file, stored in the tree.
+    preload: Vec<u8>,
+    archive_index: u16,
+    offset: u32,
+    length: u32,
+}
+
+/// A .vpk file opened to list its files and read them.
+/// Only the tree is read when the file is opened, the data of a file is read when asked for.
+#[derive(Clone, Debug)]
+pub struct VpkArchive {
+    file_path: PathBuf,
+    version: u32,
+    /// Where the data of the embedded files starts in the file.
+    data_offset: u64,
+    entries: Vec<VpkEntry>,
+}
+
+impl VpkArchive {
+    /// Open a .vpk file of version 1 or 2.
+    /// For a package split in chunks, this is the file named like `pak01_dir.vpk`.
+    pub fn open(file_path: impl AsRef<Path>) -> Result<Self, VpkError> {
+        let file_path = file_path.as_ref();
+        let read_failed = |error| VpkError::ReadFailed(file_path.to_path_buf(), error);
+        let mut file = std::fs::File::open(file_path).map_err(read_failed)?;
+        let file_size = file.metadata().map_err(read_failed)?.len();
+        let mut header = [0u8; HEADER_SIZE_V1 as usize];
+
+        if file.read_exact(&mut header).is_err() || header[0..4] != VPK_SIGNATURE {
+            return Err(VpkError::InvalidSignature(file_path.to_path_buf()));
+        }
+
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let tree_size = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as u64;
+        let header_size = match version {
+            1 => HEADER_SIZE_V1,
+            2 => HEADER_SIZE_V2,
+            version => return Err(VpkError::UnsupportedVersion(file_path.to_path_buf(), version)),
+        };
+
+        if header_size + tree_size > file_size {
+            return Err(VpkError::InvalidTree(file_path.to_path_buf()));
+        }
+
+        let mut tree = Vec::with_capacity(tree_size as usize);
+
+        file.seek(SeekFrom::Start(header_size)).map_err(read_failed)?;
+        file.take(tree_size).read_to_end(&mut tree).map_err(read_failed)?;
+
+        let entries = parse_tree(&tree).ok_or_else(|| VpkError::InvalidTree(file_path.to_path_buf()))?;
+
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            version,
+            data_offset: header_size + tree_size,
+            entries,
+        })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The files, in the order of the tree.
+    pub fn entries(&self) -> &[VpkEntry] {
+        &self.entries
+    }
+
+    /// The entry of a file. The paths are not case sensitive, like in the game.
+    pub fn find(&self, file_path: &str) -> Option<&VpkEntry> {
+        let file_path = file_path.replace('\\', "/");
+
+        self.entries.iter().find(|entry| entry.path.eq_ignore_ascii_case(&file_path))
+    }
+
+    pub fn contains(&self, file_path: &str) -> bool {
+        self.find(file_path).is_some()
+    }
+
+    /// Read the content of a file.
+    pub fn read(&self, file_path: &str) -> Result<Vec<u8>, VpkError> {
+        let entry = self
+            .find(file_path)
+            .ok_or_else(|| VpkError::FileNotFound(file_path.to_string()))?;
+
+        self.read_entry(entry)
+    }
+
+    /// Read the content of a file, from the tree, the .vpk file or its chunk file.
+    /// The content is checked against the checksum of the entry.
+    pub fn read_entry(&self, entry: &VpkEntry) -> Result<Vec<u8>, VpkError> {
+        let mut content = entry.preload.clone();
+
+        if entry.length > 0 {
+            let (data_file_path, offset) = match entry.archive_index {
+                EMBEDDED_ARCHIVE_INDEX => (self.file_path.clone(), self.data_offset + entry.offset as u64),
+                archive_index => (self.chunk_file_path(archive_index), entry.offset as u64),
+            };
+            let read_failed = |error| VpkError::ReadFailed(data_file_path.clone(), error);
+            let mut file = std::fs::File::open(&data_file_path).map_err(read_failed)?;
+
+            file.seek(SeekFrom::Start(offset)).map_err(read_failed)?;
+
+            let read_length = file
+                .take(entry.length as u64)
+                .read_to_end(&mut content)
+                .map_err(read_failed)?;
+
+            if read_length != entry.length as usize {
+                return Err(VpkError::TruncatedData(data_file_path, entry.path.clone()));
+            }
+        }
+
+        if crc32fast::hash(&content) != entry.crc {
+            return Err(VpkError::ChecksumMismatch(entry.path.clone()));
+        }
+
+        Ok(content)
+    }
+
+    /// True if the package contains the files of a HUD, not only sounds, models or materials.
+    pub fn is_hud(&self) -> bool {
+        self.entries.iter().any(|entry| {
+            let path = entry.path.to_ascii_lowercase();
+
+            path.starts_with("resource/ui/") || path == "scripts/hudlayout.res"
+        })
+    }
+
+    /// The chunk files are named after the .vpk file, without its `_dir` suffix: `pak01_dir.vpk` has `pak01_000.vpk`.
+    fn chunk_file_path(&self, archive_index: u16) -> PathBuf {
+        let stem = self
+            .file_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        self.file_path
+            .with_file_name(format!("{}_{:03}.vpk", package_name(&stem), archive_index))
+    }
+}
+
+/// The name of a package is the stem of its file, without the `_dir` suffix of a package split in chunks.
+pub(crate) fn package_name(file_stem: &str) -> &str {
+    file_stem.strip_suffix(DIRECTORY_FILE_SUFFIX).unwrap_or(file_stem)
+}
+
+/// True if a file is one of the chunk files of a package split in chunks, such as `pak01_000.vpk` for
+/// `pak01_dir.vpk`. The chunk files are in the directory of the file holding the tree.
+pub(crate) fn is_chunk_file(directory_file_path: &Path, file_path: &Path) -> bool {
+    let Some(stem) = directory_file_path.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+    let Some(stem) = stem.strip_suffix(DIRECTORY_FILE_SUFFIX) else {
+        return false;
+    };
+    let archive_index = file_path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| file_name.strip_prefix(stem))
+        .and_then(|file_name| file_name.strip_prefix('_'))
+        .and_then(|file_name| file_name.strip_suffix(".vpk"));
+
+    file_path.parent() == directory_file_path.parent()
+        && archive_index.is_some_and(|index| index.len() == 3 && index.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
+/// The chunk files on disk of a package split in chunks, sorted. Empty for a package that is not split.
+pub(crate) fn chunk_file_paths(directory_file_path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let Some(directory) = directory_file_path.parent() else {
+        return Ok(Vec::new());
+    };
+    let mut file_paths = Vec::new();
+
+    for entry in std::fs::read_dir(directory)? {
+        let file_path = entry?.path();
+
+        if is_chunk_file(directory_file_path, &file_path) {
+            file_paths.push(file_path);
+        }
+    }
+
+    file_paths.sort();
+
+    Ok(file_paths)
+}
+
+/// Parse the tree of the files, None if it is malformed.
+/// The tree lists the extensions, each followed by its directories, each followed by its files.
+/// Each list ends with an empty string.
+fn parse_tree(tree: &[u8]) -> Option<Vec<VpkEntry>> {
+    let mut reader = TreeReader { tree, position: 0 };
+    let mut entries = Vec::new();
+
+    loop {
+        let extension = reader.read_string()?;
+
+        if extension.is_empty() {
+            break;
+        }
+
+        loop {
+            let directory = reader.read_string()?;
+
+            if directory.is_empty() {
+                break;
+            }
+
+            loop {
+                let name = reader.read_string()?;
+
+                if name.is_empty() {
+                    break;
+                }
+
+                entries.push(reader.read_entry(entry_path(&directory, &name, &extension))?);
+            }
+        }
+    }
+
+    Some(entries)
+}
+
+fn entry_path(directory: &str, name: &str, extension: &str) -> String {
+    let mut path = String::new();
+
+    if directory != EMPTY_NAME {
+        path.push_str(directory.trim_matches(|c| c == '/' || c == '\\'));
+        path.push('/');
+    }
+
+    path.push_str(name);
+
+    if extension != EMPTY_NAME {
+        path.push('.');
+        path.push_str(extension);
+    }
+
+    path.replace('\\', "/")
+}
+
+struct TreeReader<'a> {
+    tree: &'a [u8],
+    position: usize,
+}
+
+impl TreeReader<'_> {
+    fn read_bytes(&mut self, count: usize) -> Option<&[u8]> {
+        let bytes = self.tree.get(self.position..self.position.checked_add(count)?)?;
+
+        self.position += count;
+
+        Some(bytes)
+    }
+
+    fn read_u16(&mut self) -> Option<u16> {
+        self.read_bytes(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        self.read_bytes(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A string terminated by a null byte.
+    fn read_string(&mut self) -> Option<String> {
+        let length = self.tree.get(self.position..)?.iter().position(|byte| *byte == 0)?;
+        let string = String::from_utf8_lossy(self.read_bytes(length)?).into_owned();
+
+        self.position += 1;
+
+        Some(string)
+    }
+
+    fn read_entry(&mut self, path: String) -> Option<VpkEntry> {
+        let crc = self.read_u32()?;
+        let preload_size = self.read_u16()?;
+        let archive_index = self.read_u16()?;
+        let offset = self.read_u32()?;
+        let length = self.read_u32()?;
+
+        if self.read_u16()? != ENTRY_TERMINATOR {
+            return None;
+        }
+
+        let preload = self.read_bytes(preload_size as usize)?.to_vec();
+
+        Some(VpkEntry {
+            path,
+            size: preload.len() as u64 + length as u64,
+            crc,
+            preload,
+            archive_index,
+            offset,
+            length,
+        })
+    }
+}
+
+/// Write a .vpk file with its files embedded, or split in chunk files of `chunk_size` bytes when it is set.
+#[cfg(test)]
+pub(crate) fn write_vpk(file_path: &Path, version: u32, files: &[(&str, &[u8])], chunk_size: Option<usize>) {
+    use std::collections::BTreeMap;
+
+    let mut tree_by_extension: BTreeMap<_, BTreeMap<_, Vec<_>>> = BTreeMap::new();
+
+    for (path, content) in files {
+        let (directory, file_name) = path.rsplit_once('/').unwrap_or((EMPTY_NAME, path));
+        let (name, extension) = file_name.rsplit_once('.').unwrap_or((file_name, EMPTY_NAME));
+
+        tree_by_extension
+            .entry(extension)
+            .or_default()
+            .entry(directory)
+            .or_default()
+            .push((name, *content));
+    }
+
+    let mut tree = Vec::new();
+    let mut data = Vec::new();
+    let mut chunks: Vec<Vec<u8>> = Vec::new();
+
+    for (extension, directories) in tree_by_extension {
+        tree.extend_from_slice(extension.as_bytes());
+        tree.push(0);
+
+        for (directory, files) in directories {
+            tree.extend_from_slice(directory.as_bytes());
+            tree.push(0);
+
+            for (name, content) in files {
+                let (archive_index, offset) = match chunk_size {
+                    None => {
+                        data.extend_from_slice(content);
+                        (EMBEDDED_ARCHIVE_INDEX, data.len() - content.len())
+                    }
+                    Some(chunk_size) => {
+                        if chunks
+                            .last()
+                            .filter(|chunk| chunk.len() + content.len() <= chunk_size)
+                            .is_none()
+                        {
+                            chunks.push(Vec::new());
+                        }
+
+                        let archive_index = chunks.len() - 1;
+                        let chunk = &mut chunks[archive_index];
+                        chunk.extend_from_slice(content);
+                        (archive_index as u16, chunk.len() - content.len())
+                    }
+                };
+
+                tree.extend_from_slice(name.as_bytes());
+                tree.push(0);
+                tree.extend_from_slice(&crc32fast::hash(content).to_le_bytes());
+                tree.extend_from_slice(&0u16.to_le_bytes());
+                tree.extend_from_slice(&archive_index.to_le_bytes());
+                tree.extend_from_slice(&(offset as u32).to_le_bytes());
+                tree.extend_from_slice(&(content.len() as u32).to_le_bytes());
+                tree.extend_from_slice(&ENTRY_TERMINATOR.to_le_bytes());
+            }
+
+            tree.push(0);
+        }
+
+        tree.push(0);
+    }
+
+    tree.push(0);
+
+    let mut content = VPK_SIGNATURE.to_vec();
+    content.extend_from_slice(&version.to_le_bytes());
+    content.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+
+    if version == 2 {
+        content.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        content.extend_from_slice(&[0u8; 12]);
+    }
+
+    content.extend_from_slice(&tree);
+    content.extend_from_slice(&data);
+    std::fs::write(file_path, content).unwrap();
+
+    let stem = package_name(file_path.file_stem().unwrap().to_str().unwrap());
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        std::fs::write(file_path.with_file_name(format!("{}_{:03}.vpk", stem, index)), chunk).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_vpk, VpkArchive, VpkError};
+    use tempdir::TempDir;
+    use test_case::test_case;
+
+    const HUD_FILES: &[(&str, &[u8])] = &[
+        ("info.vdf", b"\"ahud\"\n{\n\t\"ui_version\"\t\"3\"\n}"),
+        ("resource/ui/hudlayout.res", b"\"Resource/UI/HudLayout.res\"\n{\n}"),
+        (
+            "resource/ui/hudplayerhealth.res",
+            b"\"Resource/UI/HudPlayerHealth.res\"\n{\n}",
+        ),
+        ("resource/clientscheme.res", b"Scheme\n{\n}"),
+        ("materials/vgui/replay/thumbnails/ahud.vmt", b"\"UnlitGeneric\"\n{\n}"),
+        ("README", b"A HUD"),
+    ];
+
+    #[test_case(1, None ; "version 1")]
+    #[test_case(2, None ; "version 2")]
+    #[test_case(2, Some(40) ; "chunks")]
+    fn test_read_vpk(version: u32, chunk_size: Option<usize>) {
+        let directory = TempDir::new("test_read_vpk").unwrap();
+        let file_path = directory.path().join("ahud_dir.vpk");
+        write_vpk(&file_path, version, HUD_FILES, chunk_size);
+
+        let archive = VpkArchive::open(&file_path).unwrap();
+        let mut paths: Vec<&str> = archive.entries().iter().map(|entry| entry.path.as_str()).collect();
+        let mut expected_paths: Vec<&str> = HUD_FILES.iter().map(|(path, _)| *path).collect();
+
+        paths.sort();
+        expected_paths.sort();
+
+        assert_eq!(version, archive.version());
+        assert_eq!(expected_paths, paths);
+
+        for (path, content) in HUD_FILES {
+            assert_eq!(content.to_vec(), archive.read(path).unwrap());
+            assert_eq!(content.len() as u64, archive.find(path).unwrap().size);
+        }
+    }
+
+    #[test]
+    fn test_read_vpk_chunk_files() {
+        let directory = TempDir::new("test_read_vpk_chunk_files").unwrap();
+        write_vpk(&directory.path().join("pak01_dir.vpk"), 2, HUD_FILES, Some(40));
+
+        assert!(directory.path().join("pak01_000.vpk").is_file());
+        assert!(directory.path().join("pak01_001.vpk").is_file());
+        assert!(matches!(
+            VpkArchive::open(directory.path().join("pak01_000.vpk")),
+            Err(VpkError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_vpk_missing_chunk_file() {
+        let directory = TempDir::new("test_read_vpk_missing_chunk_file").unwrap();
+        let file_path = directory.path().join("pak01_dir.vpk");
+        write_vpk(&file_path, 2, HUD_FILES, Some(40));
+        std::fs::remove_file(directory.path().join("pak01_000.vpk")).unwrap();
+
+        let archive = VpkArchive::open(&file_path).unwrap();
+
+        assert!(matches!(archive.read("README"), Err(VpkError::ReadFailed(..))));
+    }
+
+    #[test_case("resource/ui/hudlayout.res" ; "lowercase")]
+    #[test_case("Resource/UI/HudLayout.res" ; "mixed case")]
+    #[test_case("resource\\ui\\hudlayout.res" ; "backslashes")]
+    fn test_find_vpk_entry(file_path: &str) {
+        let directory = TempDir::new("test_find_vpk_entry").unwrap();
+        let vpk_file_path = directory.path().join("ahud.vpk");
+        write_vpk(&vpk_file_path, 1, HUD_FILES, None);
+
+        let archive = VpkArchive::open(&vpk_file_path).unwrap();
+
+        assert!(archive.contains(file_path));
+    }
+
+    #[test]
+    fn test_read_missing_vpk_entry() {
+        let directory = TempDir::new("test_read_missing_vpk_entry").unwrap();
+        let file_path = directory.path().join("ahud.vpk");
+        write_vpk(&file_path, 1, HUD_FILES, None);
+
+        let archive = VpkArchive::open(&file_path).unwrap();
+
+        assert!(matches!(
+            archive.read("scripts/hudlayout.res"),
+            Err(VpkError::FileNotFound(_))
+        ));
+    }
+
+    #[test_case(HUD_FILES, true ; "resource ui")]
+    #[test_case(&[("scripts/hudlayout.res", b"")], true ; "hud layout")]
+    #[test_case(&[("sound/ui/hitsound.wav", b"RIFF"), ("models/player/scout.mdl", b"IDST")], false ; "sounds and models")]
+    #[test_case(&[("resource/ui.res", b"")], false ; "not in resource ui")]
+    fn test_vpk_is_hud(files: &[(&str, &[u8])], expected: bool) {
+        let directory = TempDir::new("test_vpk_is_hud").unwrap();
+        let file_path = directory.path().join("ahud.vpk");
+        write_vpk(&file_path, 2, files, None);
+
+        assert_eq!(expected, VpkArchive::open(&file_path).unwrap().is_hud());
+    }
+
+    #[test_case(b"" ; "empty")]
+    #[test_case(b"PK\x03\x04rest of the zip" ; "zip")]
+    fn test_open_invalid_signature(content: &[u8]) {
+        let directory = TempDir::new("test_open_invalid_signature").unwrap();
+        let file_path = directory.path().join("ahud.vpk");
+        std::fs::write(&file_path, content).unwrap();
+
+        assert!(matches!(VpkArchive::open(&file_path), Err(VpkError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn test_open_unsupported_version() {
+        let directory = TempDir::new("test_open_unsupported_version").unwrap();
+        let file_path = directory.path().join("ahud.vpk");
+        std::fs::write(&file_path, b"\x34\x12\xAA\x55\x03\x00\x00\x00\x00\x00\x00\x00").unwrap();
+
+        assert!(matches!(
+            VpkArchive::open(&file_path),
+            Err(VpkError::UnsupportedVersion(_, 3))
+        ));
+    }
+
+    #[test_case(|content: &mut Vec<u8>| content.truncate(40) ; "truncated tree")]
+    #[test_case(|content: &mut Vec<u8>| content[8..12].copy_from_slice(&u32::MAX.to_le_bytes()) ; "tree size too big")]
+    #[test_case(|content: &mut Vec<u8>| content[8..12].copy_from_slice(&2u32.to_le_bytes()) ; "unterminated name")]
+    fn test_open_invalid_tree(corrupt: fn(&mut Vec<u8>)) {
+        let directory = TempDir::new("test_open_invalid_tree").unwrap();
+        let file_path = directory.path().join("ahud.vpk");
+        write_vpk(&file_path, 1, HUD_FILES, None);
+        let mut content = std::fs::read(&file_path).unwrap();
+        corrupt(&mut content);
+        std::fs::write(&file_path, content).unwrap();
+
+        assert!(matches!(VpkArchive::open(&file_path), Err(VpkError::InvalidTree(_))));
+    }
+
+    #[test]
+    fn test_read_corrupted_vpk_entry() {
+        let directory = TempDir::new("test_read_corrupted_vpk_entry").unwrap();
+        let file_path = directory.path().join("ahud.vpk");
+        write_vpk(&file_path, 1, &[("resource/ui/hudlayout.res", b"\"HudLayout\"\n{\n}")], None);
+        let mut content = std::fs::read(&file_path).unwrap();
+        *content.last_mut().unwrap() = b'!';
+        std::fs::write(&file_path, &content).unwrap();
+
+        let archive = VpkArchive::open(&file_path).unwrap();
+
+        assert!(matches!(
+            archive.read("resource/ui/hudlayout.res"),
+            Err(VpkError::ChecksumMismatch(_))
+        ));
+
+        content.truncate(content.len() - 4);
+        std::fs::write(&file_path, &content).unwrap();
+
+        assert!(matches!(
+            archive.read("resource/ui/hudlayout.res"),
+            Err(VpkError::TruncatedData(..))
+        ));
+    }
+}
@@ -1,12 +1,15 @@
 use crate::state::{LoadStateError, State};
-use crate::Message;
-use hudhub_core::{fetch_package, install, uninstall, FetchError, PackageEntry, HudInfo, HudName, Source, Install};
+use crate::{AddViewMessage, CatalogViewMessage, Message};
+use hudhub_core::{
+    scan_package_with, uninstall, ArchiveExtractors, Catalog, CatalogLocation, FetchError, FetchSettings, PackageEntry,
+    HttpClient, HudInfo, HudName, Source, Install, Package, Registry, Revision, SourceMetadata,
+};
 use iced::Command;
 use std::path::{Path, PathBuf};
 use tempdir::TempDir;
 
 #[derive(thiserror::Error, Debug)]
-enum ScanPackageError {
+pub enum ScanPackageError {
     #[error(transparent)]
     FetchPackageFailed(#[from] FetchError),
 
@@ -14,23 +17,115 @@ enum ScanPackageError {
     FailedToCreateTempDirectory(std::io::Error),
 }
 
-pub fn scan_package(source: Source) -> Command<Message> {
-    let source_for_future = source.clone();
+/// The HUDs found in a package, and what the source told about them.
+#[derive(Clone, Debug)]
+pub struct ScannedPackage {
+    pub hud_names: Vec<HudName>,
+    pub revision: Option<Revision>,
+    pub metadata: Option<SourceMetadata>,
+    /// The SHA-256 of the archive.
+    pub sha256: Option<String>,
+}
+
+impl From<Package> for ScannedPackage {
+    fn from(package: Package) -> Self {
+        Self {
+            hud_names: package.hud_names().cloned().collect(),
+            revision: package.revision,
+            metadata: package.metadata,
+            sha256: package.sha256,
+        }
+    }
+}
+
+/// The result of the scan of one source of a list.
+#[derive(Clone, Debug)]
+pub struct ScanOutcome {
+    pub source: Source,
+    pub result: Result<ScannedPackage, String>,
+}
+
+pub fn load_catalog(location: CatalogLocation, client: HttpClient) -> Command<Message> {
+    Command::perform(async move { Catalog::load(&location, &client).await }, |result| match result {
+        Ok(catalog) => Message::CatalogView(CatalogViewMessage::CatalogLoaded(catalog)),
+        Err(error) => Message::error("Failed to load the catalog", error),
+    })
+}
+
+pub async fn get_hud_names(source: Source, settings: &FetchSettings) -> Result<ScannedPackage, ScanPackageError> {
+    let temp_directory = TempDir::new("fetch_package_name").map_err(ScanPackageError::FailedToCreateTempDirectory)?;
+    let package = scan_package_with(source, temp_directory.path(), settings).await?;
+
+    Ok(package.into())
+}
 
+/// Scan several packages concurrently. A package failing to scan does not stop the others.
+pub async fn scan_packages(sources: Vec<Source>, settings: &FetchSettings) -> Result<Vec<ScanOutcome>, ScanPackageError> {
+    let temp_directory = TempDir::new("fetch_packages").map_err(ScanPackageError::FailedToCreateTempDirectory)?;
+    let outcomes = hudhub_core::scan_packages(sources, temp_directory.path(), settings).await;
+
+    Ok(outcomes
+        .into_iter()
+        .map(|outcome| ScanOutcome {
+            source: outcome.source,
+            result: outcome.result.map(ScannedPackage::from).map_err(|error| error.to_string()),
+        })
+        .collect())
+}
+
+/// The extensions shown by the file dialog: the archives the core can extract, and the .vpk files.
+/// The dialog only matches the last extension of a file, `gz` for `tar.gz`.
+fn archive_file_extensions() -> Vec<&'static str> {
+    let mut extensions: Vec<&'static str> = ArchiveExtractors::default()
+        .extensions()
+        .filter_map(|extension| extension.rsplit('.').next())
+        .chain(["vpk"])
+        .collect();
+
+    extensions.sort();
+    extensions.dedup();
+    extensions
+}
+
+/// Show a file dialog to pick a HUD archive or a .vpk file on disk.
+pub fn pick_archive_file() -> Command<Message> {
     Command::perform(
-        async move { get_hud_names(source_for_future).await },
-        move |result| match result {
-            Err(error) => Message::error("Failed to scan package", error),
-            Ok(hud_names) => Message::AddHuds(source, hud_names),
+        async {
+            rfd::AsyncFileDialog::new()
+                .add_filter("HUD archive", &archive_file_extensions())
+                .pick_file()
+                .await
+                .map(|file| file.path().to_path_buf())
         },
+        |file_path| Message::AddView(AddViewMessage::FileSelected(file_path)),
     )
 }
 
-async fn get_hud_names(source: Source) -> Result<Vec<HudName>, ScanPackageError> {
-    let temp_directory = TempDir::new("fetch_package_name").map_err(ScanPackageError::FailedToCreateTempDirectory)?;
-    let package = fetch_package(source.clone(), temp_directory.path()).await?;
+/// Show a file dialog to pick a text file listing sources, one per line.
+pub fn pick_source_list_file() -> Command<Message> {
+    Command::perform(
+        async {
+            rfd::AsyncFileDialog::new()
+                .add_filter("Text file", &["txt"])
+                .pick_file()
+                .await
+                .map(|file| file.path().to_path_buf())
+        },
+        |file_path| Message::AddView(AddViewMessage::SourceListSelected(file_path)),
+    )
+}
 
-    Ok(package.hud_names().cloned().collect())
+/// Show a file dialog to pick a directory containing HUDs.
+pub fn pick_hud_directory() -> Command<Message> {
+    Command::perform(
+        async {
+            rfd::AsyncFileDialog::new()
+                .pick_folder()
+                .await
+                .map(|directory| directory.path().to_path_buf())
+        },
+        |directory_path| Message::AddView(AddViewMessage::DirectorySelected(directory_path)),
+    )
 }
 
 pub fn save_state(state: State, path: impl Into<PathBuf>) -> Command<Message> {
@@ -79,17 +174,16 @@ pub fn load_state(path: impl Into<PathBuf>) -> Command<Message> {
         async move { State::load(&path).await },
         |result: Result<State, LoadStateError>| match result {
             Ok(state) => Message::StateLoaded(state),
-            Err(error) => Message::error("Failed to load application state", error),
+            Err(error) => Message::error("Failed to load application state, it will not be saved", error),
         },
     )
 }
 
-pub fn install_hud(source: Source, name: HudName, huds_directory: PathBuf) -> Command<Message> {
-    let hud_name = name.clone();
-
+/// Check the source of each HUD, to find the HUDs having a newer version.
+pub fn check_for_updates(registry: Registry, settings: FetchSettings) -> Command<Message> {
     Command::perform(
-        async move { install(source, hud_name, huds_directory).await },
-        move |result| Message::InstallationFinished(name.clone(), result),
+        async move { hudhub_core::check_for_updates(&registry, &settings).await },
+        Message::UpdatesChecked,
     )
 }
 
@@ -1,15 +1,23 @@
 use crate::commands::save_state;
-use hudhub_core::{PackageEntry, HudName, Install, Source, Url};
+use crate::commands::{ScanOutcome, ScannedPackage};
+use crate::operation::{Operation, OperationId, RunningOperation};
+use hudhub_core::{
+    parse_source_list, Catalog, CatalogEntry, CatalogLocation, DownloadCache, DownloadProgress, FetchSettings, PackageEntry,
+    HudName, Install, InstallMode, Revision, Source, UpdateCheck, UpdateStatus, Url, DEFAULT_CACHE_MAX_SIZE_BYTES,
+};
 use iced::widget::text_input;
 use iced::{
     event, subscription, window, Application as IcedApplication, Command, Element, Renderer, Settings, Subscription, Theme,
 };
 use iced_views::Views;
 use state::State;
-use ui::add_view;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use ui::{add_view, catalog_view};
 use crate::paths::{DefaultPathsProvider, TestPathsProvider, PathsProvider};
 
 mod commands;
+mod operation;
 mod state;
 mod ui;
 mod paths;
@@ -17,6 +25,7 @@ mod paths;
 enum View {
     List,
     Add(AddContext),
+    Catalog(CatalogContext),
 }
 
 pub struct AddContext {
@@ -25,6 +34,8 @@ pub struct AddContext {
     error: Option<String>,
     download_url_text_input: text_input::Id,
     scanning: bool,
+    /// The result of each source, after several sources were scanned at once.
+    summary: Vec<ScanOutcome>,
 }
 
 impl Default for AddContext {
@@ -35,29 +46,72 @@ impl Default for AddContext {
             error: None,
             download_url_text_input: text_input::Id::unique(),
             scanning: false,
+            summary: Vec::new(),
+        }
+    }
+}
+
+pub struct CatalogContext {
+    query: String,
+    query_text_input: text_input::Id,
+    error: Option<String>,
+    /// The source of the HUD being added.
+    adding: Option<Source>,
+}
+
+impl Default for CatalogContext {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            query_text_input: text_input::Id::unique(),
+            error: None,
+            adding: None,
         }
     }
 }
 
+#[derive(Clone, Debug)]
+pub enum CatalogViewMessage {
+    Show,
+    CatalogLoaded(Catalog),
+    QueryChanged(String),
+    AddHud(CatalogEntry),
+}
+
 #[derive(Clone, Debug)]
 pub enum AddViewMessage {
     Show,
     DownloadUrlChanged(String),
+    BrowseFile,
+    FileSelected(Option<PathBuf>),
+    BrowseDirectory,
+    DirectorySelected(Option<PathBuf>),
+    /// Pick a text file listing the sources to add, one per line.
+    BrowseSourceList,
+    SourceListSelected(Option<PathBuf>),
     ScanPackageToAdd(Source),
+    ScanPackagesToAdd(Vec<Source>),
 }
 
 #[derive(Clone, Debug)]
 pub enum ListViewMessage {
     HudClicked(HudName),
     RemoveHud(HudName),
+    /// Pin the checksum of the archive, so the HUD installs only if its archive did not change.
+    PinChecksum(HudName),
+    UnpinChecksum(HudName),
+    CheckForUpdates,
 }
 
 #[derive(Clone, Debug)]
 pub enum Message {
     AddView(AddViewMessage),
     ListView(ListViewMessage),
-    AddHuds(Source, Vec<HudName>),
-    Install(HudName),
+    CatalogView(CatalogViewMessage),
+    AddHuds(Source, ScannedPackage),
+    /// Several packages were scanned, the HUDs of each package scanned successfully are added.
+    HudsScanned(Vec<ScanOutcome>),
+    Install(HudName, InstallMode),
     Uninstall(HudName),
     Error(String, String),
     StateSaved,
@@ -65,6 +119,10 @@ pub enum Message {
     InstallationFinished(HudName, Install),
     UninstallationFinished(HudName),
     FoundInstalledHuds(Vec<PackageEntry>),
+    UpdatesChecked(Vec<UpdateCheck>),
+    OperationProgressed(OperationId, DownloadProgress),
+    /// An operation is done, the message is its result.
+    OperationFinished(OperationId, Box<Message>),
     Quit,
     Back,
 }
@@ -82,9 +140,52 @@ struct Application {
     is_loading: bool,
     paths_provider: Box<dyn PathsProvider>,
     testing_mode_enabled: bool,
+    catalog: Option<Catalog>,
+    catalog_location: CatalogLocation,
+    operations: Vec<RunningOperation>,
+    next_operation_id: OperationId,
+    fetch_settings: FetchSettings,
+    /// The HUDs whose source has a newer revision than the one installed.
+    updates: BTreeMap<HudName, Revision>,
+    /// The HUDs whose source could not be checked for updates, with the reason.
+    failed_update_checks: BTreeMap<HudName, String>,
+    checking_updates: bool,
+    /// The state is saved only once it was loaded: a state file that failed to load is never overwritten.
+    is_state_loaded: bool,
 }
 
 impl Application {
+    fn start_operation(&mut self, operation: Operation) {
+        self.operations.push(RunningOperation {
+            id: self.next_operation_id,
+            operation,
+            progress: None,
+        });
+        self.next_operation_id += 1;
+    }
+
+    /// The progress of the download of the first operation matching the predicate.
+    fn operation_progress(&self, predicate: impl Fn(&Operation) -> bool) -> Option<&DownloadProgress> {
+        self.operations
+            .iter()
+            .find(|running_operation| predicate(&running_operation.operation))
+            .and_then(|running_operation| running_operation.progress.as_ref())
+    }
+
+    fn add_huds(&mut self, source: &Source, package: &ScannedPackage) {
+        for hud_name in package.hud_names.iter() {
+            let is_added = self.state.registry.add(hud_name.clone(), source.clone());
+            let is_same_source = self.state.registry.get(hud_name).is_some_and(|info| &info.source == source);
+
+            // A HUD with the same name from another source keeps what was recorded from its own source.
+            if is_added || is_same_source {
+                self.state.registry.set_revision(hud_name, package.revision.clone());
+                self.state.registry.set_metadata(hud_name, package.metadata.clone());
+                self.state.registry.set_sha256(hud_name, package.sha256.clone());
+            }
+        }
+    }
+
     fn process_add_view_message(&mut self, message: AddViewMessage) -> Command<Message> {
         match message {
             AddViewMessage::Show => {
@@ -98,10 +199,13 @@ impl Application {
             AddViewMessage::DownloadUrlChanged(url) => {
                 if let Some(View::Add(context)) = self.views.current_mut() {
                     context.download_url = url.clone();
-                    context.is_form_valid = if !url.is_empty() {
-                        match Url::parse(&url) {
-                            Ok(_) => true,
-                            Err(error) => {
+                    // Several URLs can be pasted at once, one per line.
+                    context.is_form_valid = if !url.trim().is_empty() {
+                        let mut lines = url.lines().map(str::trim).filter(|line| !line.is_empty());
+
+                        match lines.find_map(|line| Url::parse(line).err()) {
+                            None => true,
+                            Some(error) => {
                                 context.error = Some(format!("Invalid URL: {}", error));
                                 false
                             }
@@ -112,11 +216,93 @@ impl Application {
                     };
                 }
             }
+            AddViewMessage::BrowseFile => {
+                return commands::pick_archive_file();
+            }
+            AddViewMessage::FileSelected(file_path) => {
+                if let Some(file_path) = file_path {
+                    return self.process_add_view_message(AddViewMessage::ScanPackageToAdd(Source::LocalFile(file_path)));
+                }
+            }
+            AddViewMessage::BrowseDirectory => {
+                return commands::pick_hud_directory();
+            }
+            AddViewMessage::DirectorySelected(directory_path) => {
+                if let Some(directory_path) = directory_path {
+                    return self.process_add_view_message(AddViewMessage::ScanPackageToAdd(Source::LocalDirectory(
+                        directory_path,
+                    )));
+                }
+            }
+            AddViewMessage::BrowseSourceList => {
+                return commands::pick_source_list_file();
+            }
+            AddViewMessage::SourceListSelected(file_path) => {
+                if let Some(file_path) = file_path {
+                    match std::fs::read_to_string(&file_path) {
+                        Ok(text) => {
+                            return self.process_add_view_message(AddViewMessage::ScanPackagesToAdd(parse_source_list(
+                                &text,
+                            )));
+                        }
+                        Err(error) => {
+                            if let Some(View::Add(context)) = self.views.current_mut() {
+                                context.error = Some(format!("Failed to read '{}': {}", file_path.display(), error));
+                            }
+                        }
+                    }
+                }
+            }
             AddViewMessage::ScanPackageToAdd(source) => {
                 if let Some(View::Add(context)) = self.views.current_mut() {
                     context.error = None;
+                    context.summary.clear();
+                    context.scanning = true;
+                    self.start_operation(Operation::ScanPackage(source));
+                }
+            }
+            AddViewMessage::ScanPackagesToAdd(sources) => {
+                if let Some(View::Add(context)) = self.views.current_mut() {
+                    context.error = None;
+                    context.summary.clear();
                     context.scanning = true;
-                    return commands::scan_package(source);
+                    self.start_operation(Operation::ScanPackages(sources));
+                }
+            }
+        }
+
+        Command::none()
+    }
+
+    fn process_catalog_view_message(&mut self, message: CatalogViewMessage) -> Command<Message> {
+        match message {
+            CatalogViewMessage::Show => {
+                let context = CatalogContext::default();
+                let focus_command = text_input::focus(context.query_text_input.clone());
+
+                self.views.push(View::Catalog(context));
+
+                if self.catalog.is_none() {
+                    let load_command = commands::load_catalog(self.catalog_location.clone(), self.fetch_settings.http.clone());
+
+                    return Command::batch([focus_command, load_command]);
+                }
+
+                return focus_command;
+            }
+            CatalogViewMessage::CatalogLoaded(catalog) => {
+                self.catalog = Some(catalog);
+            }
+            CatalogViewMessage::QueryChanged(query) => {
+                if let Some(View::Catalog(context)) = self.views.current_mut() {
+                    context.query = query;
+                }
+            }
+            CatalogViewMessage::AddHud(entry) => {
+                if let Some(View::Catalog(context)) = self.views.current_mut() {
+                    context.error = None;
+                    context.adding = Some(entry.source.clone());
+                    self.start_operation(Operation::ScanCatalogEntry(entry));
                 }
             }
         }
@@ -142,6 +328,19 @@ impl Application {
                     }
                 }
             }
+            ListViewMessage::PinChecksum(hud_name) => {
+                let sha256 = self.state.registry.get(&hud_name).and_then(|info| info.sha256.clone());
+
+                self.state.registry.set_expected_sha256(&hud_name, sha256);
+            }
+            ListViewMessage::UnpinChecksum(hud_name) => {
+                self.state.registry.set_expected_sha256(&hud_name, None);
+            }
+            ListViewMessage::CheckForUpdates => {
+                self.checking_updates = true;
+
+                return commands::check_for_updates(self.state.registry.clone(), self.fetch_settings.clone());
+            }
         }
         Command::none()
     }
@@ -163,8 +362,17 @@ impl IcedApplication for Application {
                 Box::new(TestPathsProvider::new())
             },
         };
+        let catalog_location = parse_catalog_location(std::env::args()).unwrap_or_default();
         let application_state_file_path = paths_provider.get_application_state_file_path();
         let huds_directory_path = paths_provider.get_huds_directory();
+        let fetch_settings = FetchSettings {
+            cache: Some(DownloadCache::new(
+                paths_provider.get_download_cache_directory(),
+                DEFAULT_CACHE_MAX_SIZE_BYTES,
+            )),
+            partial_downloads_directory: Some(paths_provider.get_partial_downloads_directory()),
+            ..Default::default()
+        };
 
         (
             Self {
@@ -174,6 +382,15 @@ impl IcedApplication for Application {
                 is_loading: false,
                 paths_provider,
                 testing_mode_enabled,
+                catalog: None,
+                catalog_location,
+                operations: Vec::new(),
+                next_operation_id: 0,
+                fetch_settings,
+                updates: BTreeMap::new(),
+                failed_update_checks: BTreeMap::new(),
+                checking_updates: false,
+                is_state_loaded: false,
             },
             Command::batch([
                 commands::load_state(application_state_file_path),
@@ -197,46 +414,90 @@ impl IcedApplication for Application {
             Message::ListView(message) => {
                 return self.process_list_view_message(message);
             }
-            Message::AddHuds(source, hud_names) => {
-                for hud_name in hud_names.into_iter() {
-                    self.state.registry.add(hud_name, source.clone());
+            Message::CatalogView(message) => {
+                return self.process_catalog_view_message(message);
+            }
+            Message::AddHuds(source, package) => {
+                self.add_huds(&source, &package);
+
+                match self.views.current_mut() {
+                    Some(View::Add(_context)) => {
+                        self.views.pop();
+                    }
+                    Some(View::Catalog(context)) => {
+                        context.adding = None;
+                    }
+                    _ => {}
+                }
+            }
+            Message::HudsScanned(outcomes) => {
+                for outcome in outcomes.iter() {
+                    if let Ok(package) = outcome.result.as_ref() {
+                        self.add_huds(&outcome.source, package);
+                    }
                 }
 
-                if let Some(View::Add(_context)) = self.views.current() {
-                    self.views.pop();
+                // The summary stays visible, so the sources that failed can be fixed.
+                if let Some(View::Add(context)) = self.views.current_mut() {
+                    context.scanning = false;
+                    context.summary = outcomes;
                 }
             }
             Message::Error(title, error) => {
                 println!("{}: {}", title, error);
                 self.is_loading = false;
-                if let Some(View::Add(context)) = self.views.current_mut() {
-                    context.error = Some(error);
-                    context.scanning = false;
+                match self.views.current_mut() {
+                    Some(View::Add(context)) => {
+                        context.error = Some(error);
+                        context.scanning = false;
+                    }
+                    Some(View::Catalog(context)) => {
+                        context.error = Some(error);
+                        context.adding = None;
+                    }
+                    _ => {}
                 }
             }
             Message::StateSaved => {}
             Message::StateLoaded(state) => {
                 self.state = state;
+                self.is_state_loaded = true;
+                self.checking_updates = true;
+
+                return commands::check_for_updates(self.state.registry.clone(), self.fetch_settings.clone());
             }
             Message::Quit => {
+                if !self.is_state_loaded {
+                    return window::close();
+                }
+
                 return Command::batch([
                     save_state(self.state.clone(), self.paths_provider.get_application_state_file_path()),
                     window::close(),
                 ])
             }
-            Message::Install(hud_name) => {
+            Message::Install(hud_name, mode) => {
                 if let Some(info) = self.state.registry.get(&hud_name) {
-                    if let Some(huds_directory) = self.paths_provider.get_huds_directory() {
-                        assert!(!matches!(info.install, Install::Installed { .. }));
+                    let source = info.source.clone();
+                    let expected_sha256 = info.expected_sha256.clone();
 
+                    if let Some(huds_directory) = self.paths_provider.get_huds_directory() {
                         let mut commands = Vec::new();
 
                         if let Some(installed_info) = self.state.registry.get_installed() {
-                            commands.push(commands::uninstall_hud(installed_info, huds_directory.clone()));
+                            // Installing the installed HUD again updates it in place.
+                            if installed_info.name != hud_name {
+                                commands.push(commands::uninstall_hud(installed_info, huds_directory.clone()));
+                            }
                         }
 
-                        commands.push(commands::install_hud(info.source.clone(), hud_name, huds_directory));
-
+                        self.start_operation(Operation::Install {
+                            source,
+                            name: hud_name,
+                            huds_directory,
+                            mode,
+                            expected_sha256,
+                        });
                         self.is_loading = true;
 
                         return Command::batch(commands.into_iter());
@@ -253,6 +514,12 @@ impl IcedApplication for Application {
                 }
             }
             Message::InstallationFinished(hud_name, install) => {
+                if let Install::Installed { revision, .. } = &install {
+                    self.state.registry.set_revision(&hud_name, revision.clone());
+                    self.updates.remove(&hud_name);
+                    self.failed_update_checks.remove(&hud_name);
+                }
+
                 self.state.registry.set_install(&hud_name, install);
                 self.is_loading = false;
             }
@@ -265,19 +532,48 @@ impl IcedApplication for Application {
                     if let Some(info) = self.state.registry.get(&hud_directory.name) {
                         if let Install::Installed { path, .. } = &info.install {
                             if path != &hud_directory.path {
+                                let revision = info.install.revision().cloned();
+
                                 self.state
                                     .registry
-                                    .set_install(&hud_directory.name, Install::installed_now(path));
+                                    .set_install(&hud_directory.name, Install::installed_now(path, revision));
                             }
                         }
                     } else {
                         self.state.registry.add(hud_directory.name.clone(), Source::None);
                         self.state
                             .registry
-                            .set_install(&hud_directory.name, Install::installed_now(&hud_directory.path));
+                            .set_install(&hud_directory.name, Install::installed_now(&hud_directory.path, None));
+                    }
+                }
+            }
+            Message::UpdatesChecked(checks) => {
+                self.checking_updates = false;
+                self.updates.clear();
+                self.failed_update_checks.clear();
+
+                for check in checks {
+                    match check.status {
+                        UpdateStatus::Available(revision) => {
+                            self.updates.insert(check.name, revision);
+                        }
+                        UpdateStatus::Failed(error) => {
+                            self.failed_update_checks.insert(check.name, error);
+                        }
+                        UpdateStatus::UpToDate => {}
                     }
                 }
             }
+            Message::OperationProgressed(id, progress) => {
+                if let Some(running_operation) = self.operations.iter_mut().find(|operation| operation.id == id) {
+                    running_operation.progress = Some(progress);
+                }
+            }
+            Message::OperationFinished(id, message) => {
+                self.operations.retain(|operation| operation.id != id);
+
+                return self.update(*message);
+            }
             Message::Back => {
                 self.views.pop();
             }
@@ -288,8 +584,22 @@ impl IcedApplication for Application {
 
     fn view(&self) -> Element<Self::Message, Renderer<Self::Theme>> {
         match self.views.current().expect("current view") {
-            View::List => ui::list_view::view(&self.state.registry, self.selected_hud.as_ref(), self.is_loading),
-            View::Add(context) => add_view::add_view(&context),
+            View::List => ui::list_view::view(
+                &self.state.registry,
+                self.selected_hud.as_ref(),
+                &self.updates,
+                &self.failed_update_checks,
+                self.checking_updates,
+                self.is_loading,
+                self.operation_progress(|operation| matches!(operation, Operation::Install { .. })),
+            ),
+            View::Add(context) => add_view::add_view(
+                &context,
+                self.operation_progress(|operation| {
+                    matches!(operation, Operation::ScanPackage(_) | Operation::ScanPackages(_))
+                }),
+            ),
+            View::Catalog(context) => catalog_view::catalog_view(context, self.catalog.as_ref(), &self.state.registry),
         }
     }
 
@@ -298,13 +608,37 @@ impl IcedApplication for Application {
     }
 
     fn subscription(&self) -> Subscription<Self::Message> {
-        subscription::events_with(|event, _status| {
+        let events = subscription::events_with(|event, _status| {
             if let event::Event::Window(window::Event::CloseRequested) = event {
                 return Some(Message::Quit);
             }
 
             None
-        })
+        });
+        let operations = self
+            .operations
+            .iter()
+            .map(|running_operation| {
+                operation::subscription(
+                    running_operation.id,
+                    running_operation.operation.clone(),
+                    self.fetch_settings.clone(),
+                )
+            });
+
+        Subscription::batch(std::iter::once(events).chain(operations))
+    }
+}
+
+/// Read the location of the catalog from the argument `--catalog`, either a URL or a path to a JSON file.
+fn parse_catalog_location(mut args: impl Iterator<Item = String>) -> Option<CatalogLocation> {
+    args.find(|arg| arg == "--catalog")?;
+
+    let location = args.next()?;
+
+    match Url::parse(&location) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Some(CatalogLocation::Url(location)),
+        _ => Some(CatalogLocation::File(PathBuf::from(location))),
     }
 }
 
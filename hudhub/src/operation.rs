@@ -0,0 +1,123 @@
+//! Long running operations downloading packages.
+//! An operation runs in a subscription, so it can report the progress of the download before it finishes.
+
+use crate::commands::{get_hud_names, scan_packages};
+use crate::Message;
+use hudhub_core::{install_with, CatalogEntry, DownloadProgress, FetchSettings, HudName, InstallMode, Source};
+use iced::{subscription, Subscription};
+use std::path::PathBuf;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task::JoinHandle;
+
+pub type OperationId = u64;
+
+#[derive(Clone, Debug)]
+pub enum Operation {
+    /// Scan a package to find the HUDs to add.
+    ScanPackage(Source),
+    /// Scan several packages concurrently to find the HUDs to add.
+    ScanPackages(Vec<Source>),
+    /// Scan the package of a catalog entry to find the HUDs to add.
+    ScanCatalogEntry(CatalogEntry),
+    Install {
+        source: Source,
+        name: HudName,
+        huds_directory: PathBuf,
+        mode: InstallMode,
+        /// The SHA-256 pinned for the archive.
+        expected_sha256: Option<String>,
+    },
+}
+
+impl Operation {
+    async fn run(self, settings: FetchSettings) -> Message {
+        match self {
+            Operation::ScanPackage(source) => match get_hud_names(source.clone(), &settings).await {
+                Ok(package) => Message::AddHuds(source, package),
+                Err(error) => Message::error("Failed to scan package", error),
+            },
+            Operation::ScanPackages(sources) => match scan_packages(sources, &settings).await {
+                Ok(outcomes) => Message::HudsScanned(outcomes),
+                Err(error) => Message::error("Failed to scan packages", error),
+            },
+            Operation::ScanCatalogEntry(entry) => match get_hud_names(entry.source.clone(), &settings).await {
+                Ok(mut package) => {
+                    package.metadata.get_or_insert_with(|| entry.metadata());
+
+                    Message::AddHuds(entry.source, package)
+                }
+                Err(error) => Message::error("Failed to scan package", error),
+            },
+            Operation::Install {
+                source,
+                name,
+                huds_directory,
+                mode,
+                expected_sha256,
+            } => {
+                let settings = FetchSettings {
+                    expected_sha256,
+                    ..settings
+                };
+                let install = install_with(source, name.clone(), huds_directory, mode, &settings).await;
+
+                Message::InstallationFinished(name, install)
+            }
+        }
+    }
+}
+
+/// An operation and the progress of its download.
+pub struct RunningOperation {
+    pub id: OperationId,
+    pub operation: Operation,
+    pub progress: Option<DownloadProgress>,
+}
+
+enum State {
+    Starting(Operation, FetchSettings),
+    Running {
+        progress_receiver: UnboundedReceiver<DownloadProgress>,
+        task: JoinHandle<Message>,
+    },
+    Finished,
+}
+
+pub fn subscription(id: OperationId, operation: Operation, settings: FetchSettings) -> Subscription<Message> {
+    subscription::unfold(id, State::Starting(operation, settings), move |state| run(id, state))
+}
+
+async fn run(id: OperationId, state: State) -> (Option<Message>, State) {
+    match state {
+        State::Starting(operation, settings) => {
+            let (progress_sender, progress_receiver) = tokio::sync::mpsc::unbounded_channel();
+            let settings = FetchSettings {
+                progress_sender: Some(progress_sender),
+                ..settings
+            };
+            let task = tokio::spawn(operation.run(settings));
+
+            (None, State::Running { progress_receiver, task })
+        }
+        State::Running {
+            mut progress_receiver,
+            task,
+        } => match progress_receiver.recv().await {
+            Some(progress) => (
+                Some(Message::OperationProgressed(id, progress)),
+                State::Running { progress_receiver, task },
+            ),
+            // The channel is closed when the operation is done.
+            None => {
+                let message = task
+                    .await
+                    .unwrap_or_else(|error| Message::error("Operation failed", error));
+
+                (Some(Message::OperationFinished(id, Box::new(message))), State::Finished)
+            }
+        },
+        // The subscription is dropped when the operation is removed from the application.
+        // Until then, it must not end, or it would start again.
+        State::Finished => iced::futures::future::pending().await,
+    }
+}
@@ -16,6 +16,14 @@ pub trait PathsProvider {
     fn get_huds_directory(&self) -> Option<PathBuf> {
         self.get_team_fortress_directory().map(|directory| directory.join("tf").join("custom"))
     }
+
+    fn get_download_cache_directory(&self) -> PathBuf {
+        self.get_application_directory().join("download_cache")
+    }
+
+    fn get_partial_downloads_directory(&self) -> PathBuf {
+        self.get_application_directory().join("partial_downloads")
+    }
 }
 
 /// Provides the paths found on the disk.
@@ -38,6 +46,14 @@ impl PathsProvider for DefaultPathsProvider {
         self.application_directories.config_dir.clone()
     }
 
+    fn get_download_cache_directory(&self) -> PathBuf {
+        self.application_directories.cache_dir.join("downloads")
+    }
+
+    fn get_partial_downloads_directory(&self) -> PathBuf {
+        self.application_directories.cache_dir.join("partial_downloads")
+    }
+
     fn get_team_fortress_directory(&self) -> Option<PathBuf> {
         const TEAM_FORTRESS_2_STEAM_APP_ID: u32 = 440;
 
@@ -2,17 +2,30 @@ use hudhub_core::Registry;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+/// The version of the format of the state file.
+/// The state is saved in JSON, the fields added later are read as their default value from the older files.
+/// Increment the version when a change can't be read that way, and migrate the older files in [`State::load`].
+const STATE_FORMAT_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct State {
     pub registry: Registry,
 }
 
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    version: u32,
+    state: State,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum LoadStateError {
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error("Invalid file format")]
     InvalidFileFormat,
+    #[error("The state file was saved by a newer version of HudHub (format {0})")]
+    UnsupportedVersion(u32),
 }
 
 impl State {
@@ -23,7 +36,11 @@ impl State {
             }
         }
 
-        let encoded: Vec<u8> = bincode::serialize(&state).expect("serialize state");
+        let file = StateFile {
+            version: STATE_FORMAT_VERSION,
+            state: state.clone(),
+        };
+        let encoded: Vec<u8> = serde_json::to_vec_pretty(&file).expect("serialize state");
 
         tokio::fs::write(path, encoded).await
     }
@@ -33,9 +50,226 @@ impl State {
             true => {
                 let encoded = tokio::fs::read(path).await?;
 
-                Ok(bincode::deserialize(&encoded).map_err(|_| LoadStateError::InvalidFileFormat)?)
+                Self::decode(&encoded)
             }
             false => Ok(State::default()),
         }
     }
+
+    /// Decode a state file, or a file saved with bincode by the versions before the format was versioned.
+    fn decode(encoded: &[u8]) -> Result<State, LoadStateError> {
+        if let Ok(file) = serde_json::from_slice::<StateFile>(encoded) {
+            return match file.version {
+                version if version > STATE_FORMAT_VERSION => Err(LoadStateError::UnsupportedVersion(version)),
+                _ => Ok(file.state),
+            };
+        }
+
+        bincode::deserialize::<legacy::State>(encoded)
+            .map(State::from)
+            .map_err(|_| LoadStateError::InvalidFileFormat)
+    }
+}
+
+/// The state saved with bincode by the first versions.
+/// Bincode does not store the names of the fields, so the file must be read with the exact types of that time.
+mod legacy {
+    use chrono::{DateTime, Utc};
+    use hudhub_core::HudName;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    #[derive(Deserialize)]
+    pub struct State {
+        registry: Registry,
+    }
+
+    #[derive(Deserialize)]
+    struct Registry {
+        info: BTreeMap<HudName, HudInfo>,
+    }
+
+    #[derive(Deserialize)]
+    struct HudInfo {
+        name: HudName,
+        source: Source,
+        install: Install,
+    }
+
+    #[derive(Deserialize)]
+    enum Source {
+        None,
+        DownloadUrl(String),
+    }
+
+    #[derive(Deserialize)]
+    enum Install {
+        None,
+        Installed { path: PathBuf, when: DateTime<Utc> },
+        Failed { error: String },
+    }
+
+    impl From<State> for super::State {
+        fn from(state: State) -> Self {
+            let mut registry = hudhub_core::Registry::new();
+
+            for info in state.registry.info.into_values() {
+                let source = match info.source {
+                    Source::None => hudhub_core::Source::None,
+                    Source::DownloadUrl(url) => hudhub_core::Source::DownloadUrl(url),
+                };
+                let install = match info.install {
+                    Install::None => hudhub_core::Install::None,
+                    Install::Installed { path, when } => hudhub_core::Install::Installed {
+                        path,
+                        when,
+                        revision: None,
+                    },
+                    Install::Failed { error } => hudhub_core::Install::Failed { error },
+                };
+
+                registry.add(info.name.clone(), source);
+                registry.set_install(&info.name, install);
+            }
+
+            Self { registry }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadStateError, State, StateFile, STATE_FORMAT_VERSION};
+    use chrono::TimeZone;
+    use hudhub_core::{HudName, Install, Source};
+    use serde::Serialize;
+    use std::path::PathBuf;
+
+    /// The types of the first versions, as they were serialized with bincode.
+    #[derive(Serialize)]
+    struct LegacyState {
+        registry: LegacyRegistry,
+    }
+
+    #[derive(Serialize)]
+    struct LegacyRegistry {
+        info: std::collections::BTreeMap<HudName, LegacyHudInfo>,
+    }
+
+    #[derive(Serialize)]
+    struct LegacyHudInfo {
+        name: HudName,
+        source: LegacySource,
+        install: LegacyInstall,
+    }
+
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum LegacySource {
+        None,
+        DownloadUrl(String),
+    }
+
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum LegacyInstall {
+        None,
+        Installed {
+            path: PathBuf,
+            when: chrono::DateTime<chrono::Utc>,
+        },
+        Failed {
+            error: String,
+        },
+    }
+
+    #[test]
+    fn test_load_legacy_state() {
+        let when = chrono::Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        let legacy_state = LegacyState {
+            registry: LegacyRegistry {
+                info: [(
+                    HudName::new("ahud"),
+                    LegacyHudInfo {
+                        name: HudName::new("ahud"),
+                        source: LegacySource::DownloadUrl("https://example.com/ahud.zip".into()),
+                        install: LegacyInstall::Installed {
+                            path: PathBuf::from("/huds/ahud"),
+                            when,
+                        },
+                    },
+                )]
+                .into(),
+            },
+        };
+
+        let state = State::decode(&bincode::serialize(&legacy_state).unwrap()).unwrap();
+        let info = state.registry.get(&HudName::new("ahud")).unwrap();
+
+        assert_eq!(Source::DownloadUrl("https://example.com/ahud.zip".into()), info.source);
+        assert!(matches!(
+            &info.install,
+            Install::Installed { path, when: installed_when, revision: None }
+                if path == &PathBuf::from("/huds/ahud") && installed_when == &when
+        ));
+    }
+
+    #[test]
+    fn test_load_state() {
+        let mut file = StateFile {
+            version: STATE_FORMAT_VERSION,
+            state: State::default(),
+        };
+        file.state.registry.add(
+            HudName::new("ahud"),
+            Source::DownloadUrl("https://example.com/ahud.zip".into()),
+        );
+
+        let state = State::decode(&serde_json::to_vec_pretty(&file).unwrap()).unwrap();
+
+        assert!(state.registry.get(&HudName::new("ahud")).is_some());
+    }
+
+    /// The fields added to the registry are read as their default value from the files saved before.
+    #[test]
+    fn test_load_state_without_new_fields() {
+        let encoded = br#"{
+            "version": 1,
+            "state": {
+                "registry": {
+                    "info": {
+                        "ahud": {
+                            "name": "ahud",
+                            "source": "None",
+                            "install": { "Installed": { "path": "/huds/ahud", "when": "2020-09-13T12:26:40Z" } }
+                        }
+                    }
+                }
+            }
+        }"#;
+
+        let state = State::decode(encoded).unwrap();
+        let info = state.registry.get(&HudName::new("ahud")).unwrap();
+
+        assert!(matches!(&info.install, Install::Installed { revision: None, .. }));
+    }
+
+    #[test]
+    fn test_load_newer_state() {
+        let file = StateFile {
+            version: STATE_FORMAT_VERSION + 1,
+            state: State::default(),
+        };
+
+        assert!(matches!(
+            State::decode(&serde_json::to_vec(&file).unwrap()),
+            Err(LoadStateError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_invalid_state() {
+        assert!(matches!(State::decode(b"\x01"), Err(LoadStateError::InvalidFileFormat)));
+    }
 }
@@ -1,30 +1,23 @@
-use crate::ui::DEFAULT_SPACING;
+use crate::ui::{progress, DEFAULT_SPACING};
 use crate::{AddContext, AddViewMessage, Message};
-use hudhub_core::Source;
-use iced::widget::{button, column, container, horizontal_space, row, text, text_input};
+use crate::commands::ScanOutcome;
+use hudhub_core::{parse_source_list, DownloadProgress, Source};
+use iced::widget::{button, column, container, horizontal_space, row, scrollable, text, text_input, Column};
 use iced::{Alignment, Element, Length};
 use iced_aw::floating_element::Anchor;
 use iced_aw::native::FloatingElement;
-use iced_aw::Spinner;
 
-pub fn add_view(context: &AddContext) -> Element<Message> {
+pub fn add_view<'a>(context: &'a AddContext, progress: Option<&DownloadProgress>) -> Element<'a, Message> {
     match context.scanning {
-        true => container(
-            Spinner::new()
-                .circle_radius(4.0)
-                .width(Length::Fixed(64.0))
-                .height(Length::Fixed(64.0)),
-        )
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .center_x()
-        .center_y()
-        .into(),
+        true => container(progress::download_progress(progress))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into(),
         false => {
             let mut add_button = button("Add HUB!");
-            let scan_package_message = Message::AddView(AddViewMessage::ScanPackageToAdd(Source::DownloadUrl(
-                context.download_url.clone(),
-            )));
+            let scan_package_message = scan_message(&context.download_url);
             if context.is_form_valid {
                 add_button = add_button.on_press(scan_package_message.clone());
             }
@@ -37,8 +30,12 @@ pub fn add_view(context: &AddContext) -> Element<Message> {
                 .id(context.download_url_text_input.clone())
                 .width(Length::FillPortion(3))
                 .on_submit(scan_package_message.clone()),
+                button("Browse...").on_press(Message::AddView(AddViewMessage::BrowseFile)),
+                button("Folder...").on_press(Message::AddView(AddViewMessage::BrowseDirectory)),
+                button("List...").on_press(Message::AddView(AddViewMessage::BrowseSourceList)),
                 horizontal_space(Length::Fill)
-            ];
+            ]
+            .spacing(DEFAULT_SPACING);
 
             let mut main_column = column![input].align_items(Alignment::Center).spacing(DEFAULT_SPACING);
 
@@ -48,6 +45,10 @@ pub fn add_view(context: &AddContext) -> Element<Message> {
 
             main_column = main_column.push(add_button);
 
+            if !context.summary.is_empty() {
+                main_column = main_column.push(scrollable(summary(&context.summary)).height(Length::Fill));
+            }
+
             let content = container(main_column).height(Length::Fill).center_y();
 
             FloatingElement::new(content, || button("X").on_press(Message::Back).into())
@@ -56,3 +57,40 @@ pub fn add_view(context: &AddContext) -> Element<Message> {
         }
     }
 }
+
+/// The message scanning the sources entered, one per line.
+fn scan_message(text: &str) -> Message {
+    let mut sources = parse_source_list(text);
+
+    match sources.len() {
+        1 => Message::AddView(AddViewMessage::ScanPackageToAdd(sources.remove(0))),
+        _ => Message::AddView(AddViewMessage::ScanPackagesToAdd(sources)),
+    }
+}
+
+/// The result of each source scanned, so the sources that failed can be fixed and added again.
+fn summary(outcomes: &[ScanOutcome]) -> Column<Message> {
+    outcomes.iter().fold(column![].spacing(DEFAULT_SPACING), |lines, outcome| {
+        let line = match outcome.result.as_ref() {
+            Ok(package) => format!(
+                "Added {}: {}",
+                source_label(&outcome.source),
+                package.hud_names.iter().map(|name| name.to_string()).collect::<Vec<String>>().join(", ")
+            ),
+            Err(error) => format!("Failed {}: {}", source_label(&outcome.source), error),
+        };
+
+        lines.push(text(line))
+    })
+}
+
+fn source_label(source: &Source) -> String {
+    match source {
+        Source::None => String::new(),
+        Source::DownloadUrl(url) => url.clone(),
+        Source::GitHub { owner, repo, .. } => format!("github.com/{}/{}", owner, repo),
+        Source::LocalFile(path) | Source::LocalDirectory(path) => path.display().to_string(),
+        Source::GameBanana { mod_id, .. } => format!("gamebanana.com/mods/{}", mod_id),
+        Source::Custom { location, .. } => location.clone(),
+    }
+}
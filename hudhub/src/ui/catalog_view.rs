@@ -0,0 +1,87 @@
+use crate::ui::DEFAULT_SPACING;
+use crate::{CatalogContext, CatalogViewMessage, Message};
+use hudhub_core::{Catalog, CatalogEntry, Registry};
+use iced::widget::{button, column, container, horizontal_space, row, scrollable, text, text_input};
+use iced::{Alignment, Element, Length};
+use iced_aw::floating_element::Anchor;
+use iced_aw::native::FloatingElement;
+use iced_aw::Spinner;
+
+pub fn catalog_view<'a>(
+    context: &'a CatalogContext,
+    catalog: Option<&'a Catalog>,
+    registry: &'a Registry,
+) -> Element<'a, Message> {
+    let content: Element<Message> = match catalog {
+        None if context.error.is_some() => container(text(context.error.as_deref().unwrap_or_default()))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into(),
+        None => container(
+            Spinner::new()
+                .circle_radius(4.0)
+                .width(Length::Fixed(64.0))
+                .height(Length::Fixed(64.0)),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x()
+        .center_y()
+        .into(),
+        Some(catalog) => {
+            let search_input = text_input("Search HUDs", &context.query, |text| {
+                Message::CatalogView(CatalogViewMessage::QueryChanged(text))
+            })
+            .id(context.query_text_input.clone());
+            let mut main_column = column![search_input].spacing(DEFAULT_SPACING);
+
+            if let Some(error) = context.error.as_ref() {
+                main_column = main_column.push(text(error));
+            }
+
+            let entries = catalog
+                .search(&context.query)
+                .fold(column![].spacing(DEFAULT_SPACING), |c, entry| {
+                    c.push(catalog_entry_view(context, entry, registry))
+                });
+
+            main_column.push(scrollable(entries).height(Length::Fill)).into()
+        }
+    };
+
+    let content = container(content)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(DEFAULT_SPACING);
+
+    FloatingElement::new(content, || button("X").on_press(Message::Back).into())
+        .anchor(Anchor::NorthEast)
+        .into()
+}
+
+fn catalog_entry_view<'a>(context: &CatalogContext, entry: &'a CatalogEntry, registry: &Registry) -> Element<'a, Message> {
+    let mut description = column![text(&entry.name).size(24)];
+
+    if !entry.author.is_empty() {
+        description = description.push(text(format!("by {}", entry.author)));
+    }
+
+    if !entry.tags.is_empty() {
+        description = description.push(text(entry.tags.join(", ")).size(16));
+    }
+
+    let is_added = registry.iter().any(|info| info.source == entry.source);
+    let add_button = match (is_added, context.adding.as_ref()) {
+        (true, _) => button("Added"),
+        (false, Some(adding)) if adding == &entry.source => button("Adding..."),
+        (false, Some(_)) => button("Add"),
+        (false, None) => button("Add").on_press(Message::CatalogView(CatalogViewMessage::AddHud(entry.clone()))),
+    };
+
+    row![description, horizontal_space(Length::Fill), add_button]
+        .align_items(Alignment::Center)
+        .spacing(DEFAULT_SPACING)
+        .into()
+}
@@ -1,24 +1,42 @@
-use crate::ui::{color, DEFAULT_SPACING};
-use crate::{AddViewMessage, ListViewMessage, Message};
-use hudhub_core::{HudInfo, HudName, Install, Registry};
+use crate::ui::{color, progress, DEFAULT_SPACING};
+use crate::{AddViewMessage, CatalogViewMessage, ListViewMessage, Message};
+use hudhub_core::{DownloadProgress, HudInfo, HudName, Install, InstallMode, Registry, Revision, Source};
 use iced::widget::{button, column, container, row, scrollable, text, vertical_space, Container};
 use iced::{theme, Alignment, Background, Color, Element, Length, Theme};
-use iced_aw::Spinner;
 use crate::ui::color::brighter_by;
+use std::collections::BTreeMap;
 
-pub fn view<'a>(registry: &'a Registry, selected_hud: Option<&'a HudName>, is_loading: bool) -> Element<'a, Message> {
+pub fn view<'a>(
+    registry: &'a Registry,
+    selected_hud: Option<&'a HudName>,
+    updates: &'a BTreeMap<HudName, Revision>,
+    failed_update_checks: &'a BTreeMap<HudName, String>,
+    checking_updates: bool,
+    is_loading: bool,
+    progress: Option<&DownloadProgress>,
+) -> Element<'a, Message> {
     row![
-        hud_list(registry, selected_hud).width(Length::FillPortion(4)).height(Length::Fill),
-        action_list(registry, selected_hud, is_loading).width(Length::Fill).height(Length::Fill)
+        hud_list(registry, selected_hud, updates, failed_update_checks).width(Length::FillPortion(4)).height(Length::Fill),
+        action_list(registry, selected_hud, updates, failed_update_checks, checking_updates, is_loading, progress)
+            .width(Length::Fill)
+            .height(Length::Fill)
     ]
     .spacing(DEFAULT_SPACING)
     .padding(DEFAULT_SPACING)
     .into()
 }
 
-fn action_list<'a>(registry: &'a Registry, selected_hud: Option<&'a HudName>, is_loading: bool) -> Container<'a, Message> {
+fn action_list<'a>(
+    registry: &'a Registry,
+    selected_hud: Option<&'a HudName>,
+    updates: &'a BTreeMap<HudName, Revision>,
+    failed_update_checks: &'a BTreeMap<HudName, String>,
+    checking_updates: bool,
+    is_loading: bool,
+    progress: Option<&DownloadProgress>,
+) -> Container<'a, Message> {
     if is_loading {
-        return container(Spinner::new())
+        return container(progress::download_progress(progress))
             .style(theme::Container::Custom(Box::new(BoxContainer{})))
             .center_x()
             .center_y()
@@ -31,17 +49,74 @@ fn action_list<'a>(registry: &'a Registry, selected_hud: Option<&'a HudName>, is
     if let Some(selected_hud) = selected_hud {
         match registry.get(selected_hud) {
             None => {}
-            Some(info) => match info.install {
-                Install::None => {
-                    content = content.push(button("Install").on_press(Message::Install(info.name.clone())));
+            Some(info) => {
+                if let Some(metadata) = info.metadata.as_ref() {
+                    content = content.push(text(&metadata.title).size(24));
+
+                    if let Some(author) = metadata.author.as_ref() {
+                        content = content.push(text(format!("by {}", author)));
+                    }
+
+                    if let Some(version) = metadata.version.as_ref() {
+                        content = content.push(text(format!("Version {}", version)));
+                    }
                 }
-                Install::Installed { .. } => {
-                    content = content.push(button("Uninstall").on_press(Message::Uninstall(info.name.clone())));
+
+                if let Some(sha256) = info.sha256.as_ref() {
+                    content = content.push(text(format!("SHA-256 {}", sha256)).size(12));
                 }
-                Install::Failed { .. } => {
-                    content = content.push(button("Install").on_press(Message::Install(info.name.clone())));
+
+                if let Some(error) = failed_update_checks.get(&info.name) {
+                    content = content.push(text(format!("Failed to check for updates: {}", error)).size(14));
+                }
+
+                match (info.expected_sha256.as_ref(), info.sha256.as_ref()) {
+                    (Some(_), _) => {
+                        content = content.push(
+                            button("Unpin checksum")
+                                .on_press(Message::ListView(ListViewMessage::UnpinChecksum(info.name.clone()))),
+                        );
+                    }
+                    (None, Some(_)) => {
+                        content = content.push(
+                            button("Pin checksum").on_press(Message::ListView(ListViewMessage::PinChecksum(info.name.clone()))),
+                        );
+                    }
+                    (None, None) => {}
+                }
+
+                let is_local_directory = matches!(info.source, Source::LocalDirectory(_));
+
+                match &info.install {
+                    Install::None | Install::Failed { .. } => {
+                        content = content
+                            .push(button("Install").on_press(Message::Install(info.name.clone(), InstallMode::Copy)));
+
+                        if is_local_directory {
+                            content = content
+                                .push(button("Link").on_press(Message::Install(info.name.clone(), InstallMode::Link)));
+                        }
+                    }
+                    Install::Installed { path, .. } => {
+                        // Installing the installed HUD again replaces it in place.
+                        if updates.contains_key(&info.name) {
+                            content = content.push(
+                                button("Update")
+                                    .on_press(Message::Install(info.name.clone(), InstallMode::Copy))
+                                    .style(theme::Button::Positive),
+                            );
+                        }
+
+                        content = content.push(button("Uninstall").on_press(Message::Uninstall(info.name.clone())));
+
+                        // A linked HUD is always up to date.
+                        if is_local_directory && !path.is_symlink() {
+                            content = content
+                                .push(button("Sync").on_press(Message::Install(info.name.clone(), InstallMode::Copy)));
+                        }
+                    }
                 }
-            },
+            }
         }
 
         content =
@@ -49,6 +124,15 @@ fn action_list<'a>(registry: &'a Registry, selected_hud: Option<&'a HudName>, is
     }
 
     content = content.push(vertical_space(Length::Fill));
+    content = content.push(match checking_updates {
+        true => button("Checking for updates..."),
+        false => button("Check for updates").on_press(Message::ListView(ListViewMessage::CheckForUpdates)),
+    });
+    content = content.push(
+        button(text("Browse HUDs").size(24))
+            .padding(12)
+            .on_press(Message::CatalogView(CatalogViewMessage::Show)),
+    );
     content = content.push(
         button(text("Add HUD").size(36))
             .padding(16)
@@ -80,10 +164,20 @@ impl container::StyleSheet for BoxContainer {
     }
 }
 
-fn hud_list<'a>(registry: &'a Registry, selected_hud: Option<&'a HudName>) -> Container<'a, Message> {
+fn hud_list<'a>(
+    registry: &'a Registry,
+    selected_hud: Option<&'a HudName>,
+    updates: &'a BTreeMap<HudName, Revision>,
+    failed_update_checks: &'a BTreeMap<HudName, String>,
+) -> Container<'a, Message> {
     container(scrollable(
         registry.iter().fold(column![].spacing(DEFAULT_SPACING), |c, info| {
-            c.push(hud_info_view(info, selected_hud == Some(&info.name)))
+            c.push(hud_info_view(
+                info,
+                selected_hud == Some(&info.name),
+                updates.contains_key(&info.name),
+                failed_update_checks.contains_key(&info.name),
+            ))
         }),
     )).style(theme::Container::Custom(Box::new(BoxContainer{}))).padding(DEFAULT_SPACING)
 }
@@ -137,8 +231,16 @@ impl button::StyleSheet for UnselectedInfoView {
     }
 }
 
-fn hud_info_view(info: &HudInfo, is_selected: bool) -> Element<Message> {
-    let mut button = button(row![text(&info.name)])
+fn hud_info_view(info: &HudInfo, is_selected: bool, has_update: bool, update_check_failed: bool) -> Element<Message> {
+    let mut label = row![text(&info.name)].spacing(DEFAULT_SPACING);
+
+    if has_update {
+        label = label.push(text("Update available").size(14));
+    } else if update_check_failed {
+        label = label.push(text("Update check failed").size(14));
+    }
+
+    let mut button = button(label)
         .on_press(Message::ListView(ListViewMessage::HudClicked(info.name.clone())))
         .width(Length::Fill)
         .style(theme::Button::Custom(match is_selected {
@@ -1,5 +1,7 @@
 pub mod add_view;
+pub mod catalog_view;
 mod color;
 pub mod list_view;
+mod progress;
 
 const DEFAULT_SPACING: u16 = 8;
@@ -0,0 +1,56 @@
+use crate::ui::DEFAULT_SPACING;
+use crate::Message;
+use hudhub_core::DownloadProgress;
+use iced::widget::{column, progress_bar, text};
+use iced::{Alignment, Element, Length};
+use iced_aw::Spinner;
+
+/// Show the progress of a download as a progress bar.
+/// A spinner is shown until the download starts, or if the size of the file is unknown.
+pub fn download_progress<'a>(progress: Option<&DownloadProgress>) -> Element<'a, Message> {
+    let mut content = column![].spacing(DEFAULT_SPACING).align_items(Alignment::Center);
+
+    match progress.and_then(|progress| progress.ratio()) {
+        Some(ratio) => content = content.push(progress_bar(0.0..=1.0, ratio).width(Length::Fixed(256.0))),
+        None => {
+            content = content.push(
+                Spinner::new()
+                    .circle_radius(4.0)
+                    .width(Length::Fixed(64.0))
+                    .height(Length::Fixed(64.0)),
+            )
+        }
+    }
+
+    if let Some(progress) = progress {
+        let downloaded = match progress.total_bytes {
+            Some(total_bytes) => format!("{} / {}", format_bytes(progress.downloaded_bytes), format_bytes(total_bytes)),
+            None => format_bytes(progress.downloaded_bytes),
+        };
+
+        content = content.push(text(format!(
+            "{} ({}/s)",
+            downloaded,
+            format_bytes(progress.bytes_per_second as u64)
+        )));
+    }
+
+    content.into()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[unit]),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
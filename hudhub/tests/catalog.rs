@@ -0,0 +1,10 @@
+//! The catalog published at the root of the repository, downloaded by default by the application.
+
+use hudhub_core::Catalog;
+
+#[test]
+fn test_default_catalog() {
+    let catalog = Catalog::from_json(include_str!("../../catalog.json")).unwrap();
+
+    assert!(!catalog.entries.is_empty());
+}